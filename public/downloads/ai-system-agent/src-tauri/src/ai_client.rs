@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 
+use crate::policy::CommandPolicy;
+use crate::risk::{self, RiskAssessment};
+use crate::simulate::{self, Simulation};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AIProvider {
     pub id: String,
@@ -35,9 +39,18 @@ pub struct SuggestedCommand {
     pub command: String,
     pub args: Vec<String>,
     pub description: String,
+    /// As stated by the model; see `local_assessment` for the backend's own view
     pub requires_sudo: bool,
     pub risk_level: String, // "low", "medium", "high"
     pub explanation: String,
+    /// Only checks the result of the commands before it, e.g. `systemctl is-active`
+    #[serde(default)]
+    pub verify: bool,
+    pub local_assessment: RiskAssessment,
+    /// Where the model's risk or sudo claims differ from `local_assessment`
+    pub disagreements: Vec<String>,
+    /// Dry-run form offered before approval; `None` when no safe simulation exists
+    pub simulation: Option<Simulation>,
 }
 
 const SYSTEM_PROMPT: &str = r#"Você é o AI System Agent, um assistente especializado em administração de sistemas Linux e Windows.
@@ -67,6 +80,8 @@ Quando sugerir comandos, use este formato JSON no final da sua resposta:
 }
 ```
 
+Quando os comandos só fazem sentido em sequência (atualizar, instalar, habilitar, iniciar), liste-os na ordem de execução. Para comandos que apenas conferem o resultado dos anteriores (ex.: "systemctl is-active nginx"), inclua "verify": true.
+
 Lembre-se: O usuário terá que aprovar CADA comando antes da execução. Seja claro sobre o que cada comando faz."#;
 
 #[tauri::command]
@@ -332,6 +347,9 @@ async fn send_anthropic(
 }
 
 fn extract_commands(content: &str) -> Vec<SuggestedCommand> {
+    // A broken user policy is reported when executing; assess with the built-in one meanwhile
+    let policy = CommandPolicy::load().unwrap_or_default();
+
     // Try to find JSON block with commands
     if let Some(start) = content.find("```json") {
         if let Some(end) = content[start..].find("```\n").or_else(|| content[start..].rfind("```")) {
@@ -341,20 +359,32 @@ fn extract_commands(content: &str) -> Vec<SuggestedCommand> {
                     return commands
                         .iter()
                         .filter_map(|cmd| {
+                            let command = cmd["command"].as_str()?.to_string();
+                            let args: Vec<String> = cmd["args"]
+                                .as_array()?
+                                .iter()
+                                .filter_map(|a| a.as_str().map(String::from))
+                                .collect();
+                            let requires_sudo = cmd["requires_sudo"].as_bool().unwrap_or(false);
+                            let risk_level = cmd["risk_level"]
+                                .as_str()
+                                .unwrap_or("medium")
+                                .to_string();
+                            let local_assessment = risk::assess(&policy, &command, &args);
+                            let disagreements =
+                                risk::disagreements(&risk_level, requires_sudo, &local_assessment);
+                            let simulation = simulate::plan(&command, &args, &policy).ok();
                             Some(SuggestedCommand {
-                                command: cmd["command"].as_str()?.to_string(),
-                                args: cmd["args"]
-                                    .as_array()?
-                                    .iter()
-                                    .filter_map(|a| a.as_str().map(String::from))
-                                    .collect(),
+                                command,
+                                args,
                                 description: cmd["description"].as_str()?.to_string(),
-                                requires_sudo: cmd["requires_sudo"].as_bool().unwrap_or(false),
-                                risk_level: cmd["risk_level"]
-                                    .as_str()
-                                    .unwrap_or("medium")
-                                    .to_string(),
+                                requires_sudo,
+                                risk_level,
                                 explanation: cmd["explanation"].as_str().unwrap_or("").to_string(),
+                                verify: cmd["verify"].as_bool().unwrap_or(false),
+                                local_assessment,
+                                disagreements,
+                                simulation,
                             })
                         })
                         .collect();
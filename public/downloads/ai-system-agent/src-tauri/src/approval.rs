@@ -0,0 +1,448 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use tauri::api::dialog::{MessageDialogBuilder, MessageDialogButtons, MessageDialogKind};
+use tauri::{State, Window};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::commands;
+use crate::executor::ExecutionContext;
+use crate::pipeline::PipelineStage;
+use crate::plan::PlanStep;
+use crate::policy::{PolicyVerdict, RiskLevel};
+use crate::sandbox::SandboxMode;
+use crate::session::SessionStore;
+
+/// How long an approval stays valid after the user confirms
+const APPROVAL_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ApprovalToken {
+    pub token: String,
+    pub expires_at: String,
+}
+
+struct Approval {
+    hash: String,
+    expires: Instant,
+}
+
+/// One-time tokens issued when the user approves a command, keyed by token
+#[derive(Default)]
+pub struct ApprovalStore {
+    approvals: Mutex<HashMap<String, Approval>>,
+}
+
+impl ApprovalStore {
+    pub fn issue(
+        &self,
+        command: &str,
+        args: &[String],
+        requires_sudo: bool,
+        context: &ExecutionContext,
+    ) -> ApprovalToken {
+        self.issue_hash(command_hash(command, args, requires_sudo, context))
+    }
+
+    /// Token for running `stages` as one pipeline
+    pub fn issue_pipeline(
+        &self,
+        stages: &[PipelineStage],
+        context: &ExecutionContext,
+    ) -> ApprovalToken {
+        self.issue_hash(pipeline_hash(stages, context))
+    }
+
+    /// Token for running `steps` in order as one plan
+    pub fn issue_plan(&self, steps: &[PlanStep], context: &ExecutionContext) -> ApprovalToken {
+        self.issue_hash(plan_hash(steps, context))
+    }
+
+    /// Token for restoring snapshot `id`, or only `paths` among its files
+    pub fn issue_restore(&self, id: &str, paths: Option<&[String]>) -> ApprovalToken {
+        self.issue_hash(restore_hash(id, paths))
+    }
+
+    fn issue_hash(&self, hash: String) -> ApprovalToken {
+        let token = Uuid::new_v4().simple().to_string();
+        let now = Instant::now();
+
+        let mut approvals = self.approvals.lock().unwrap();
+        approvals.retain(|_, approval| approval.expires > now);
+        approvals.insert(
+            token.clone(),
+            Approval {
+                hash,
+                expires: now + APPROVAL_TTL,
+            },
+        );
+
+        let ttl = chrono::Duration::from_std(APPROVAL_TTL).unwrap_or_default();
+        ApprovalToken {
+            token,
+            expires_at: (Utc::now() + ttl).to_rfc3339(),
+        }
+    }
+
+    /// Use up `token`, checking it was issued for exactly this command.
+    ///
+    /// The token is discarded even when the check fails, so it cannot be retried.
+    pub fn consume(
+        &self,
+        token: &str,
+        command: &str,
+        args: &[String],
+        requires_sudo: bool,
+        context: &ExecutionContext,
+    ) -> Result<(), String> {
+        self.consume_hash(token, &command_hash(command, args, requires_sudo, context))
+    }
+
+    /// Use up `token`, checking it was issued for exactly this pipeline
+    pub fn consume_pipeline(
+        &self,
+        token: &str,
+        stages: &[PipelineStage],
+        context: &ExecutionContext,
+    ) -> Result<(), String> {
+        self.consume_hash(token, &pipeline_hash(stages, context))
+    }
+
+    /// Use up `token`, checking it was issued for exactly this plan
+    pub fn consume_plan(
+        &self,
+        token: &str,
+        steps: &[PlanStep],
+        context: &ExecutionContext,
+    ) -> Result<(), String> {
+        self.consume_hash(token, &plan_hash(steps, context))
+    }
+
+    /// Use up `token`, checking it was issued for exactly this restore
+    pub fn consume_restore(
+        &self,
+        token: &str,
+        id: &str,
+        paths: Option<&[String]>,
+    ) -> Result<(), String> {
+        self.consume_hash(token, &restore_hash(id, paths))
+    }
+
+    fn consume_hash(&self, token: &str, hash: &str) -> Result<(), String> {
+        let approval = self
+            .approvals
+            .lock()
+            .unwrap()
+            .remove(token)
+            .ok_or("⚠️ SEGURANÇA: Aprovação inválida ou já utilizada")?;
+
+        if approval.expires <= Instant::now() {
+            return Err("⚠️ SEGURANÇA: Aprovação expirada, confirme o comando novamente".into());
+        }
+        if approval.hash != hash {
+            return Err("⚠️ SEGURANÇA: A aprovação não corresponde a este comando".into());
+        }
+        Ok(())
+    }
+}
+
+/// Whether a command needs the native confirmation: high risk, or run elevated
+fn needs_confirmation(verdict: &PolicyVerdict, requires_sudo: bool) -> bool {
+    verdict.risk >= RiskLevel::High || requires_sudo || verdict.requires_sudo
+}
+
+/// Confirm high-risk or elevated commands in a native dialog, out of the webview's reach.
+///
+/// Returns whether the dialog was shown.
+pub async fn confirm_natively(
+    window: &Window,
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    verdict: &PolicyVerdict,
+) -> Result<bool, String> {
+    if !needs_confirmation(verdict, requires_sudo) {
+        return Ok(false);
+    }
+
+    let mut message = String::from("argv:\n");
+    push_argv(&mut message, command, args);
+    ask(
+        window,
+        message,
+        verdict,
+        requires_sudo || verdict.requires_sudo,
+    )
+    .await
+}
+
+/// Same as `confirm_natively` for a pipeline, listing every stage; `verdict` covers all of them
+pub async fn confirm_pipeline_natively(
+    window: &Window,
+    stages: &[PipelineStage],
+    verdict: &PolicyVerdict,
+) -> Result<bool, String> {
+    if !needs_confirmation(verdict, false) {
+        return Ok(false);
+    }
+
+    let mut message = String::new();
+    for (i, stage) in stages.iter().enumerate() {
+        message.push_str(&format!("Etapa {}:\n", i + 1));
+        push_argv(&mut message, &stage.command, &stage.args);
+    }
+    ask(window, message, verdict, verdict.requires_sudo).await
+}
+
+// Debug formatting quotes every argument, so embedded spaces and control characters show
+fn push_argv(message: &mut String, command: &str, args: &[String]) {
+    for (i, arg) in std::iter::once(command)
+        .chain(args.iter().map(String::as_str))
+        .enumerate()
+    {
+        message.push_str(&format!("  [{}] {:?}\n", i, arg));
+    }
+}
+
+/// Show the dialog without blocking a runtime worker while it is open
+async fn ask(
+    window: &Window,
+    mut message: String,
+    verdict: &PolicyVerdict,
+    elevated: bool,
+) -> Result<bool, String> {
+    message.push_str(&format!(
+        "\nRisco: {}",
+        verdict.risk.as_str().to_uppercase()
+    ));
+    if elevated {
+        message.push_str(" (privilégios elevados)");
+    }
+    message.push_str("\nMotivos:\n");
+    for reason in verdict.reason.split("; ").filter(|r| !r.is_empty()) {
+        message.push_str(&format!("  - {}\n", reason));
+    }
+    message.push_str("\nDeseja executar este comando?");
+
+    if show(
+        window,
+        "Confirmar comando de alto risco",
+        message,
+        "Executar",
+    )
+    .await
+    {
+        Ok(true)
+    } else {
+        Err("Execução cancelada pelo usuário".into())
+    }
+}
+
+/// Whether the user pressed `confirm`; a dialog dropped without an answer counts as cancelled
+async fn show(window: &Window, title: &str, message: String, confirm: &str) -> bool {
+    let (answer, answered) = oneshot::channel();
+    MessageDialogBuilder::new(title, message)
+        .parent(window)
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelWithLabels(
+            confirm.into(),
+            "Cancelar".into(),
+        ))
+        .show(move |confirmed| {
+            let _ = answer.send(confirmed);
+        });
+    answered.await.unwrap_or(false)
+}
+
+/// Confirm a snapshot restore in a native dialog, listing the files it overwrites or removes.
+/// `elevated` are the paths written with elevated privileges.
+pub async fn confirm_restore_natively(
+    window: &Window,
+    id: &str,
+    paths: &[String],
+    elevated: &[String],
+) -> Result<(), String> {
+    let mut message = format!("Snapshot {}:\n", id);
+    for path in paths {
+        let marker = if elevated.contains(path) {
+            " (privilégios elevados)"
+        } else {
+            ""
+        };
+        message.push_str(&format!("  {:?}{}\n", path, marker));
+    }
+    message.push_str("\nO conteúdo atual destes arquivos será substituído. Deseja restaurar?");
+
+    if show(
+        window,
+        "Confirmar restauração de snapshot",
+        message,
+        "Restaurar",
+    )
+    .await
+    {
+        Ok(())
+    } else {
+        Err("Restauração cancelada pelo usuário".into())
+    }
+}
+
+/// Confirm in a native dialog a change of `sandbox_mode` that confines fewer commands,
+/// so the webview cannot turn the sandbox off through `save_config`
+pub async fn confirm_sandbox_mode_natively(
+    window: &Window,
+    current: SandboxMode,
+    requested: SandboxMode,
+) -> Result<(), String> {
+    let message = format!(
+        "O modo do sandbox vai mudar de '{}' para '{}'.\n\n\
+         Comandos somente leitura poderão rodar sem isolamento. Deseja continuar?",
+        current.as_str(),
+        requested.as_str()
+    );
+    if show(window, "Enfraquecer o sandbox", message, "Alterar").await {
+        Ok(())
+    } else {
+        Err("Alteração do sandbox cancelada pelo usuário".into())
+    }
+}
+
+/// SHA-256 over an unambiguous encoding of what the user approved
+fn command_hash(
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    context: &ExecutionContext,
+) -> String {
+    let encoded = serde_json::to_vec(&(command, args, requires_sudo, context)).unwrap_or_default();
+    format!("{:x}", Sha256::digest(&encoded))
+}
+
+fn pipeline_hash(stages: &[PipelineStage], context: &ExecutionContext) -> String {
+    let encoded = serde_json::to_vec(&("pipeline", stages, context)).unwrap_or_default();
+    format!("{:x}", Sha256::digest(&encoded))
+}
+
+fn plan_hash(steps: &[PlanStep], context: &ExecutionContext) -> String {
+    let encoded = serde_json::to_vec(&("plan", steps, context)).unwrap_or_default();
+    format!("{:x}", Sha256::digest(&encoded))
+}
+
+fn restore_hash(id: &str, paths: Option<&[String]>) -> String {
+    let encoded = serde_json::to_vec(&("restore", id, paths)).unwrap_or_default();
+    format!("{:x}", Sha256::digest(&encoded))
+}
+
+/// Called by the UI once the user confirms a command; the token is then passed to `execute_command`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn request_approval(
+    command: String,
+    args: Vec<String>,
+    requires_sudo: bool,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    approvals: State<'_, ApprovalStore>,
+    sessions: State<'_, SessionStore>,
+) -> Result<ApprovalToken, String> {
+    // No point approving something the policy will refuse anyway
+    let context = sessions.context(session_id.as_deref(), cwd, env, requires_sudo)?;
+    context.validate(requires_sudo)?;
+    commands::check_policy(&command, &args, context.cwd.as_deref())?;
+    Ok(approvals.issue(&command, &args, requires_sudo, &context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn token_is_accepted_once_for_the_approved_command() {
+        let approvals = ApprovalStore::default();
+        let context = ExecutionContext::default();
+        let token = approvals.issue("apt", &args(&["update"]), true, &context);
+
+        assert!(approvals
+            .consume(&token.token, "apt", &args(&["update"]), true, &context)
+            .is_ok());
+        assert!(approvals
+            .consume(&token.token, "apt", &args(&["update"]), true, &context)
+            .is_err());
+    }
+
+    #[test]
+    fn token_does_not_cover_another_command() {
+        let approvals = ApprovalStore::default();
+        let context = ExecutionContext::default();
+        let token = approvals.issue("apt", &args(&["update"]), false, &context);
+
+        let other = approvals.consume(
+            &token.token,
+            "apt",
+            &args(&["remove", "nginx"]),
+            false,
+            &context,
+        );
+        assert!(other.is_err());
+        // A failed check still spends the token
+        assert!(approvals
+            .consume(&token.token, "apt", &args(&["update"]), false, &context)
+            .is_err());
+    }
+
+    #[test]
+    fn token_does_not_cover_elevation_or_another_context() {
+        let approvals = ApprovalStore::default();
+        let context = ExecutionContext::default();
+        let token = approvals.issue("ls", &args(&["/var"]), false, &context);
+        assert!(approvals
+            .consume(&token.token, "ls", &args(&["/var"]), true, &context)
+            .is_err());
+
+        let token = approvals.issue("ls", &args(&["/var"]), false, &context);
+        let elsewhere = ExecutionContext::new(Some("/tmp".to_string()), None);
+        assert!(approvals
+            .consume(&token.token, "ls", &args(&["/var"]), false, &elsewhere)
+            .is_err());
+    }
+
+    #[test]
+    fn expired_token_is_refused() {
+        let approvals = ApprovalStore::default();
+        let context = ExecutionContext::default();
+        let token = approvals.issue("ls", &[], false, &context);
+        approvals
+            .approvals
+            .lock()
+            .unwrap()
+            .get_mut(&token.token)
+            .unwrap()
+            .expires = Instant::now();
+
+        let expired = approvals.consume(&token.token, "ls", &[], false, &context);
+        assert!(expired.unwrap_err().contains("expirada"));
+    }
+
+    #[test]
+    fn restore_token_covers_the_selected_files_only() {
+        let approvals = ApprovalStore::default();
+        let id = "0b7f3c0e-93c4-4a53-9b1e-4f8f6f0f3d2a";
+        let paths = args(&["/etc/nginx/nginx.conf"]);
+
+        let token = approvals.issue_restore(id, Some(&paths));
+        assert!(approvals.consume_restore(&token.token, id, None).is_err());
+        let token = approvals.issue_restore(id, Some(&paths));
+        assert!(approvals
+            .consume_restore(&token.token, id, Some(&paths))
+            .is_ok());
+    }
+}
@@ -0,0 +1,584 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::Utc;
+use tauri::State;
+
+use crate::commands::CommandResult;
+use crate::config;
+use crate::executor::LimitKind;
+use crate::pipeline::StageStatus;
+use crate::policy::RiskLevel;
+
+const AUDIT_FILE: &str = "audit.jsonl";
+/// Sequence number and hash of the last entry, so truncating the log is detected
+const HEAD_FILE: &str = "audit.head";
+/// `prev_hash` of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// How an execution was authorized
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditApproval {
+    /// SHA-256 of the one-time approval token, never the token itself
+    pub token_digest: String,
+    pub risk: RiskLevel,
+    pub reason: String,
+    /// Whether the user also confirmed in the native dialog
+    pub native_confirmation: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub timestamp: String,
+    pub host: String,
+    /// Label of the window that requested the execution
+    pub requester: String,
+    pub approval: AuditApproval,
+    pub command_id: Option<String>,
+    pub argv: Vec<String>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Names of the explicitly set environment variables
+    #[serde(default)]
+    pub env: Vec<String>,
+    pub elevated: bool,
+    /// Argv and exit status of each stage when the execution was a pipeline
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageStatus>,
+    pub exit_code: Option<i32>,
+    pub cancelled: bool,
+    pub limit_hit: Option<LimitKind>,
+    pub duration_ms: Option<u64>,
+    pub stdout_sha256: Option<String>,
+    pub stderr_sha256: Option<String>,
+    /// Set when the command could not be run at all
+    pub error: Option<String>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// Entry for one execution attempt; `seq` and the hashes are filled in on append
+    pub fn execution(
+        requester: &str,
+        approval: AuditApproval,
+        command: &str,
+        args: &[String],
+        elevated: bool,
+        outcome: &Result<CommandResult, String>,
+    ) -> Self {
+        let argv = std::iter::once(command.to_string())
+            .chain(args.iter().cloned())
+            .collect();
+        let mut entry = AuditEntry::new(requester, approval, argv, elevated);
+
+        match outcome {
+            Ok(result) => {
+                entry.command_id = Some(result.id.clone());
+                entry.cwd = result.cwd.clone();
+                entry.env = result.env.clone();
+                entry.exit_code = Some(result.exit_code);
+                entry.cancelled = result.cancelled;
+                entry.limit_hit = result.limit_hit;
+                entry.duration_ms = Some(result.duration_ms);
+                entry.stdout_sha256 = result.stdout_sha256.clone();
+                entry.stderr_sha256 = result.stderr_sha256.clone();
+            }
+            Err(e) => entry.error = Some(e.clone()),
+        }
+        entry
+    }
+
+    /// Entry for restoring snapshot `id`; the argv lists the files put back,
+    /// or those selected when the restore failed
+    pub fn restore(
+        requester: &str,
+        approval: AuditApproval,
+        id: &str,
+        paths: &[String],
+        elevated: bool,
+        outcome: &Result<Vec<String>, String>,
+    ) -> Self {
+        let restored = outcome.as_deref().unwrap_or(paths);
+        let argv = ["restore_snapshot".to_string(), id.to_string()]
+            .into_iter()
+            .chain(restored.iter().cloned())
+            .collect();
+        let mut entry = AuditEntry::new(requester, approval, argv, elevated);
+        match outcome {
+            Ok(_) => entry.exit_code = Some(0),
+            Err(e) => entry.error = Some(e.clone()),
+        }
+        entry
+    }
+
+    fn new(requester: &str, approval: AuditApproval, argv: Vec<String>, elevated: bool) -> Self {
+        AuditEntry {
+            seq: 0,
+            timestamp: Utc::now().to_rfc3339(),
+            host: hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_else(|| "unknown".to_string()),
+            requester: requester.to_string(),
+            approval,
+            command_id: None,
+            argv,
+            cwd: None,
+            env: vec![],
+            elevated,
+            stages: vec![],
+            exit_code: None,
+            cancelled: false,
+            limit_hit: None,
+            duration_ms: None,
+            stdout_sha256: None,
+            stderr_sha256: None,
+            error: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct AuditHead {
+    seq: u64,
+    hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub entries: u64,
+    /// Sequence number of the first entry that failed the check
+    pub broken_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl AuditVerification {
+    fn broken(entries: u64, broken_at: Option<u64>, error: String) -> Self {
+        Self {
+            valid: false,
+            entries,
+            broken_at,
+            error: Some(error),
+        }
+    }
+}
+
+/// Append-only JSON lines log, each entry hash-chained to the previous one
+#[derive(Default)]
+pub struct AuditLog {
+    // Head of the chain, loaded on first use (`None` until then); also serializes
+    // appends so sequence numbers and the chain stay consistent
+    head: Mutex<Option<Option<AuditHead>>>,
+    /// Directory holding the log; the app data directory when unset
+    dir: Option<PathBuf>,
+}
+
+impl AuditLog {
+    /// A log kept in `dir` instead of the app data directory
+    #[cfg(test)]
+    fn in_dir(dir: PathBuf) -> Self {
+        Self {
+            head: Mutex::new(None),
+            dir: Some(dir),
+        }
+    }
+
+    pub fn append(&self, mut entry: AuditEntry) -> Result<(), String> {
+        let mut guard = self.head.lock().unwrap();
+        let head = match guard.as_ref() {
+            Some(head) => head.clone(),
+            None => self.reconcile_head()?,
+        };
+        *guard = Some(head.clone());
+
+        entry.seq = head.as_ref().map_or(0, |h| h.seq + 1);
+        entry.prev_hash = head.map_or_else(|| GENESIS_HASH.to_string(), |h| h.hash);
+
+        let mut value = serde_json::to_value(&entry)
+            .map_err(|e| format!("Erro ao serializar auditoria: {}", e))?;
+        let hash = entry_hash(&mut value)?;
+        value["hash"] = serde_json::Value::String(hash.clone());
+
+        let mut file = open_log(&self.audit_path()?)?;
+        writeln!(file, "{}", value)
+            .and_then(|_| file.sync_data())
+            .map_err(|e| format!("Erro ao gravar auditoria: {}", e))?;
+
+        // The entry is in the log, so the chain moves on even if the head file cannot be
+        // written; the next append, or the next start, writes it again
+        let head = AuditHead {
+            seq: entry.seq,
+            hash,
+        };
+        *guard = Some(Some(head.clone()));
+        save_head(&self.head_path()?, &head)
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Result<Vec<AuditEntry>, String> {
+        let _guard = self.head.lock().unwrap();
+
+        let path = self.audit_path()?;
+        if !path.exists() {
+            return Ok(vec![]);
+        }
+
+        let file = fs::File::open(&path).map_err(|e| format!("Erro ao ler auditoria: {}", e))?;
+        BufReader::new(file)
+            .lines()
+            .map(|line| {
+                let line = line.map_err(|e| format!("Erro ao ler auditoria: {}", e))?;
+                serde_json::from_str(&line).map_err(|e| format!("Erro ao parsear auditoria: {}", e))
+            })
+            .collect()
+    }
+
+    /// Walk the whole chain, checking sequence numbers, links, hashes and the head file
+    pub fn verify(&self) -> Result<AuditVerification, String> {
+        let mut guard = self.head.lock().unwrap();
+        let head = match guard.as_ref() {
+            Some(head) => head.clone(),
+            None => self.reconcile_head()?,
+        };
+        *guard = Some(head.clone());
+
+        let path = self.audit_path()?;
+        if !path.exists() {
+            return Ok(match head {
+                Some(_) => AuditVerification::broken(
+                    0,
+                    None,
+                    "O log de auditoria foi removido".to_string(),
+                ),
+                None => AuditVerification {
+                    valid: true,
+                    entries: 0,
+                    broken_at: None,
+                    error: None,
+                },
+            });
+        }
+
+        let file = fs::File::open(&path).map_err(|e| format!("Erro ao ler auditoria: {}", e))?;
+        let mut entries = 0;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Erro ao ler auditoria: {}", e))?;
+            let expected_seq = entries;
+
+            let Ok(mut value) = serde_json::from_str::<serde_json::Value>(&line) else {
+                return Ok(AuditVerification::broken(
+                    entries,
+                    Some(expected_seq),
+                    format!("Entrada {} não é JSON válido", expected_seq),
+                ));
+            };
+            let seq = value["seq"].as_u64();
+            let stored_hash = value["hash"].as_str().unwrap_or_default().to_string();
+            let stored_prev = value["prev_hash"].as_str().unwrap_or_default().to_string();
+
+            if seq != Some(expected_seq) {
+                return Ok(AuditVerification::broken(
+                    entries,
+                    Some(expected_seq),
+                    format!("Entrada {} ausente ou fora de ordem", expected_seq),
+                ));
+            }
+            if stored_prev != prev_hash {
+                return Ok(AuditVerification::broken(
+                    entries,
+                    Some(expected_seq),
+                    format!("Entrada {} não está ligada à anterior", expected_seq),
+                ));
+            }
+            if entry_hash(&mut value)? != stored_hash {
+                return Ok(AuditVerification::broken(
+                    entries,
+                    Some(expected_seq),
+                    format!("Entrada {} foi alterada", expected_seq),
+                ));
+            }
+
+            prev_hash = stored_hash;
+            entries += 1;
+        }
+
+        // The chain alone cannot tell that entries were cut from the end
+        let consistent = match &head {
+            Some(head) => entries == head.seq + 1 && prev_hash == head.hash,
+            None => entries == 0,
+        };
+        if !consistent {
+            return Ok(AuditVerification::broken(
+                entries,
+                Some(entries),
+                "O log de auditoria foi truncado ou o cabeçalho não corresponde".to_string(),
+            ));
+        }
+
+        Ok(AuditVerification {
+            valid: true,
+            entries,
+            broken_at: None,
+            error: None,
+        })
+    }
+
+    fn audit_path(&self) -> Result<PathBuf, String> {
+        Ok(self.dir()?.join(AUDIT_FILE))
+    }
+
+    fn head_path(&self) -> Result<PathBuf, String> {
+        Ok(self.dir()?.join(HEAD_FILE))
+    }
+
+    fn dir(&self) -> Result<PathBuf, String> {
+        match &self.dir {
+            Some(dir) => Ok(dir.clone()),
+            None => config::get_data_dir(),
+        }
+    }
+
+    /// The head file, or the last entry of the log when that is the one entry the head
+    /// file missed because saving it failed after the entry was written.
+    ///
+    /// Any other mismatch is left alone for `verify` to report, so cutting entries from
+    /// the end of the log is still detected.
+    fn reconcile_head(&self) -> Result<Option<AuditHead>, String> {
+        let head = load_head(&self.head_path()?)?;
+        let path = self.audit_path()?;
+        if !path.exists() {
+            return Ok(head);
+        }
+
+        let file = fs::File::open(&path).map_err(|e| format!("Erro ao ler auditoria: {}", e))?;
+        let Some(last) = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty())
+            .last()
+        else {
+            return Ok(head);
+        };
+        let Ok(last) = serde_json::from_str::<serde_json::Value>(&last) else {
+            return Ok(head);
+        };
+
+        let next_seq = head.as_ref().map_or(0, |h| h.seq + 1);
+        let next_prev = head.as_ref().map_or(GENESIS_HASH, |h| h.hash.as_str());
+        let missed =
+            last["seq"].as_u64() == Some(next_seq) && last["prev_hash"].as_str() == Some(next_prev);
+        match last["hash"].as_str() {
+            Some(hash) if missed => {
+                let head = AuditHead {
+                    seq: next_seq,
+                    hash: hash.to_string(),
+                };
+                save_head(&self.head_path()?, &head)?;
+                Ok(Some(head))
+            }
+            _ => Ok(head),
+        }
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// Hash of an entry without its own `hash` field; `prev_hash` is part of it, forming the chain
+fn entry_hash(value: &mut serde_json::Value) -> Result<String, String> {
+    let object = value
+        .as_object_mut()
+        .ok_or("Entrada de auditoria inválida")?;
+    let hash = object.remove("hash");
+    let encoded =
+        serde_json::to_vec(object).map_err(|e| format!("Erro ao serializar auditoria: {}", e))?;
+    if let Some(hash) = hash {
+        object.insert("hash".to_string(), hash);
+    }
+    Ok(sha256_hex(&encoded))
+}
+
+fn open_log(path: &Path) -> Result<fs::File, String> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .map_err(|e| format!("Erro ao abrir auditoria: {}", e))
+}
+
+fn load_head(path: &Path) -> Result<Option<AuditHead>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Erro ao ler cabeçalho da auditoria: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Erro ao parsear cabeçalho da auditoria: {}", e))
+}
+
+fn save_head(path: &Path, head: &AuditHead) -> Result<(), String> {
+    let content = serde_json::to_string(head)
+        .map_err(|e| format!("Erro ao serializar cabeçalho da auditoria: {}", e))?;
+
+    let tmp_path = path.with_extension("head.tmp");
+    fs::write(&tmp_path, content)
+        .map_err(|e| format!("Erro ao salvar cabeçalho da auditoria: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Erro ao salvar cabeçalho da auditoria: {}", e))
+}
+
+#[tauri::command]
+pub fn verify_audit_log(audit: State<'_, AuditLog>) -> Result<AuditVerification, String> {
+    audit.verify()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Log directory of one test, removed when dropped
+    struct LogDir(PathBuf);
+
+    impl LogDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("audit-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn log(&self) -> String {
+            fs::read_to_string(self.0.join(AUDIT_FILE)).unwrap()
+        }
+
+        fn rewrite_log(&self, change: impl FnOnce(&mut Vec<&str>)) {
+            let log = self.log();
+            let mut lines: Vec<&str> = log.lines().collect();
+            change(&mut lines);
+            fs::write(self.0.join(AUDIT_FILE), lines.join("\n") + "\n").unwrap();
+        }
+    }
+
+    impl Drop for LogDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn entry(command: &str) -> AuditEntry {
+        let approval = AuditApproval {
+            token_digest: sha256_hex(b"token"),
+            risk: RiskLevel::Low,
+            reason: String::new(),
+            native_confirmation: false,
+        };
+        AuditEntry::execution("main", approval, command, &[], false, &Err("x".into()))
+    }
+
+    /// A log holding entries for `ls`, `df` and `ps`
+    fn filled_log(dir: &LogDir) -> AuditLog {
+        let audit = AuditLog::in_dir(dir.0.clone());
+        for command in ["ls", "df", "ps"] {
+            audit.append(entry(command)).unwrap();
+        }
+        audit
+    }
+
+    #[test]
+    fn entry_hash_leaves_out_the_hash_field() {
+        let mut value = serde_json::json!({"seq": 0, "prev_hash": GENESIS_HASH});
+        let hash = entry_hash(&mut value).unwrap();
+        value["hash"] = serde_json::Value::String("anything".to_string());
+        assert_eq!(entry_hash(&mut value).unwrap(), hash);
+        value["seq"] = serde_json::json!(1);
+        assert_ne!(entry_hash(&mut value).unwrap(), hash);
+    }
+
+    #[test]
+    fn appended_entries_form_a_valid_chain() {
+        let dir = LogDir::new();
+        let verification = filled_log(&dir).verify().unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.entries, 3);
+
+        // A fresh log picks the chain up from the head file
+        let reopened = AuditLog::in_dir(dir.0.clone());
+        reopened.append(entry("id")).unwrap();
+        let entries = reopened.entries().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[3].seq, 3);
+        assert_eq!(entries[3].prev_hash, entries[2].hash);
+    }
+
+    #[test]
+    fn edited_entry_breaks_the_chain() {
+        let dir = LogDir::new();
+        let audit = filled_log(&dir);
+        let log = dir.log();
+        let edited = log.replace("\"df\"", "\"rm\"");
+        fs::write(dir.0.join(AUDIT_FILE), edited).unwrap();
+
+        let verification = audit.verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(1));
+    }
+
+    #[test]
+    fn removed_entry_breaks_the_sequence() {
+        let dir = LogDir::new();
+        let audit = filled_log(&dir);
+        dir.rewrite_log(|lines| {
+            lines.remove(1);
+        });
+
+        assert_eq!(audit.verify().unwrap().broken_at, Some(1));
+    }
+
+    #[test]
+    fn truncation_is_caught_by_the_head_file() {
+        let dir = LogDir::new();
+        filled_log(&dir);
+        dir.rewrite_log(|lines| {
+            lines.pop();
+        });
+
+        // A fresh log must not adopt the shortened log as the new head
+        let verification = AuditLog::in_dir(dir.0.clone()).verify().unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.broken_at, Some(2));
+    }
+
+    #[test]
+    fn entry_the_head_file_missed_is_adopted() {
+        let dir = LogDir::new();
+        filled_log(&dir);
+        // As if saving the head failed after the last entry was written
+        let log = dir.log();
+        let second: serde_json::Value = serde_json::from_str(log.lines().nth(1).unwrap()).unwrap();
+        let stale = AuditHead {
+            seq: 1,
+            hash: second["hash"].as_str().unwrap().to_string(),
+        };
+        save_head(&dir.0.join(HEAD_FILE), &stale).unwrap();
+
+        let reopened = AuditLog::in_dir(dir.0.clone());
+        assert!(reopened.verify().unwrap().valid);
+        assert_eq!(load_head(&dir.0.join(HEAD_FILE)).unwrap().unwrap().seq, 2);
+    }
+}
@@ -0,0 +1,478 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, FixedOffset, Utc};
+use tauri::api::dialog::FileDialogBuilder;
+use tauri::{State, Window};
+use tokio::sync::oneshot;
+
+use crate::audit::{AuditEntry, AuditLog};
+use crate::history;
+use crate::policy::RiskLevel;
+
+const APP_NAME: &str = "ai-system-agent";
+/// Private enterprise number reserved for documentation (RFC 5612), used for our SD-ID
+const SD_ID: &str = "exec@32473";
+/// Syslog facility 13, "log audit"
+const SYSLOG_FACILITY: u8 = 13;
+const CSV_HEADER: &str = "seq,timestamp,host,requester,argv,elevated,risk,native_confirmation,exit_code,cancelled,limit_hit,duration_ms,stdout_sha256,stderr_sha256,error,hash";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+    /// RFC 5424 lines
+    Syslog,
+    /// ArcSight Common Event Format
+    Cef,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Syslog => "log",
+            ExportFormat::Cef => "cef",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ExportDestination {
+    /// Hand the export back to the caller
+    #[default]
+    Return,
+    /// A file the user picks in a native save dialog; the webview never names the path
+    File,
+    /// Local syslog daemon socket (`/dev/log`); only for `syslog` and `cef`
+    Syslog,
+}
+
+/// Dates accept RFC 3339 or `YYYY-MM-DD`, like the history filters
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Case-insensitive host name
+    pub host: Option<String>,
+    pub destination: ExportDestination,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditExport {
+    pub entries: usize,
+    /// Only set for `ExportDestination::Return`
+    pub content: Option<String>,
+    /// Where the export was saved, for `ExportDestination::File`
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Build the export and deliver it; `file` is where `ExportDestination::File` writes
+pub fn export(
+    entries: &[AuditEntry],
+    options: &ExportOptions,
+    file: Option<&Path>,
+) -> Result<AuditExport, String> {
+    if matches!(options.destination, ExportDestination::Syslog)
+        && !matches!(options.format, ExportFormat::Syslog | ExportFormat::Cef)
+    {
+        return Err("O envio ao syslog só aceita os formatos syslog e cef".to_string());
+    }
+
+    let from = options
+        .from
+        .as_deref()
+        .map(|d| history::parse_date(d, false))
+        .transpose()?;
+    let to = options
+        .to
+        .as_deref()
+        .map(|d| history::parse_date(d, true))
+        .transpose()?;
+    let host = options.host.as_deref().map(str::to_lowercase);
+
+    let selected: Vec<&AuditEntry> = entries
+        .iter()
+        .filter(|entry| {
+            if host
+                .as_deref()
+                .is_some_and(|host| entry.host.to_lowercase() != host)
+            {
+                return false;
+            }
+            if from.is_some() || to.is_some() {
+                let Ok(timestamp) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                    return false;
+                };
+                if from.is_some_and(|from| timestamp < from) || to.is_some_and(|to| timestamp > to)
+                {
+                    return false;
+                }
+            }
+            true
+        })
+        .collect();
+
+    let lines: Vec<String> = match options.format {
+        ExportFormat::Json => {
+            let json = serde_json::to_string_pretty(&selected)
+                .map_err(|e| format!("Erro ao serializar exportação: {}", e))?;
+            vec![json]
+        }
+        ExportFormat::Csv => std::iter::once(CSV_HEADER.to_string())
+            .chain(selected.iter().map(|entry| to_csv(entry)))
+            .collect(),
+        ExportFormat::Syslog => selected.iter().map(|entry| to_syslog(entry)).collect(),
+        ExportFormat::Cef => selected.iter().map(|entry| to_cef(entry)).collect(),
+    };
+
+    let mut path = None;
+    let content = match &options.destination {
+        ExportDestination::Return => Some(join_lines(&lines)),
+        ExportDestination::File => {
+            let file = file.ok_or("Nenhum arquivo escolhido para a exportação")?;
+            fs::write(file, join_lines(&lines))
+                .map_err(|e| format!("Erro ao salvar exportação: {}", e))?;
+            path = Some(file.display().to_string());
+            None
+        }
+        ExportDestination::Syslog => {
+            // CEF travels as the message of an RFC 5424 envelope
+            let messages: Vec<String> = match options.format {
+                ExportFormat::Cef => selected
+                    .iter()
+                    .zip(&lines)
+                    .map(|(entry, cef)| syslog_envelope(entry, "-", cef))
+                    .collect(),
+                _ => lines,
+            };
+            send_to_local_syslog(&messages)?;
+            None
+        }
+    };
+
+    Ok(AuditExport {
+        entries: selected.len(),
+        content,
+        path,
+    })
+}
+
+fn join_lines(lines: &[String]) -> String {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    content
+}
+
+fn failed(entry: &AuditEntry) -> bool {
+    entry.error.is_some() || entry.exit_code != Some(0) || entry.limit_hit.is_some()
+}
+
+fn optional<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(T::to_string).unwrap_or_default()
+}
+
+fn limit_name(entry: &AuditEntry) -> String {
+    entry
+        .limit_hit
+        .and_then(|kind| serde_json::to_value(kind).ok())
+        .and_then(|value| value.as_str().map(String::from))
+        .unwrap_or_default()
+}
+
+fn to_csv(entry: &AuditEntry) -> String {
+    [
+        entry.seq.to_string(),
+        entry.timestamp.clone(),
+        entry.host.clone(),
+        entry.requester.clone(),
+        entry.argv.join(" "),
+        entry.elevated.to_string(),
+        entry.approval.risk.as_str().to_string(),
+        entry.approval.native_confirmation.to_string(),
+        optional(&entry.exit_code),
+        entry.cancelled.to_string(),
+        limit_name(entry),
+        optional(&entry.duration_ms),
+        optional(&entry.stdout_sha256),
+        optional(&entry.stderr_sha256),
+        optional(&entry.error),
+        entry.hash.clone(),
+    ]
+    .iter()
+    .map(|field| csv_field(field))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Quote a field when it holds a separator, quote or line break (RFC 4180).
+///
+/// Text a spreadsheet would read as a formula, like an argv starting with `=` or `-`,
+/// is prefixed with `'`; numbers such as a `-1` exit code are left as they are.
+fn csv_field(value: &str) -> String {
+    let number = value.parse::<f64>().is_ok_and(f64::is_finite);
+    let formula = value.starts_with(['=', '+', '-', '@', '\t', '\r']) && !number;
+    let value = if formula {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn to_syslog(entry: &AuditEntry) -> String {
+    let params = [
+        ("seq", entry.seq.to_string()),
+        ("requester", entry.requester.clone()),
+        ("elevated", entry.elevated.to_string()),
+        ("risk", entry.approval.risk.as_str().to_string()),
+        (
+            "nativeConfirmation",
+            entry.approval.native_confirmation.to_string(),
+        ),
+        ("exitCode", optional(&entry.exit_code)),
+        ("cancelled", entry.cancelled.to_string()),
+        ("limitHit", limit_name(entry)),
+        ("durationMs", optional(&entry.duration_ms)),
+        ("stdoutSha256", optional(&entry.stdout_sha256)),
+        ("stderrSha256", optional(&entry.stderr_sha256)),
+        ("hash", entry.hash.clone()),
+    ];
+    let structured_data = format!(
+        "[{}{}]",
+        SD_ID,
+        params
+            .iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| format!(" {}=\"{}\"", name, sd_escape(value)))
+            .collect::<String>()
+    );
+
+    let mut message = entry.argv.join(" ");
+    if let Some(error) = &entry.error {
+        message.push_str(&format!(" ({})", error));
+    }
+    syslog_envelope(entry, &structured_data, &message)
+}
+
+/// `<PRI>1 TIMESTAMP HOSTNAME APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+fn syslog_envelope(entry: &AuditEntry, structured_data: &str, message: &str) -> String {
+    // notice for clean runs, warning otherwise
+    let severity = if failed(entry) { 4 } else { 5 };
+    format!(
+        "<{}>1 {} {} {} {} EXEC {} {}",
+        SYSLOG_FACILITY * 8 + severity,
+        syslog_timestamp(&entry.timestamp),
+        header_field(&entry.host, 255),
+        APP_NAME,
+        std::process::id(),
+        structured_data,
+        message.replace(['\n', '\r'], " ")
+    )
+}
+
+/// RFC 5424 allows at most microsecond precision
+fn syslog_timestamp(timestamp: &str) -> String {
+    DateTime::parse_from_rfc3339(timestamp)
+        .map(|t: DateTime<FixedOffset>| t.to_rfc3339_opts(chrono::SecondsFormat::Micros, true))
+        .unwrap_or_else(|_| "-".to_string())
+}
+
+/// Header fields are printable ASCII without spaces, `-` when empty
+fn header_field(value: &str, max_len: usize) -> String {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max_len)
+        .collect();
+    if cleaned.is_empty() {
+        "-".to_string()
+    } else {
+        cleaned
+    }
+}
+
+fn sd_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace(']', "\\]")
+}
+
+fn to_cef(entry: &AuditEntry) -> String {
+    let severity = match entry.approval.risk {
+        RiskLevel::Low => 3,
+        RiskLevel::Medium => 5,
+        RiskLevel::High => 8,
+    };
+    let rt = DateTime::parse_from_rfc3339(&entry.timestamp)
+        .map(|t| t.timestamp_millis().to_string())
+        .unwrap_or_default();
+    let outcome = if failed(entry) { "failure" } else { "success" };
+
+    // Custom fields go as `csN=value` plus `csNLabel=name`, skipping empty ones
+    let custom = [
+        ("cs1", "requester", entry.requester.clone()),
+        ("cs2", "argv", entry.argv.join(" ")),
+        ("cs3", "stdoutSha256", optional(&entry.stdout_sha256)),
+        ("cs4", "stderrSha256", optional(&entry.stderr_sha256)),
+        ("cs5", "entryHash", entry.hash.clone()),
+        ("cs6", "elevated", entry.elevated.to_string()),
+        ("cn1", "exitCode", optional(&entry.exit_code)),
+        ("cn2", "durationMs", optional(&entry.duration_ms)),
+        ("cn3", "seq", entry.seq.to_string()),
+    ];
+    let mut fields = vec![
+        ("rt".to_string(), rt),
+        ("dvchost".to_string(), entry.host.clone()),
+        ("outcome".to_string(), outcome.to_string()),
+        ("reason".to_string(), optional(&entry.error)),
+    ];
+    for (key, label, value) in custom {
+        if !value.is_empty() {
+            fields.push((format!("{}Label", key), label.to_string()));
+            fields.push((key.to_string(), value));
+        }
+    }
+    let extension = fields
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("{}={}", key, cef_extension_escape(value)))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "CEF:0|AI System Agent|{}|{}|exec|{}|{}|{}",
+        APP_NAME,
+        env!("CARGO_PKG_VERSION"),
+        cef_header_escape(&format!("Execução de comando ({})", outcome)),
+        severity,
+        extension
+    )
+}
+
+fn cef_header_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+fn cef_extension_escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(unix)]
+fn send_to_local_syslog(messages: &[String]) -> Result<(), String> {
+    use std::os::unix::net::UnixDatagram;
+
+    let socket =
+        UnixDatagram::unbound().map_err(|e| format!("Erro ao criar socket do syslog: {}", e))?;
+    // Linux uses /dev/log, macOS /var/run/syslog
+    ["/dev/log", "/var/run/syslog"]
+        .iter()
+        .find_map(|path| socket.connect(path).ok())
+        .ok_or("Socket local do syslog não encontrado")?;
+
+    for message in messages {
+        socket
+            .send(message.as_bytes())
+            .map_err(|e| format!("Erro ao enviar ao syslog: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn send_to_local_syslog(_messages: &[String]) -> Result<(), String> {
+    Err("O envio ao syslog local não é suportado nesta plataforma".to_string())
+}
+
+/// Ask where to save the export in a native dialog
+async fn choose_export_file(window: &Window, format: ExportFormat) -> Result<PathBuf, String> {
+    let extension = format.extension();
+    let file_name = format!("audit-{}.{}", Utc::now().format("%Y%m%d-%H%M%S"), extension);
+    let (chosen, picked) = oneshot::channel();
+    FileDialogBuilder::new()
+        .set_parent(window)
+        .set_title("Exportar auditoria")
+        .set_file_name(&file_name)
+        .add_filter(extension, &[extension])
+        .save_file(move |path| {
+            let _ = chosen.send(path);
+        });
+
+    picked
+        .await
+        .ok()
+        .flatten()
+        .ok_or_else(|| "Exportação cancelada pelo usuário".to_string())
+}
+
+#[tauri::command]
+pub async fn export_audit_log(
+    window: Window,
+    options: Option<ExportOptions>,
+    audit: State<'_, AuditLog>,
+) -> Result<AuditExport, String> {
+    let options = options.unwrap_or_default();
+    let file = match options.destination {
+        ExportDestination::File => Some(choose_export_file(&window, options.format).await?),
+        _ => None,
+    };
+    export(&audit.entries()?, &options, file.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("ls -la"), "ls -la");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("diz \"oi\""), "\"diz \"\"oi\"\"\"");
+        assert_eq!(csv_field("linha\nnova"), "\"linha\nnova\"");
+    }
+
+    #[test]
+    fn csv_fields_never_start_a_formula() {
+        assert_eq!(
+            csv_field("=HYPERLINK(\"http://x\")"),
+            "\"'=HYPERLINK(\"\"http://x\"\")\""
+        );
+        assert_eq!(csv_field("+cmd|' /C calc'!A0"), "'+cmd|' /C calc'!A0");
+        assert_eq!(csv_field("-rf"), "'-rf");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\t=1"), "'\t=1");
+        // Numbers are not formulas
+        assert_eq!(csv_field("-1"), "-1");
+        assert_eq!(csv_field("+0.5"), "+0.5");
+        assert_eq!(csv_field("-inf"), "'-inf");
+    }
+
+    #[test]
+    fn structured_data_values_escape_quotes_backslashes_and_brackets() {
+        assert_eq!(sd_escape(r#"a"b\c]d"#), r#"a\"b\\c\]d"#);
+        assert_eq!(sd_escape("[ok]"), r"[ok\]");
+    }
+
+    #[test]
+    fn cef_escapes_header_and_extension_separators() {
+        assert_eq!(cef_header_escape(r"a|b\c"), r"a\|b\\c");
+        assert_eq!(cef_extension_escape("k=v\\x\nnova\r"), r"k\=v\\x\nnova\r");
+        // Pipes only separate header fields
+        assert_eq!(cef_extension_escape("a|b"), "a|b");
+    }
+}
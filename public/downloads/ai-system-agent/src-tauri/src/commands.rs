@@ -1,8 +1,23 @@
 use serde::{Deserialize, Serialize};
-use std::process::Command;
-use chrono::Utc;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tauri::{Manager, State, Window};
 use uuid::Uuid;
 
+use crate::approval::{self, ApprovalStore};
+use crate::audit::{self, AuditApproval, AuditEntry, AuditLog};
+use crate::config;
+use crate::decode::OutputEncoding;
+use crate::executor::{self, ExecutionContext, ExecutionLimits, LimitKind};
+use crate::helper::HelperStore;
+use crate::history::HistoryStore;
+use crate::policy::{CommandPolicy, PolicyVerdict};
+use crate::process_registry::ProcessRegistry;
+use crate::queue::{CommandQueue, QueueStatus};
+use crate::session::SessionStore;
+use crate::snapshot;
+use crate::usage::ResourceUsage;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CommandResult {
     pub id: String,
@@ -14,6 +29,44 @@ pub struct CommandResult {
     pub exit_code: i32,
     pub executed_at: String,
     pub duration_ms: u64,
+    #[serde(default)]
+    pub cancelled: bool,
+    #[serde(default)]
+    pub limit_hit: Option<LimitKind>,
+    #[serde(default)]
+    pub cwd: Option<String>,
+    /// Names of the variables set explicitly for this run; values are never stored
+    #[serde(default)]
+    pub env: Vec<String>,
+    /// Snapshot of the files the command could change, taken before it ran
+    #[serde(default)]
+    pub snapshot_id: Option<String>,
+    /// Whether stdout or stderr was cut at the inline cap
+    #[serde(default)]
+    pub truncated: bool,
+    /// Handle of the complete output for `read_command_output`
+    #[serde(default)]
+    pub output_handle: Option<String>,
+    /// Encoding the output was decoded from
+    #[serde(default)]
+    pub encoding: Option<String>,
+    /// Base64 of the bytes `stdout` was decoded from, when encoding the text again would not
+    /// give them back (invalid sequences replaced); escape sequences alone do not count
+    #[serde(default)]
+    pub stdout_raw: Option<String>,
+    #[serde(default)]
+    pub stderr_raw: Option<String>,
+    /// SHA-256 of the bytes the command wrote to stdout, before any cap or decoding
+    #[serde(default)]
+    pub stdout_sha256: Option<String>,
+    #[serde(default)]
+    pub stderr_sha256: Option<String>,
+    /// Whether the command ran in the read-only sandbox
+    #[serde(default)]
+    pub sandboxed: bool,
+    /// CPU time, memory and I/O of the process; `None` where the platform does not report it
+    #[serde(default)]
+    pub usage: Option<ResourceUsage>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,126 +78,322 @@ pub struct SystemInfo {
     pub username: String,
 }
 
-// Command whitelist for security
-const ALLOWED_COMMANDS: &[&str] = &[
-    // Package managers
-    "apt", "apt-get", "dpkg", "dnf", "yum", "pacman", "zypper",
-    // Container tools
-    "docker", "docker-compose", "podman",
-    // Service management
-    "systemctl", "service", "journalctl",
-    // File operations (read-only)
-    "cat", "ls", "head", "tail", "grep", "find", "which", "whereis",
-    // Network diagnostics
-    "ping", "curl", "wget", "netstat", "ss", "ip",
-    // System info
-    "uname", "hostname", "whoami", "id", "df", "free", "top", "ps",
-    // PHP/Web
-    "php", "composer", "npm", "node",
-    // Database
-    "mysql", "mariadb", "psql",
-    // Web servers
-    "nginx", "apache2", "httpd",
-    // Text editors (for config check)
-    "nano", "vim", "vi",
-    // Windows equivalents
-    "dir", "type", "ipconfig", "netsh", "wmic", "powershell",
-];
-
-fn is_command_allowed(cmd: &str) -> bool {
-    let base_cmd = cmd.split_whitespace().next().unwrap_or("");
-    let base_cmd = base_cmd.split('/').last().unwrap_or(base_cmd);
-    ALLOWED_COMMANDS.iter().any(|&allowed| base_cmd == allowed)
+/// Validate command, arguments and working directory against the command policy
+pub fn check_policy(
+    command: &str,
+    args: &[String],
+    cwd: Option<&str>,
+) -> Result<PolicyVerdict, String> {
+    let verdict = CommandPolicy::load()?.evaluate_in(command, args, cwd.map(Path::new));
+    if !verdict.allowed {
+        return Err(format!("⚠️ SEGURANÇA: {}", verdict.reason));
+    }
+
+    Ok(verdict)
 }
 
-#[tauri::command]
-pub async fn execute_command(
+/// Shared checks run before any command is spawned
+pub async fn validate_execution(
+    window: &Window,
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    context: &ExecutionContext,
+    approval_token: &str,
+    approvals: &ApprovalStore,
+) -> Result<AuditApproval, String> {
+    // CRITICAL SECURITY: Never execute without a backend-issued approval for this exact command
+    approvals.consume(approval_token, command, args, requires_sudo, context)?;
+
+    context.validate(requires_sudo)?;
+    let verdict = check_policy(command, args, context.cwd.as_deref())?;
+
+    // The webview alone cannot approve high-risk or elevated commands
+    let native_confirmation =
+        approval::confirm_natively(window, command, args, requires_sudo, &verdict).await?;
+
+    Ok(AuditApproval {
+        token_digest: audit::sha256_hex(approval_token.as_bytes()),
+        risk: verdict.risk,
+        reason: verdict.reason,
+        native_confirmation,
+    })
+}
+
+/// Per-request output encoding, falling back to the one in `AppConfig`
+pub fn resolve_encoding(encoding: Option<String>) -> Result<OutputEncoding, String> {
+    let encoding = encoding.or_else(|| config::get_config().unwrap_or_default().output_encoding);
+    OutputEncoding::from_label(encoding.as_deref())
+}
+
+/// Per-request limits, with anything left unset taken from `AppConfig`
+pub fn resolve_limits(limits: Option<ExecutionLimits>) -> ExecutionLimits {
+    let defaults = config::get_config().unwrap_or_default().command_limits;
+    limits.unwrap_or_default().or(&defaults)
+}
+
+pub fn audit_execution(
+    window: &Window,
+    approval: AuditApproval,
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    outcome: &Result<CommandResult, String>,
+    audit: &AuditLog,
+) {
+    let entry = AuditEntry::execution(
+        window.label(),
+        approval,
+        command,
+        args,
+        requires_sudo,
+        outcome,
+    );
+    if let Err(e) = audit.append(entry) {
+        eprintln!("[AUDIT] {}", e);
+    }
+}
+
+pub fn log_execution(result: &CommandResult, history: &HistoryStore) {
+    println!(
+        "[EXEC] {} {} | exit={} | duration={}ms",
+        result.command,
+        result.args.join(" "),
+        result.exit_code,
+        result.duration_ms
+    );
+
+    // A failure to persist must not hide the result of a command that already ran
+    if let Err(e) = history.record(result) {
+        eprintln!("[HISTORY] {}", e);
+    }
+}
+
+/// `log_execution` for async callers, on the blocking pool since the history is a file
+pub async fn log_execution_blocking(window: &Window, result: &CommandResult) {
+    let app = window.app_handle();
+    let result = result.clone();
+    let logged =
+        tokio::task::spawn_blocking(move || log_execution(&result, &app.state::<HistoryStore>()))
+            .await;
+    if let Err(e) = logged {
+        eprintln!("[HISTORY] {}", e);
+    }
+}
+
+/// Arguments of `execute_command` and `execute_command_stream`
+struct ExecutionRequest {
     command: String,
     args: Vec<String>,
     requires_sudo: bool,
-    user_confirmed: bool,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    limits: Option<ExecutionLimits>,
+    snapshot_paths: Option<Vec<String>>,
+    encoding: Option<String>,
+    queue_id: Option<String>,
+}
+
+/// Managed state an execution reads from and records into
+struct ExecutionServices<'a> {
+    approvals: &'a ApprovalStore,
+    helper: &'a HelperStore,
+    registry: &'a ProcessRegistry,
+    audit: &'a AuditLog,
+    sessions: &'a SessionStore,
+    queue: &'a CommandQueue,
+}
+
+/// Validate, run, audit and record one approved command. With `stream`, output is
+/// emitted to that window as it arrives.
+async fn execute(
+    window: &Window,
+    request: ExecutionRequest,
+    stream: Option<&Window>,
+    services: ExecutionServices<'_>,
 ) -> Result<CommandResult, String> {
-    // CRITICAL SECURITY: Never execute without user confirmation
-    if !user_confirmed {
-        return Err("⚠️ SEGURANÇA: Comando requer confirmação explícita do usuário".into());
-    }
+    let ExecutionRequest {
+        command,
+        args,
+        requires_sudo,
+        approval_token,
+        cwd,
+        env,
+        session_id,
+        limits,
+        snapshot_paths,
+        encoding,
+        queue_id,
+    } = request;
+    let queue = services.queue;
+    let queue_id = queue_id.as_deref();
+    queue.check_pending(queue_id, &command, &args)?;
+    let outcome = async {
+        let context = services
+            .sessions
+            .context(session_id.as_deref(), cwd, env, requires_sudo)?;
+        let encoding = resolve_encoding(encoding)?;
+        let approval = validate_execution(
+            window,
+            &command,
+            &args,
+            requires_sudo,
+            &context,
+            &approval_token,
+            services.approvals,
+        )
+        .await?;
+        let context = ExecutionContext {
+            approval_digest: Some(approval.token_digest.clone()),
+            ..context
+        };
+        queue.set_status(window, queue_id, QueueStatus::Approved);
+        let _slot = queue.slot().await?;
+        queue.set_status(window, queue_id, QueueStatus::Executing);
 
-    // Validate command against whitelist
-    if !is_command_allowed(&command) {
-        return Err(format!(
-            "⚠️ SEGURANÇA: Comando '{}' não está na lista de comandos permitidos.\n\
-            Comandos permitidos: {:?}",
-            command, ALLOWED_COMMANDS
-        ));
+        let limits = resolve_limits(limits);
+        let id = Uuid::new_v4().to_string();
+        let snapshot_id = snapshot::before_execution(
+            window,
+            &id,
+            &command,
+            &args,
+            requires_sudo,
+            approval.risk,
+            &context,
+            &snapshot_paths.unwrap_or_default(),
+        )
+        .await;
+        let outcome = executor::run(
+            id,
+            &command,
+            &args,
+            requires_sudo,
+            &limits,
+            &context,
+            encoding,
+            stream,
+            Some(services.helper),
+            services.registry,
+        )
+        .await
+        .map(|result| CommandResult {
+            snapshot_id,
+            ..result
+        });
+        audit_execution(
+            window,
+            approval,
+            &command,
+            &args,
+            requires_sudo,
+            &outcome,
+            services.audit,
+        );
+        let result = outcome?;
+        log_execution_blocking(window, &result).await;
+        Ok(result)
     }
+    .await;
+    queue.settle(window, queue_id, &outcome);
 
-    let start = std::time::Instant::now();
-    let id = Uuid::new_v4().to_string();
-
-    // Build the command
-    let output = if cfg!(target_os = "windows") {
-        if requires_sudo {
-            // On Windows, use runas for elevation (will prompt UAC)
-            Command::new("powershell")
-                .args([
-                    "-Command",
-                    &format!(
-                        "Start-Process -FilePath '{}' -ArgumentList '{}' -Verb RunAs -Wait",
-                        command,
-                        args.join(" ")
-                    ),
-                ])
-                .output()
-        } else {
-            Command::new("cmd")
-                .args(["/C", &command])
-                .args(&args)
-                .output()
-        }
-    } else {
-        // Unix-like systems
-        if requires_sudo {
-            // Use pkexec for graphical sudo prompt
-            Command::new("pkexec")
-                .arg(&command)
-                .args(&args)
-                .output()
-        } else {
-            Command::new(&command)
-                .args(&args)
-                .output()
-        }
+    outcome
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_command(
+    window: Window,
+    command: String,
+    args: Vec<String>,
+    requires_sudo: bool,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    limits: Option<ExecutionLimits>,
+    snapshot_paths: Option<Vec<String>>,
+    encoding: Option<String>,
+    queue_id: Option<String>,
+    approvals: State<'_, ApprovalStore>,
+    helper: State<'_, HelperStore>,
+    registry: State<'_, ProcessRegistry>,
+    audit: State<'_, AuditLog>,
+    sessions: State<'_, SessionStore>,
+    queue: State<'_, CommandQueue>,
+) -> Result<CommandResult, String> {
+    let request = ExecutionRequest {
+        command,
+        args,
+        requires_sudo,
+        approval_token,
+        cwd,
+        env,
+        session_id,
+        limits,
+        snapshot_paths,
+        encoding,
+        queue_id,
     };
+    let services = ExecutionServices {
+        approvals: &approvals,
+        helper: &helper,
+        registry: &registry,
+        audit: &audit,
+        sessions: &sessions,
+        queue: &queue,
+    };
+    execute(&window, request, None, services).await
+}
 
-    let duration = start.elapsed().as_millis() as u64;
-
-    match output {
-        Ok(out) => {
-            let result = CommandResult {
-                id,
-                command: command.clone(),
-                args: args.clone(),
-                success: out.status.success(),
-                stdout: String::from_utf8_lossy(&out.stdout).to_string(),
-                stderr: String::from_utf8_lossy(&out.stderr).to_string(),
-                exit_code: out.status.code().unwrap_or(-1),
-                executed_at: Utc::now().to_rfc3339(),
-                duration_ms: duration,
-            };
-
-            // Log the execution (in production, save to file)
-            println!(
-                "[EXEC] {} {} | exit={} | duration={}ms",
-                command,
-                args.join(" "),
-                result.exit_code,
-                duration
-            );
-
-            Ok(result)
-        }
-        Err(e) => Err(format!("Erro ao executar comando: {}", e)),
-    }
+/// Same as `execute_command`, but emits `command-started`, `command-output` and
+/// `command-completed` events to the calling window while the process runs
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_command_stream(
+    window: Window,
+    command: String,
+    args: Vec<String>,
+    requires_sudo: bool,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    limits: Option<ExecutionLimits>,
+    snapshot_paths: Option<Vec<String>>,
+    encoding: Option<String>,
+    queue_id: Option<String>,
+    approvals: State<'_, ApprovalStore>,
+    helper: State<'_, HelperStore>,
+    registry: State<'_, ProcessRegistry>,
+    audit: State<'_, AuditLog>,
+    sessions: State<'_, SessionStore>,
+    queue: State<'_, CommandQueue>,
+) -> Result<CommandResult, String> {
+    let request = ExecutionRequest {
+        command,
+        args,
+        requires_sudo,
+        approval_token,
+        cwd,
+        env,
+        session_id,
+        limits,
+        snapshot_paths,
+        encoding,
+        queue_id,
+    };
+    let services = ExecutionServices {
+        approvals: &approvals,
+        helper: &helper,
+        registry: &registry,
+        audit: &audit,
+        sessions: &sessions,
+        queue: &queue,
+    };
+    execute(&window, request, Some(&window), services).await
 }
 
 #[tauri::command]
@@ -187,9 +436,3 @@ pub fn get_system_info() -> Result<SystemInfo, String> {
         username,
     })
 }
-
-#[tauri::command]
-pub fn get_command_history() -> Result<Vec<CommandResult>, String> {
-    // In a real implementation, this would read from a local database/file
-    Ok(vec![])
-}
@@ -2,8 +2,15 @@ use serde::{Deserialize, Serialize};
 use directories::ProjectDirs;
 use std::fs;
 use std::path::PathBuf;
+use tauri::Window;
+
+use crate::approval;
+use crate::elevation::ElevationBackend;
+use crate::executor::ExecutionLimits;
+use crate::sandbox::SandboxMode;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct AppConfig {
     pub default_provider: String,
     pub default_model: String,
@@ -12,6 +19,23 @@ pub struct AppConfig {
     pub auto_scroll: bool,
     pub confirm_dangerous_commands: bool,
     pub max_history_items: usize,
+    /// Default limits for every executed command, overridable per request
+    pub command_limits: ExecutionLimits,
+    /// Variables passed on from the app's environment; everything else is scrubbed
+    pub env_allowlist: Vec<String>,
+    /// Encoding of command output, e.g. `cp850` or `latin1`; detected when unset
+    pub output_encoding: Option<String>,
+    /// How elevated commands get their privileges on Unix; the first available one when unset
+    pub elevation_backend: Option<ElevationBackend>,
+    /// Askpass helper for the `sudo-askpass` backend; looked up on PATH when unset
+    pub askpass_program: Option<String>,
+    /// Seconds without elevated commands after which the privileged helper exits
+    pub helper_idle_timeout_secs: u64,
+    /// Whether commands the policy tags read-only run in the sandbox
+    pub sandbox_mode: SandboxMode,
+    /// Commands, pipelines, simulations and terminal sessions allowed to run at once; read
+    /// at startup
+    pub max_concurrent_commands: usize,
 }
 
 impl Default for AppConfig {
@@ -24,19 +48,64 @@ impl Default for AppConfig {
             auto_scroll: true,
             confirm_dangerous_commands: true,
             max_history_items: 100,
+            command_limits: ExecutionLimits {
+                timeout_ms: Some(10 * 60 * 1000),
+                max_output_bytes: Some(512 * 1024 * 1024),
+                inline_output_bytes: Some(1024 * 1024),
+                cpu_seconds: Some(300),
+                // Runtimes like node reserve far more address space than they use,
+                // so there is no default cap
+                memory_bytes: None,
+            },
+            env_allowlist: [
+                "PATH", "HOME", "USER", "LOGNAME", "SHELL", "LANG", "LANGUAGE", "LC_ALL",
+                "LC_CTYPE", "LC_MESSAGES", "TERM", "TZ", "TMPDIR",
+                // Windows
+                "SYSTEMROOT", "WINDIR", "COMSPEC", "PATHEXT", "TEMP", "TMP", "USERNAME",
+                "USERPROFILE", "APPDATA", "LOCALAPPDATA", "PROGRAMDATA", "PROGRAMFILES",
+            ]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+            output_encoding: None,
+            elevation_backend: None,
+            askpass_program: None,
+            helper_idle_timeout_secs: 15 * 60,
+            sandbox_mode: SandboxMode::Auto,
+            max_concurrent_commands: 4,
         }
     }
 }
 
-fn get_config_path() -> Result<PathBuf, String> {
-    let proj_dirs = ProjectDirs::from("dev", "aisystemagent", "AI System Agent")
-        .ok_or_else(|| "Não foi possível determinar diretório de configuração".to_string())?;
+fn get_project_dirs() -> Result<ProjectDirs, String> {
+    ProjectDirs::from("dev", "aisystemagent", "AI System Agent")
+        .ok_or_else(|| "Não foi possível determinar diretório de configuração".to_string())
+}
+
+/// Directory holding `config.json` and other user-editable settings, created on first use
+pub fn get_config_dir() -> Result<PathBuf, String> {
+    let proj_dirs = get_project_dirs()?;
 
     let config_dir = proj_dirs.config_dir();
     fs::create_dir_all(config_dir)
         .map_err(|e| format!("Erro ao criar diretório de config: {}", e))?;
 
-    Ok(config_dir.join("config.json"))
+    Ok(config_dir.to_path_buf())
+}
+
+fn get_config_path() -> Result<PathBuf, String> {
+    Ok(get_config_dir()?.join("config.json"))
+}
+
+/// Directory for local app data (history, logs, ...), created on first use
+pub fn get_data_dir() -> Result<PathBuf, String> {
+    let proj_dirs = get_project_dirs()?;
+
+    let data_dir = proj_dirs.data_dir();
+    fs::create_dir_all(data_dir)
+        .map_err(|e| format!("Erro ao criar diretório de dados: {}", e))?;
+
+    Ok(data_dir.to_path_buf())
 }
 
 #[tauri::command]
@@ -53,8 +122,13 @@ pub fn get_config() -> Result<AppConfig, String> {
     }
 }
 
+/// Save the settings; weakening `sandbox_mode` needs a native confirmation first
 #[tauri::command]
-pub fn save_config(config: AppConfig) -> Result<String, String> {
+pub async fn save_config(window: Window, config: AppConfig) -> Result<String, String> {
+    let current = get_config().unwrap_or_default().sandbox_mode;
+    if current.weakened_by(config.sandbox_mode) {
+        approval::confirm_sandbox_mode_natively(&window, current, config.sandbox_mode).await?;
+    }
     let config_path = get_config_path()?;
 
     let content = serde_json::to_string_pretty(&config)
@@ -0,0 +1,339 @@
+use base64::Engine;
+use chardetng::EncodingDetector;
+use encoding_rs::{Decoder, Encoding, UTF_8};
+
+use crate::executor;
+
+/// Characters 0x80-0xFF of IBM code page 850
+#[rustfmt::skip]
+const CP850_HIGH: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç',
+    'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù',
+    'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º',
+    '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À',
+    '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã',
+    '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î',
+    'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ',
+    'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{ad}', '±', '‗', '¾', '¶', '§', '÷', '¸',
+    '°', '¨', '·', '¹', '³', '²', '■', '\u{a0}',
+];
+
+/// How command output is turned into text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputEncoding {
+    /// UTF-8 when the bytes are valid UTF-8, otherwise guessed from them
+    #[default]
+    Auto,
+    Standard(&'static Encoding),
+    /// IBM code page 850, the OEM code page of pt-BR Windows consoles, missing from encoding_rs
+    Cp850,
+}
+
+impl OutputEncoding {
+    /// Parse a label such as `utf-8`, `latin1`, `windows-1252` or `cp850`; empty or `auto` detects
+    pub fn from_label(label: Option<&str>) -> Result<Self, String> {
+        let Some(label) = label.map(str::trim).filter(|l| !l.is_empty()) else {
+            return Ok(OutputEncoding::Auto);
+        };
+        match label.to_ascii_lowercase().as_str() {
+            "auto" => Ok(OutputEncoding::Auto),
+            "cp850" | "ibm850" | "850" => Ok(OutputEncoding::Cp850),
+            other => Encoding::for_label(other.as_bytes())
+                .map(OutputEncoding::Standard)
+                .ok_or_else(|| format!("Codificação desconhecida: '{}'", label)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputEncoding::Auto => "auto",
+            OutputEncoding::Standard(encoding) => encoding.name(),
+            OutputEncoding::Cp850 => "IBM850",
+        }
+    }
+}
+
+/// Output of an execution as shown to the user
+#[derive(Debug)]
+pub struct DecodedOutput {
+    pub stdout: String,
+    pub stderr: String,
+    /// Base64 of the bytes each stream was decoded from, when they cannot be recovered
+    /// from the text by encoding it again
+    pub stdout_raw: Option<String>,
+    pub stderr_raw: Option<String>,
+    pub encoding: &'static str,
+}
+
+/// The encoding to decode `streams` with: `encoding` unless it is `Auto`, in which case
+/// UTF-8 if every stream is valid UTF-8 and a guess from their bytes otherwise
+pub fn resolve(encoding: OutputEncoding, streams: &[&[u8]]) -> OutputEncoding {
+    if encoding != OutputEncoding::Auto {
+        return encoding;
+    }
+    // A stream cut at the inline cap may end in the middle of a character
+    let utf8 = streams
+        .iter()
+        .all(|bytes| match std::str::from_utf8(bytes) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        });
+    if utf8 {
+        return OutputEncoding::Standard(UTF_8);
+    }
+
+    // Console programs on Windows write in the OEM code page, 850 on pt-BR systems
+    if cfg!(windows) {
+        return OutputEncoding::Cp850;
+    }
+    let mut detector = EncodingDetector::new();
+    for bytes in streams {
+        detector.feed(bytes, false);
+    }
+    detector.feed(&[], true);
+    OutputEncoding::Standard(detector.guess(Some(b"br"), false))
+}
+
+/// Decode stdout and stderr with one encoding and strip their escape sequences
+pub fn decode_output(stdout: &[u8], stderr: &[u8], encoding: OutputEncoding) -> DecodedOutput {
+    let encoding = resolve(encoding, &[stdout, stderr]);
+    let (stdout_text, stdout_raw) = decode_stream(stdout, encoding);
+    let (stderr_text, stderr_raw) = decode_stream(stderr, encoding);
+    DecodedOutput {
+        stdout: stdout_text,
+        stderr: stderr_text,
+        stdout_raw,
+        stderr_raw,
+        encoding: encoding.name(),
+    }
+}
+
+fn decode_stream(bytes: &[u8], encoding: OutputEncoding) -> (String, Option<String>) {
+    let decoded = decode_with(bytes, encoding);
+    let raw = (!round_trips(&decoded, bytes, encoding))
+        .then(|| base64::engine::general_purpose::STANDARD.encode(bytes));
+    (strip_ansi(&decoded), raw)
+}
+
+/// Whether encoding `decoded` again gives back exactly `bytes`, so the text loses nothing
+/// but the escape sequences stripped afterwards
+fn round_trips(decoded: &str, bytes: &[u8], encoding: OutputEncoding) -> bool {
+    match encoding {
+        OutputEncoding::Auto => decoded.as_bytes() == bytes,
+        OutputEncoding::Standard(encoding) => {
+            let (encoded, used, unmappable) = encoding.encode(decoded);
+            used == encoding && !unmappable && *encoded == *bytes
+        }
+        // Every byte maps to its own character
+        OutputEncoding::Cp850 => true,
+    }
+}
+
+/// Decode `bytes`, already resolved to an encoding, and strip escape sequences
+pub fn decode_text(bytes: &[u8], encoding: OutputEncoding) -> String {
+    strip_ansi(&decode_with(bytes, encoding))
+}
+
+fn decode_with(bytes: &[u8], encoding: OutputEncoding) -> String {
+    match encoding {
+        OutputEncoding::Auto => String::from_utf8_lossy(bytes).to_string(),
+        OutputEncoding::Standard(encoding) => {
+            encoding.decode_without_bom_handling(bytes).0.to_string()
+        }
+        OutputEncoding::Cp850 => bytes
+            .iter()
+            .map(|&b| {
+                if b < 0x80 {
+                    b as char
+                } else {
+                    CP850_HIGH[(b - 0x80) as usize]
+                }
+            })
+            .collect(),
+    }
+}
+
+fn decode_chunk(decoder: &mut Decoder, bytes: &[u8]) -> String {
+    let capacity = decoder
+        .max_utf8_buffer_length(bytes.len())
+        .unwrap_or(bytes.len() * 3);
+    let mut text = String::with_capacity(capacity);
+    let _ = decoder.decode_to_string(bytes, &mut text, false);
+    text
+}
+
+pub fn strip_ansi(text: &str) -> String {
+    AnsiStripper::default().strip(text)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+enum AnsiState {
+    #[default]
+    Text,
+    Escape,
+    /// `ESC` followed by intermediate bytes, e.g. `ESC ( B`
+    Intermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM and APC strings, ended by BEL or `ESC \`
+    String,
+    StringEscape,
+}
+
+/// Removes terminal escape sequences (colors, cursor movement, titles) from text that
+/// arrives in pieces, so a sequence split across two chunks is still removed
+#[derive(Debug, Default)]
+pub struct AnsiStripper {
+    state: AnsiState,
+}
+
+impl AnsiStripper {
+    pub fn strip(&mut self, text: &str) -> String {
+        let mut plain = String::with_capacity(text.len());
+        for c in text.chars() {
+            self.state = match (self.state, c) {
+                (AnsiState::Text, '\x1b') => AnsiState::Escape,
+                (AnsiState::Text, '\u{9b}') => AnsiState::Csi,
+                (AnsiState::Text, c) => {
+                    plain.push(c);
+                    AnsiState::Text
+                }
+                (AnsiState::Escape, '[') => AnsiState::Csi,
+                (AnsiState::Escape, ']' | 'P' | 'X' | '^' | '_') => AnsiState::String,
+                (AnsiState::Escape | AnsiState::Intermediate, ' '..='/') => AnsiState::Intermediate,
+                (AnsiState::Escape | AnsiState::Intermediate, _) => AnsiState::Text,
+                (AnsiState::Csi, '@'..='~') => AnsiState::Text,
+                (AnsiState::Csi, _) => AnsiState::Csi,
+                (AnsiState::String | AnsiState::StringEscape, '\x07') => AnsiState::Text,
+                (AnsiState::String | AnsiState::StringEscape, '\x1b') => AnsiState::StringEscape,
+                (AnsiState::StringEscape, '\\') => AnsiState::Text,
+                (AnsiState::String | AnsiState::StringEscape, _) => AnsiState::String,
+            };
+        }
+        plain
+    }
+}
+
+/// Decodes one output stream chunk by chunk for live events, choosing the encoding
+/// the way `resolve` does from what has arrived so far
+pub struct StreamDecoder {
+    encoding: OutputEncoding,
+    decoder: Option<Decoder>,
+    /// Bytes of a character split across two chunks, while the stream still looks like UTF-8
+    pending: Vec<u8>,
+    ansi: AnsiStripper,
+}
+
+impl StreamDecoder {
+    pub fn new(encoding: OutputEncoding) -> Self {
+        let decoder = match encoding {
+            OutputEncoding::Standard(encoding) => Some(encoding.new_decoder_without_bom_handling()),
+            _ => None,
+        };
+        Self {
+            encoding,
+            decoder,
+            pending: Vec::new(),
+            ansi: AnsiStripper::default(),
+        }
+    }
+
+    /// Text of `bytes` ready to be shown, escape sequences removed
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        let text = match (self.encoding, &mut self.decoder) {
+            (OutputEncoding::Standard(_), Some(decoder)) => decode_chunk(decoder, bytes),
+            (OutputEncoding::Auto, _) => {
+                self.pending.extend_from_slice(bytes);
+                if matches!(std::str::from_utf8(&self.pending), Err(e) if e.error_len().is_some()) {
+                    // Not UTF-8 after all; the guess holds for the rest of the stream
+                    let pending = std::mem::take(&mut self.pending);
+                    *self = StreamDecoder {
+                        ansi: std::mem::take(&mut self.ansi),
+                        ..StreamDecoder::new(resolve(OutputEncoding::Auto, &[&pending]))
+                    };
+                    return self.push(&pending);
+                }
+                executor::take_utf8_prefix(&mut self.pending)
+            }
+            (encoding, _) => decode_with(bytes, encoding),
+        };
+        self.ansi.strip(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_output_has_no_raw_copy() {
+        let decoded = decode_output("ação\n".as_bytes(), b"", OutputEncoding::Auto);
+        assert_eq!(decoded.stdout, "ação\n");
+        assert_eq!(decoded.encoding, "UTF-8");
+        assert!(decoded.stdout_raw.is_none());
+    }
+
+    #[test]
+    fn escape_sequences_are_stripped_without_a_raw_copy() {
+        let decoded = decode_output(b"\x1b[1;31merro\x1b[0m\n", b"", OutputEncoding::Auto);
+        assert_eq!(decoded.stdout, "erro\n");
+        assert!(decoded.stdout_raw.is_none());
+    }
+
+    #[test]
+    fn latin1_round_trips_without_a_raw_copy() {
+        let encoding = OutputEncoding::from_label(Some("latin1")).unwrap();
+        let decoded = decode_output(b"a\xe7\xe3o", b"", encoding);
+        assert_eq!(decoded.stdout, "ação");
+        assert!(decoded.stdout_raw.is_none());
+    }
+
+    #[test]
+    fn invalid_bytes_keep_a_raw_copy() {
+        let encoding = OutputEncoding::from_label(Some("utf-8")).unwrap();
+        let decoded = decode_output(b"ok \xff\xfe", b"", encoding);
+        assert_eq!(decoded.stdout, "ok \u{fffd}\u{fffd}");
+        assert_eq!(decoded.stdout_raw.as_deref(), Some("b2sg//4="));
+    }
+
+    #[test]
+    fn cp850_decodes_the_oem_code_page() {
+        let encoding = OutputEncoding::from_label(Some("cp850")).unwrap();
+        let decoded = decode_output(b"a\x87\xc6o", b"", encoding);
+        assert_eq!(decoded.stdout, "ação");
+        assert_eq!(decoded.encoding, "IBM850");
+        assert!(decoded.stdout_raw.is_none());
+    }
+
+    #[test]
+    fn unknown_label_is_refused() {
+        assert!(OutputEncoding::from_label(Some("klingon")).is_err());
+        assert_eq!(
+            OutputEncoding::from_label(Some(" ")).unwrap(),
+            OutputEncoding::Auto
+        );
+    }
+
+    #[test]
+    fn stream_decoder_joins_characters_split_across_chunks() {
+        let bytes = "ação".as_bytes();
+        let mut decoder = StreamDecoder::new(OutputEncoding::Auto);
+        let mut text = decoder.push(&bytes[..2]);
+        text.push_str(&decoder.push(&bytes[2..]));
+        assert_eq!(text, "ação");
+    }
+
+    #[test]
+    fn stripper_removes_sequences_split_across_chunks() {
+        let mut stripper = AnsiStripper::default();
+        let mut text = stripper.strip("ok \x1b[3");
+        text.push_str(&stripper.strip("2mverde\x1b]0;título\x07!"));
+        assert_eq!(text, "ok verde!");
+    }
+}
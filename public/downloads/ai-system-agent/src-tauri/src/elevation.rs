@@ -0,0 +1,427 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use crate::config::{self, AppConfig};
+
+/// Askpass helpers looked up on PATH when `askpass_program` is not configured
+const ASKPASS_HELPERS: &[&str] = &[
+    "ssh-askpass",
+    "ksshaskpass",
+    "lxqt-openssh-askpass",
+    "x11-ssh-askpass",
+];
+/// Variables a graphical askpass helper needs to reach the user's session
+const SESSION_ENV: &[&str] = &[
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XAUTHORITY",
+    "XDG_RUNTIME_DIR",
+];
+const DOAS_CONFIGS: &[&str] = &["/etc/doas.conf", "/usr/local/etc/doas.conf"];
+
+/// How elevated commands get their privileges on Unix
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ElevationBackend {
+    /// Polkit, prompting through the desktop's authentication agent
+    Pkexec,
+    /// `sudo -A`, asking for the password through a graphical askpass helper
+    SudoAskpass,
+    /// `sudo -n`, only for commands sudoers allows without a password
+    SudoNonInteractive,
+    /// `doas -n`, only for commands doas.conf allows with `nopass`
+    Doas,
+}
+
+/// Tried in this order when no backend is configured
+const AUTO_ORDER: [ElevationBackend; 4] = [
+    ElevationBackend::Pkexec,
+    ElevationBackend::SudoAskpass,
+    ElevationBackend::SudoNonInteractive,
+    ElevationBackend::Doas,
+];
+
+impl ElevationBackend {
+    fn name(self) -> &'static str {
+        match self {
+            ElevationBackend::Pkexec => "pkexec",
+            ElevationBackend::SudoAskpass => "sudo -A",
+            ElevationBackend::SudoNonInteractive => "sudo -n",
+            ElevationBackend::Doas => "doas",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BackendStatus {
+    pub backend: ElevationBackend,
+    pub available: bool,
+    /// Why the backend cannot be used
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ElevationInfo {
+    /// Backend chosen in `AppConfig`; `None` picks the first available one
+    pub configured: Option<ElevationBackend>,
+    /// Backend elevated commands currently go through
+    pub active: Option<ElevationBackend>,
+    pub backends: Vec<BackendStatus>,
+    /// Why no elevated command can run, when `active` is `None`
+    pub error: Option<String>,
+}
+
+/// Elevation tools found on this system
+struct Probe {
+    pkexec: Option<PathBuf>,
+    sudo: Option<PathBuf>,
+    doas: Option<PathBuf>,
+    /// Whether a desktop session is there to show polkit and askpass prompts
+    graphical: bool,
+    /// Whether sudoers has rules that need no password
+    sudo_preauthorized: bool,
+    doas_configured: bool,
+}
+
+static PROBE: OnceLock<Probe> = OnceLock::new();
+
+/// Probe the elevation tools up front, so the first elevated command does not wait for it
+pub fn detect() {
+    probe();
+}
+
+fn probe() -> &'static Probe {
+    PROBE.get_or_init(|| {
+        let sudo = find_program("sudo");
+        // `sudo -l` needs no password when any of the user's rules is NOPASSWD. `-k` ignores
+        // credentials cached by an earlier `sudo`, which would only pass until they expire.
+        let sudo_preauthorized = sudo.as_ref().is_some_and(|sudo| {
+            Command::new(sudo)
+                .args(["-n", "-k", "-l"])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success())
+        });
+
+        Probe {
+            pkexec: find_program("pkexec"),
+            sudo,
+            doas: find_program("doas"),
+            graphical: ["DISPLAY", "WAYLAND_DISPLAY"]
+                .iter()
+                .any(|name| std::env::var_os(name).is_some_and(|value| !value.is_empty())),
+            sudo_preauthorized,
+            doas_configured: DOAS_CONFIGS.iter().any(|path| Path::new(path).is_file()),
+        }
+    })
+}
+
+fn find_program(name: &str) -> Option<PathBuf> {
+    std::env::var_os("PATH")
+        .iter()
+        .flat_map(std::env::split_paths)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// The askpass helper sees the password, so it must be a root-owned program nobody
+/// else can modify; otherwise the webview could point sudo at a script that records it
+#[cfg(unix)]
+fn is_trusted(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    path.is_absolute()
+        && path
+            .metadata()
+            .is_ok_and(|m| m.uid() == 0 && m.mode() & 0o022 == 0)
+}
+
+#[cfg(not(unix))]
+fn is_trusted(_path: &Path) -> bool {
+    false
+}
+
+fn askpass(config: &AppConfig) -> Result<PathBuf, String> {
+    if let Some(program) = config.askpass_program.as_deref().filter(|p| !p.is_empty()) {
+        let path = PathBuf::from(program);
+        if !is_executable(&path) {
+            return Err(format!(
+                "o programa askpass '{}' não existe ou não é executável",
+                program
+            ));
+        }
+        if !is_trusted(&path) {
+            return Err(format!(
+                "o programa askpass '{}' deve pertencer ao root e não ser gravável por outros",
+                program
+            ));
+        }
+        return Ok(path);
+    }
+
+    std::env::var_os("SUDO_ASKPASS")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(ASKPASS_HELPERS.iter().filter_map(|name| find_program(name)))
+        .find(|path| is_executable(path) && is_trusted(path))
+        .ok_or_else(|| "nenhum programa askpass encontrado (configure askpass_program)".to_string())
+}
+
+fn check(backend: ElevationBackend, probe: &Probe, config: &AppConfig) -> Result<(), String> {
+    match backend {
+        ElevationBackend::Pkexec => {
+            probe.pkexec.as_ref().ok_or("pkexec não está instalado")?;
+            if !probe.graphical {
+                return Err("sem sessão gráfica para o agente do polkit".to_string());
+            }
+        }
+        ElevationBackend::SudoAskpass => {
+            probe.sudo.as_ref().ok_or("sudo não está instalado")?;
+            if !probe.graphical {
+                return Err("sem sessão gráfica para o askpass".to_string());
+            }
+            askpass(config)?;
+        }
+        ElevationBackend::SudoNonInteractive => {
+            probe.sudo.as_ref().ok_or("sudo não está instalado")?;
+            if !probe.sudo_preauthorized {
+                return Err("nenhuma regra do sudoers dispensa a senha".to_string());
+            }
+        }
+        ElevationBackend::Doas => {
+            probe.doas.as_ref().ok_or("doas não está instalado")?;
+            if !probe.doas_configured {
+                return Err("doas.conf não encontrado".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The configured backend, or the first available one
+fn select(config: &AppConfig) -> Result<ElevationBackend, String> {
+    select_from(probe(), config)
+}
+
+fn select_from(probe: &Probe, config: &AppConfig) -> Result<ElevationBackend, String> {
+    if let Some(backend) = config.elevation_backend {
+        return check(backend, probe, config)
+            .map(|_| backend)
+            .map_err(|reason| {
+                format!(
+                    "O método de elevação '{}' não está disponível: {}",
+                    backend.name(),
+                    reason
+                )
+            });
+    }
+
+    let mut reasons = Vec::new();
+    for backend in AUTO_ORDER {
+        match check(backend, probe, config) {
+            Ok(()) => return Ok(backend),
+            Err(reason) => reasons.push(format!("{}: {}", backend.name(), reason)),
+        }
+    }
+    Err(format!(
+        "Nenhum método de elevação disponível ({}). Configure elevation_backend e askpass_program nas configurações.",
+        reasons.join("; ")
+    ))
+}
+
+/// Wrap `command args` in the selected backend. Variables the backend needs are set on
+/// the returned process and survive `ExecutionContext::apply`.
+pub fn elevate(command: &str, args: &[String]) -> Result<Command, String> {
+    let config = config::get_config().unwrap_or_default();
+    let probe = probe();
+    let helper = |program: &Option<PathBuf>| program.clone().unwrap_or_default();
+
+    let mut process = match select(&config)? {
+        // pkexec takes no `--`; the program is always its first non-option argument
+        ElevationBackend::Pkexec => Command::new(helper(&probe.pkexec)),
+        ElevationBackend::SudoAskpass => {
+            let mut process = Command::new(helper(&probe.sudo));
+            process.env("SUDO_ASKPASS", askpass(&config)?);
+            for name in SESSION_ENV {
+                if let Some(value) = std::env::var_os(name) {
+                    process.env(name, value);
+                }
+            }
+            process.args(["-A", "--"]);
+            process
+        }
+        ElevationBackend::SudoNonInteractive => {
+            let mut process = Command::new(helper(&probe.sudo));
+            process.args(["-n", "--"]);
+            process
+        }
+        // There is no terminal to type a password into, so only `nopass` rules can work
+        ElevationBackend::Doas => {
+            let mut process = Command::new(helper(&probe.doas));
+            process.args(["-n", "--"]);
+            process
+        }
+    };
+    process.arg(command).args(args);
+    Ok(process)
+}
+
+/// Elevation backends on this system and the one elevated commands go through
+#[tauri::command]
+pub fn get_elevation_backends() -> ElevationInfo {
+    let config = config::get_config().unwrap_or_default();
+    let probe = probe();
+    let backends = AUTO_ORDER
+        .iter()
+        .map(|&backend| {
+            let reason = check(backend, probe, &config).err();
+            BackendStatus {
+                backend,
+                available: reason.is_none(),
+                reason,
+            }
+        })
+        .collect();
+    let selected = select(&config);
+
+    ElevationInfo {
+        configured: config.elevation_backend,
+        active: selected.as_ref().ok().copied(),
+        backends,
+        error: selected.err(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(graphical: bool) -> Probe {
+        Probe {
+            pkexec: None,
+            sudo: Some(PathBuf::from("/usr/bin/sudo")),
+            doas: None,
+            graphical,
+            sudo_preauthorized: false,
+            doas_configured: false,
+        }
+    }
+
+    /// Executable script anyone can modify, removed when dropped
+    #[cfg(unix)]
+    struct WritableScript(PathBuf);
+
+    #[cfg(unix)]
+    impl WritableScript {
+        fn new() -> Self {
+            use std::os::unix::fs::PermissionsExt;
+            let path = std::env::temp_dir().join(format!("askpass-test-{}", uuid::Uuid::new_v4()));
+            std::fs::write(&path, "#!/bin/sh\n").unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o777)).unwrap();
+            Self(path)
+        }
+    }
+
+    #[cfg(unix)]
+    impl Drop for WritableScript {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn the_first_available_backend_is_selected() {
+        let with_pkexec = Probe {
+            pkexec: Some(PathBuf::from("/usr/bin/pkexec")),
+            ..probe(true)
+        };
+        assert_eq!(
+            select_from(&with_pkexec, &AppConfig::default()),
+            Ok(ElevationBackend::Pkexec)
+        );
+
+        // Without a desktop session only the non-interactive backends are left
+        let headless = Probe {
+            pkexec: Some(PathBuf::from("/usr/bin/pkexec")),
+            sudo_preauthorized: true,
+            ..probe(false)
+        };
+        assert_eq!(
+            select_from(&headless, &AppConfig::default()),
+            Ok(ElevationBackend::SudoNonInteractive)
+        );
+
+        let doas = Probe {
+            sudo: None,
+            doas: Some(PathBuf::from("/usr/bin/doas")),
+            doas_configured: true,
+            ..probe(false)
+        };
+        assert_eq!(
+            select_from(&doas, &AppConfig::default()),
+            Ok(ElevationBackend::Doas)
+        );
+    }
+
+    #[test]
+    fn every_reason_is_reported_when_nothing_is_available() {
+        let err = select_from(&probe(false), &AppConfig::default()).unwrap_err();
+        assert!(err.contains("pkexec: pkexec não está instalado"), "{}", err);
+        assert!(err.contains("sudo -n: nenhuma regra do sudoers"), "{}", err);
+        assert!(err.contains("doas: doas não está instalado"), "{}", err);
+    }
+
+    #[test]
+    fn a_configured_backend_does_not_fall_back() {
+        let config = AppConfig {
+            elevation_backend: Some(ElevationBackend::Doas),
+            ..AppConfig::default()
+        };
+        let preauthorized = Probe {
+            sudo_preauthorized: true,
+            ..probe(false)
+        };
+        let err = select_from(&preauthorized, &config).unwrap_err();
+        assert!(err.starts_with("O método de elevação 'doas' não está disponível"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_askpass_program_others_can_modify_is_refused() {
+        let script = WritableScript::new();
+        let config = AppConfig {
+            askpass_program: Some(script.0.to_string_lossy().to_string()),
+            ..AppConfig::default()
+        };
+        let err = askpass(&config).unwrap_err();
+        assert!(err.contains("deve pertencer ao root"), "{}", err);
+
+        // Nor does the selection fall back to another askpass helper
+        let preauthorized = Probe {
+            sudo_preauthorized: true,
+            ..probe(true)
+        };
+        assert_eq!(
+            select_from(&preauthorized, &config),
+            Ok(ElevationBackend::SudoNonInteractive)
+        );
+        let err = check(ElevationBackend::SudoAskpass, &preauthorized, &config).unwrap_err();
+        assert!(err.contains("deve pertencer ao root"), "{}", err);
+    }
+}
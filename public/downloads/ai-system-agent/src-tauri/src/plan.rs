@@ -0,0 +1,455 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Instant;
+use chrono::Utc;
+use tauri::{State, Window};
+use uuid::Uuid;
+
+use crate::approval::{self, ApprovalStore, ApprovalToken};
+use crate::audit::{self, AuditApproval, AuditLog};
+use crate::commands::{self, CommandResult};
+use crate::executor::{self, ExecutionContext, ExecutionLimits};
+use crate::helper::HelperStore;
+use crate::policy::{CommandPolicy, RiskLevel};
+use crate::process_registry::ProcessRegistry;
+use crate::queue::{CommandQueue, QueueStatus};
+use crate::snapshot;
+
+/// Event emitted after each step of a plan, carrying its `StepReport`
+pub const PLAN_STEP_EVENT: &str = "plan-step";
+
+/// What to do when a step fails
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    /// Skip every remaining step
+    #[default]
+    Stop,
+    Continue,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlanStep {
+    pub command: String,
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub requires_sudo: bool,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub on_failure: OnFailure,
+    /// Checks the outcome of the previous steps (e.g. `systemctl is-active nginx`);
+    /// must be low risk and unelevated
+    #[serde(default)]
+    pub verify: bool,
+    /// For verification steps, text stdout must contain for the check to pass
+    #[serde(default)]
+    pub expect_output: Option<String>,
+    /// Files to snapshot before the step, besides those detected from its arguments
+    #[serde(default)]
+    pub snapshot_paths: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    Succeeded,
+    Failed,
+    /// Not run because an earlier step failed with `on_failure: stop`
+    Skipped,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StepReport {
+    pub plan_id: String,
+    pub index: usize,
+    pub step: PlanStep,
+    pub status: StepStatus,
+    pub result: Option<CommandResult>,
+    /// Why the step failed without a result, or why its verification did not pass
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlanReport {
+    pub id: String,
+    pub success: bool,
+    pub steps: Vec<StepReport>,
+    /// Index of the step whose failure stopped the plan
+    pub stopped_at: Option<usize>,
+    pub started_at: String,
+    pub duration_ms: u64,
+}
+
+/// Check every step up front, so a plan is refused before anything runs
+fn check_plan(steps: &[PlanStep], context: &ExecutionContext) -> Result<(), String> {
+    check_steps(&CommandPolicy::load()?, steps, context)
+}
+
+fn check_steps(
+    policy: &CommandPolicy,
+    steps: &[PlanStep],
+    context: &ExecutionContext,
+) -> Result<(), String> {
+    if steps.is_empty() {
+        return Err("O plano não tem etapas".to_string());
+    }
+
+    for (i, step) in steps.iter().enumerate() {
+        let prefix = format!("Etapa {} ({})", i + 1, step.command);
+        context
+            .validate(step.requires_sudo)
+            .map_err(|e| format!("{}: {}", prefix, e))?;
+        let verdict = policy.evaluate_in(
+            &step.command,
+            &step.args,
+            context.cwd.as_deref().map(Path::new),
+        );
+        if !verdict.allowed {
+            return Err(format!("{}: ⚠️ SEGURANÇA: {}", prefix, verdict.reason));
+        }
+
+        if step.verify
+            && (step.requires_sudo || verdict.requires_sudo || verdict.risk > RiskLevel::Low)
+        {
+            return Err(format!(
+                "{}: etapas de verificação devem ser de baixo risco e sem privilégios elevados",
+                prefix
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Status of a step that was reached, with its result and why it failed: the error that
+/// kept it from running, a non-zero exit, or verification output that did not match
+fn step_outcome(
+    step: &PlanStep,
+    outcome: Result<CommandResult, String>,
+) -> (StepStatus, Option<CommandResult>, Option<String>) {
+    let result = match outcome {
+        Ok(result) => result,
+        Err(e) => return (StepStatus::Failed, None, Some(e)),
+    };
+    let failure = if !result.success {
+        Some(format!(
+            "O comando terminou com código {}",
+            result.exit_code
+        ))
+    } else {
+        match &step.expect_output {
+            Some(expected) if step.verify && !result.stdout.contains(expected.as_str()) => Some(
+                format!("A verificação não encontrou '{}' na saída", expected),
+            ),
+            _ => None,
+        }
+    };
+    match failure {
+        Some(error) => (StepStatus::Failed, Some(result), Some(error)),
+        None => (StepStatus::Succeeded, Some(result), None),
+    }
+}
+
+/// Whether the remaining steps are skipped after this one
+fn stops_plan(step: &PlanStep, status: StepStatus) -> bool {
+    status == StepStatus::Failed && step.on_failure == OnFailure::Stop
+}
+
+/// Called by the UI once the user confirms a plan; the token is then passed to `execute_plan`
+#[tauri::command]
+pub fn request_plan_approval(
+    steps: Vec<PlanStep>,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    approvals: State<'_, ApprovalStore>,
+) -> Result<ApprovalToken, String> {
+    let context = ExecutionContext::new(cwd, env);
+    check_plan(&steps, &context)?;
+    Ok(approvals.issue_plan(&steps, &context))
+}
+
+/// Run the steps of a plan in order, approved as a whole.
+///
+/// High-risk and elevated steps are still confirmed natively when they are reached.
+/// A `plan-step` event follows each step; the report holds every `CommandResult`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_plan(
+    window: Window,
+    steps: Vec<PlanStep>,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    limits: Option<ExecutionLimits>,
+    queue_ids: Option<Vec<String>>,
+    approvals: State<'_, ApprovalStore>,
+    helper: State<'_, HelperStore>,
+    registry: State<'_, ProcessRegistry>,
+    audit: State<'_, AuditLog>,
+    queue: State<'_, CommandQueue>,
+) -> Result<PlanReport, String> {
+    // Queue entries of the steps, in the same order
+    let queue_ids = queue_ids.unwrap_or_default();
+    if !queue_ids.is_empty() && queue_ids.len() != steps.len() {
+        return Err("A fila não corresponde às etapas do plano".to_string());
+    }
+    let queue_id = |index: usize| queue_ids.get(index).map(String::as_str);
+    for (index, step) in steps.iter().enumerate() {
+        queue.check_pending(queue_id(index), &step.command, &step.args)?;
+    }
+
+    let context = ExecutionContext::new(cwd, env);
+    approvals.consume_plan(&approval_token, &steps, &context)?;
+    check_plan(&steps, &context)?;
+    for index in 0..steps.len() {
+        queue.set_status(&window, queue_id(index), QueueStatus::Approved);
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let start = Instant::now();
+    let started_at = Utc::now().to_rfc3339();
+    let limits = commands::resolve_limits(limits);
+    let encoding = commands::resolve_encoding(None)?;
+    let token_digest = audit::sha256_hex(approval_token.as_bytes());
+    let context = ExecutionContext {
+        approval_digest: Some(token_digest.clone()),
+        ..context
+    };
+
+    let mut reports = Vec::new();
+    let mut stopped_at = None;
+    for (index, step) in steps.iter().enumerate() {
+        let report = |(status, result, error)| StepReport {
+            plan_id: id.clone(),
+            index,
+            step: step.clone(),
+            status,
+            result,
+            error,
+        };
+
+        if stopped_at.is_some() {
+            // Back to pending, so the step can still be run on its own
+            queue.set_status(&window, queue_id(index), QueueStatus::Pending);
+            reports.push(report((StepStatus::Skipped, None, None)));
+            continue;
+        }
+
+        // The policy may have changed since the plan was approved
+        let checked = async {
+            let verdict =
+                commands::check_policy(&step.command, &step.args, context.cwd.as_deref())?;
+            let native_confirmation = approval::confirm_natively(
+                &window,
+                &step.command,
+                &step.args,
+                step.requires_sudo,
+                &verdict,
+            )
+            .await?;
+            Ok::<_, String>(AuditApproval {
+                token_digest: token_digest.clone(),
+                risk: verdict.risk,
+                reason: verdict.reason,
+                native_confirmation,
+            })
+        }
+        .await;
+
+        let step_report = match checked {
+            Ok(approval) => {
+                let _slot = queue.slot().await?;
+                queue.set_status(&window, queue_id(index), QueueStatus::Executing);
+                let command_id = Uuid::new_v4().to_string();
+                let snapshot_id = snapshot::before_execution(
+                    &window,
+                    &command_id,
+                    &step.command,
+                    &step.args,
+                    step.requires_sudo,
+                    approval.risk,
+                    &context,
+                    &step.snapshot_paths,
+                )
+                .await;
+                let outcome = executor::run(
+                    command_id,
+                    &step.command,
+                    &step.args,
+                    step.requires_sudo,
+                    &limits,
+                    &context,
+                    encoding,
+                    None,
+                    Some(&helper),
+                    &registry,
+                )
+                .await
+                .map(|result| CommandResult {
+                    snapshot_id,
+                    ..result
+                });
+                commands::audit_execution(
+                    &window,
+                    approval,
+                    &step.command,
+                    &step.args,
+                    step.requires_sudo,
+                    &outcome,
+                    &audit,
+                );
+                if let Ok(result) = &outcome {
+                    commands::log_execution_blocking(&window, result).await;
+                }
+                report(step_outcome(step, outcome))
+            }
+            Err(e) => report(step_outcome(step, Err(e))),
+        };
+
+        queue.record(
+            &window,
+            queue_id(index),
+            if step_report.status == StepStatus::Succeeded {
+                QueueStatus::Completed
+            } else {
+                QueueStatus::Failed
+            },
+            step_report.result.clone(),
+            step_report.error.clone(),
+        );
+        if stops_plan(step, step_report.status) {
+            stopped_at = Some(index);
+        }
+        let _ = window.emit(PLAN_STEP_EVENT, step_report.clone());
+        reports.push(step_report);
+    }
+
+    Ok(PlanReport {
+        id,
+        success: reports.iter().all(|r| r.status == StepStatus::Succeeded),
+        steps: reports,
+        stopped_at,
+        started_at,
+        duration_ms: start.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(command: &str, args: &[&str]) -> PlanStep {
+        serde_json::from_value(serde_json::json!({ "command": command, "args": args })).unwrap()
+    }
+
+    fn verify(command: &str, args: &[&str], expect_output: &str) -> PlanStep {
+        PlanStep {
+            verify: true,
+            expect_output: Some(expect_output.to_string()),
+            ..step(command, args)
+        }
+    }
+
+    fn ran(exit_code: i32, stdout: &str) -> CommandResult {
+        serde_json::from_value(serde_json::json!({
+            "id": "r",
+            "command": "x",
+            "args": [],
+            "success": exit_code == 0,
+            "stdout": stdout,
+            "stderr": "",
+            "exit_code": exit_code,
+            "executed_at": "2026-01-01T00:00:00Z",
+            "duration_ms": 0,
+        }))
+        .unwrap()
+    }
+
+    fn check(steps: &[PlanStep]) -> Result<(), String> {
+        check_steps(
+            &CommandPolicy::default(),
+            steps,
+            &ExecutionContext::new(None, None),
+        )
+    }
+
+    #[test]
+    fn plans_are_checked_step_by_step() {
+        assert!(check(&[]).is_err());
+        assert!(check(&[
+            step("systemctl", &["restart", "nginx"]),
+            verify("systemctl", &["is-active", "nginx"], "active"),
+        ])
+        .is_ok());
+
+        let err = check(&[
+            step("ls", &["/tmp"]),
+            step("find", &["/tmp", "-exec", "sh", "{}", ";"]),
+        ])
+        .unwrap_err();
+        assert!(err.starts_with("Etapa 2 (find): ⚠️ SEGURANÇA"), "{}", err);
+    }
+
+    #[test]
+    fn verification_steps_must_be_low_risk_and_unelevated() {
+        let err = check(&[verify("systemctl", &["restart", "nginx"], "")]).unwrap_err();
+        assert!(err.contains("etapas de verificação"), "{}", err);
+        let elevated = PlanStep {
+            requires_sudo: true,
+            ..verify("systemctl", &["is-active", "nginx"], "active")
+        };
+        assert!(check(&[elevated]).is_err());
+    }
+
+    #[test]
+    fn step_outcome_follows_the_exit_code_and_the_expected_output() {
+        let restart = step("systemctl", &["restart", "nginx"]);
+        let (status, result, error) = step_outcome(&restart, Ok(ran(0, "")));
+        assert_eq!(status, StepStatus::Succeeded);
+        assert!(result.is_some() && error.is_none());
+
+        let (status, result, error) = step_outcome(&restart, Ok(ran(3, "")));
+        assert_eq!(status, StepStatus::Failed);
+        assert!(result.is_some());
+        assert!(error.unwrap().contains("código 3"));
+
+        let (status, result, _) = step_outcome(&restart, Err("recusado".to_string()));
+        assert_eq!(status, StepStatus::Failed);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn expected_output_only_applies_to_verification_steps() {
+        let check = verify("systemctl", &["is-active", "nginx"], "active");
+        assert_eq!(
+            step_outcome(&check, Ok(ran(0, "active\n"))).0,
+            StepStatus::Succeeded
+        );
+        let (status, _, error) = step_outcome(&check, Ok(ran(0, "failed\n")));
+        assert_eq!(status, StepStatus::Failed);
+        assert!(error.unwrap().contains("'active'"));
+
+        let plain = PlanStep {
+            verify: false,
+            ..check
+        };
+        assert_eq!(
+            step_outcome(&plain, Ok(ran(0, "failed\n"))).0,
+            StepStatus::Succeeded
+        );
+    }
+
+    #[test]
+    fn only_failed_steps_that_stop_end_the_plan() {
+        let stop = step("ls", &[]);
+        let continue_ = PlanStep {
+            on_failure: OnFailure::Continue,
+            ..step("ls", &[])
+        };
+        assert_eq!(stop.on_failure, OnFailure::Stop);
+        assert!(stops_plan(&stop, StepStatus::Failed));
+        assert!(!stops_plan(&stop, StepStatus::Succeeded));
+        assert!(!stops_plan(&continue_, StepStatus::Failed));
+    }
+}
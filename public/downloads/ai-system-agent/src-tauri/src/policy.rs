@@ -0,0 +1,705 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config;
+use crate::inspector::{self, FindingAction};
+
+/// Built-in ruleset, used unless the user provides `policy.json` in the config directory
+const DEFAULT_POLICY: &str = include_str!("default_policy.json");
+const POLICY_FILE: &str = "policy.json";
+/// Holds the policy of the privileged helper, out of reach of the unprivileged user
+#[cfg(unix)]
+const SYSTEM_POLICY_DIR: &str = "/etc/ai-system-agent";
+
+/// Directories a program may be named by full path from; anywhere else the path could
+/// point at a look-alike binary that matches the rule of the program it imitates
+const TRUSTED_PROGRAM_DIRS: &[&str] = &[
+    "/usr/bin",
+    "/bin",
+    "/usr/sbin",
+    "/sbin",
+    r"C:\Windows\System32",
+];
+
+/// Characters a shell (or `cmd /C` on Windows) would interpret in a program name
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '&', '|', '`', '$', '<', '>', '(', ')', '\n', '\r', '"', '\'', '*', '?', '%', '^',
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl RiskLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        }
+    }
+}
+
+/// A subcommand (positional word, or two words like `system prune`) and its risk
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubcommandRule {
+    pub names: Vec<String>,
+    pub risk: RiskLevel,
+    #[serde(default)]
+    pub forbidden: bool,
+    #[serde(default)]
+    pub forbidden_flags: Vec<String>,
+    /// Overrides the rule's `requires_sudo` for this subcommand
+    #[serde(default)]
+    pub requires_sudo: Option<bool>,
+}
+
+/// Flags that raise the risk of an otherwise allowed command
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FlagRisk {
+    pub flags: Vec<String>,
+    pub risk: RiskLevel,
+    #[serde(default)]
+    pub requires_sudo: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommandRule {
+    pub program: String,
+    #[serde(default)]
+    pub description: String,
+    /// Risk when no subcommand rule matches
+    pub risk: RiskLevel,
+    /// Whether the command needs elevated privileges when no subcommand rule says otherwise
+    #[serde(default)]
+    pub requires_sudo: bool,
+    /// Index of the positional argument holding the subcommand (`service nginx stop` uses 1)
+    #[serde(default)]
+    pub subcommand_position: usize,
+    #[serde(default)]
+    pub subcommands: Vec<SubcommandRule>,
+    /// Refuse subcommands not listed in `subcommands`
+    #[serde(default)]
+    pub deny_unknown_subcommands: bool,
+    /// When set, any flag outside this list is refused
+    #[serde(default)]
+    pub allowed_flags: Option<Vec<String>>,
+    #[serde(default)]
+    pub forbidden_flags: Vec<String>,
+    #[serde(default)]
+    pub risky_flags: Vec<FlagRisk>,
+    /// Minimum risk when an argument points inside one of the policy's `system_paths`,
+    /// which also makes the command require elevated privileges
+    #[serde(default)]
+    pub path_risk: Option<RiskLevel>,
+    /// The program only reads; it runs in the sandbox, so no argument can make it write
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommandPolicy {
+    /// Paths that may never be passed as arguments
+    #[serde(default)]
+    pub protected_paths: Vec<String>,
+    #[serde(default)]
+    pub system_paths: Vec<String>,
+    /// Directories simulations may read a configuration file from (`nginx -c`, `apache2 -f`)
+    #[serde(default)]
+    pub config_paths: Vec<String>,
+    pub rules: Vec<CommandRule>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PolicyVerdict {
+    pub allowed: bool,
+    pub risk: RiskLevel,
+    pub requires_sudo: bool,
+    pub reason: String,
+    /// Program of the rule that matched, if any
+    pub rule: Option<String>,
+    pub subcommand: Option<String>,
+    /// Whether the matched rule is tagged `read_only`
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl PolicyVerdict {
+    fn deny(reason: String, rule: Option<&CommandRule>) -> Self {
+        Self {
+            allowed: false,
+            risk: RiskLevel::High,
+            requires_sudo: false,
+            reason,
+            rule: rule.map(|r| r.program.clone()),
+            subcommand: None,
+            read_only: false,
+        }
+    }
+}
+
+impl Default for CommandPolicy {
+    fn default() -> Self {
+        serde_json::from_str(DEFAULT_POLICY).expect("default_policy.json is invalid")
+    }
+}
+
+impl CommandPolicy {
+    /// Load the user policy from the config directory, falling back to the built-in one
+    pub fn load() -> Result<Self, String> {
+        Self::load_from(&config::get_config_dir()?)
+    }
+
+    /// Load `policy.json` from `dir`, falling back to the built-in one
+    pub fn load_from(dir: &Path) -> Result<Self, String> {
+        let path = dir.join(POLICY_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Erro ao ler política de comandos: {}", e))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Erro ao parsear política de comandos: {}", e))
+    }
+
+    /// Load the policy the privileged helper checks requests against: `policy.json` in
+    /// `SYSTEM_POLICY_DIR` when only root can change it, otherwise the built-in one
+    #[cfg(unix)]
+    pub fn load_system() -> Result<Self, String> {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = Path::new(SYSTEM_POLICY_DIR);
+        if !dir.join(POLICY_FILE).exists() {
+            return Ok(Self::default());
+        }
+        for path in [dir.to_path_buf(), dir.join(POLICY_FILE)] {
+            let metadata = fs::metadata(&path)
+                .map_err(|e| format!("Erro ao ler política de comandos: {}", e))?;
+            if metadata.uid() != 0 || metadata.mode() & 0o022 != 0 {
+                return Err(format!(
+                    "⚠️ SEGURANÇA: '{}' deve pertencer ao root e não ser gravável por outros",
+                    path.display()
+                ));
+            }
+        }
+        Self::load_from(dir)
+    }
+
+    pub fn find_rule(&self, command: &str) -> Option<&CommandRule> {
+        let program = program_name(command);
+        self.rules.iter().find(|rule| rule.program == program)
+    }
+
+    /// Whether `path`, already normalized, is or lies inside a protected path
+    pub fn is_protected(&self, path: &Path) -> bool {
+        self.protected_paths.iter().any(|p| is_within(path, p))
+    }
+
+    /// Decide whether `command args` may run and how risky it is
+    pub fn evaluate(&self, command: &str, args: &[String]) -> PolicyVerdict {
+        self.evaluate_in(command, args, None)
+    }
+
+    /// Like `evaluate`, resolving relative arguments against the working directory `cwd`
+    pub fn evaluate_in(&self, command: &str, args: &[String], cwd: Option<&Path>) -> PolicyVerdict {
+        if command.is_empty()
+            || command.chars().any(char::is_whitespace)
+            || command.contains(SHELL_METACHARACTERS)
+        {
+            return PolicyVerdict::deny(
+                format!(
+                    "O nome do comando '{}' contém caracteres não permitidos",
+                    command
+                ),
+                None,
+            );
+        }
+
+        if !is_trusted_program_path(command) {
+            return PolicyVerdict::deny(
+                format!(
+                    "O comando '{}' deve ser um nome de programa ou estar em um diretório do sistema",
+                    command
+                ),
+                None,
+            );
+        }
+
+        let Some(rule) = self.find_rule(command) else {
+            return PolicyVerdict::deny(
+                format!(
+                    "Comando '{}' não está na lista de comandos permitidos",
+                    program_name(command)
+                ),
+                None,
+            );
+        };
+
+        if let Some(flag) = find_flag(args, &rule.forbidden_flags) {
+            return PolicyVerdict::deny(
+                format!("A opção '{}' não é permitida para '{}'", flag, rule.program),
+                Some(rule),
+            );
+        }
+
+        if let Some(allowed) = &rule.allowed_flags {
+            if let Some(flag) = args
+                .iter()
+                .filter(|arg| is_flag(arg))
+                .find(|arg| !allowed.iter().any(|a| flag_matches(arg, a)))
+            {
+                return PolicyVerdict::deny(
+                    format!(
+                        "A opção '{}' não está entre as permitidas para '{}'",
+                        flag, rule.program
+                    ),
+                    Some(rule),
+                );
+            }
+        }
+
+        if let Some(cwd) = cwd {
+            let cwd = normalize_path(&cwd.to_string_lossy());
+            if self.is_protected(&cwd) {
+                return PolicyVerdict::deny(
+                    format!("O diretório de trabalho '{}' é protegido", cwd.display()),
+                    Some(rule),
+                );
+            }
+        }
+
+        for arg in args {
+            for path in path_candidates_in(arg, cwd) {
+                if self.is_protected(&path) {
+                    return PolicyVerdict::deny(
+                        format!(
+                            "O caminho '{}' é protegido e não pode ser usado",
+                            path.display()
+                        ),
+                        Some(rule),
+                    );
+                }
+            }
+        }
+
+        let mut risk = rule.risk;
+        let mut requires_sudo = rule.requires_sudo;
+        let mut reasons = Vec::new();
+        let positionals: Vec<&String> = args.iter().filter(|a| !is_flag(a)).collect();
+
+        let subcommand = match_subcommand(rule, &positionals);
+        match &subcommand {
+            Some((name, sub)) => {
+                if sub.forbidden {
+                    return PolicyVerdict::deny(
+                        format!("'{} {}' não é permitido", rule.program, name),
+                        Some(rule),
+                    );
+                }
+                if let Some(flag) = find_flag(args, &sub.forbidden_flags) {
+                    return PolicyVerdict::deny(
+                        format!(
+                            "A opção '{}' não é permitida em '{} {}'",
+                            flag, rule.program, name
+                        ),
+                        Some(rule),
+                    );
+                }
+                risk = sub.risk;
+                requires_sudo = sub.requires_sudo.unwrap_or(rule.requires_sudo);
+                reasons.push(format!(
+                    "'{} {}' é de risco {}",
+                    rule.program,
+                    name,
+                    sub.risk.as_str()
+                ));
+            }
+            None => {
+                if let Some(word) = positionals.get(rule.subcommand_position) {
+                    if rule.deny_unknown_subcommands {
+                        return PolicyVerdict::deny(
+                            format!(
+                                "Subcomando '{}' não é permitido para '{}'",
+                                word, rule.program
+                            ),
+                            Some(rule),
+                        );
+                    }
+                }
+                reasons.push(format!(
+                    "'{}' é de risco {}",
+                    rule.program,
+                    rule.risk.as_str()
+                ));
+            }
+        }
+
+        for flag_risk in &rule.risky_flags {
+            if let Some(flag) = find_flag(args, &flag_risk.flags) {
+                if flag_risk.risk > risk {
+                    risk = flag_risk.risk;
+                }
+                requires_sudo |= flag_risk.requires_sudo;
+                reasons.push(format!(
+                    "a opção '{}' eleva o risco para {}",
+                    flag,
+                    flag_risk.risk.as_str()
+                ));
+            }
+        }
+
+        if let Some(path_risk) = rule.path_risk {
+            let system_path = args
+                .iter()
+                .flat_map(|arg| path_candidates_in(arg, cwd))
+                .find(|path| self.system_paths.iter().any(|p| is_within(path, p)));
+            if let Some(path) = system_path {
+                if path_risk > risk {
+                    risk = path_risk;
+                }
+                requires_sudo = true;
+                reasons.push(format!("'{}' é um caminho do sistema", path.display()));
+            }
+        }
+
+        for finding in inspector::inspect(command, args, &self.system_paths, cwd) {
+            match finding.action {
+                FindingAction::Block => return PolicyVerdict::deny(finding.reason, Some(rule)),
+                FindingAction::Escalate => {
+                    risk = RiskLevel::High;
+                    reasons.push(finding.reason);
+                }
+            }
+        }
+
+        PolicyVerdict {
+            allowed: true,
+            risk,
+            requires_sudo,
+            reason: reasons.join("; "),
+            rule: Some(rule.program.clone()),
+            subcommand: subcommand.map(|(name, _)| name),
+            read_only: rule.read_only,
+        }
+    }
+}
+
+/// Base name of a program, accepting full paths like `/usr/bin/apt`
+pub fn program_name(command: &str) -> &str {
+    let name = command.rsplit(['/', '\\']).next().unwrap_or(command);
+    match name.len().checked_sub(4) {
+        Some(stem)
+            if name
+                .get(stem..)
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(".exe")) =>
+        {
+            &name[..stem]
+        }
+        _ => name,
+    }
+}
+
+/// A bare program name, looked up in `PATH`, or a path directly inside a system directory
+fn is_trusted_program_path(command: &str) -> bool {
+    let Some((dir, _)) = command.rsplit_once(['/', '\\']) else {
+        return true;
+    };
+    TRUSTED_PROGRAM_DIRS.iter().any(|trusted| {
+        if cfg!(target_os = "windows") {
+            dir.eq_ignore_ascii_case(trusted)
+        } else {
+            dir == *trusted
+        }
+    })
+}
+
+pub fn is_flag(arg: &str) -> bool {
+    arg.starts_with('-') && arg.len() > 1
+}
+
+/// Whether `arg` sets `flag`, handling `--opt=value` and clustered short flags like `-rf`
+pub fn flag_matches(arg: &str, flag: &str) -> bool {
+    if arg == flag {
+        return true;
+    }
+    if flag.starts_with("--") {
+        // `--opt=value` matches `--opt`; rules like `--net=host` need the exact form
+        return !flag.contains('=') && arg.split_once('=').is_some_and(|(name, _)| name == flag);
+    }
+    // `-X` inside a cluster of short flags such as `-Syu`
+    let mut flag_chars = flag.chars();
+    if let (Some('-'), Some(letter), None) =
+        (flag_chars.next(), flag_chars.next(), flag_chars.next())
+    {
+        return arg.starts_with('-')
+            && !arg.starts_with("--")
+            && arg[1..].chars().all(|c| c.is_ascii_alphabetic())
+            && arg[1..].contains(letter);
+    }
+    false
+}
+
+/// First argument setting one of `flags`, also matching the `--opt value` form of `--opt=value` rules
+pub fn find_flag<'a>(args: &'a [String], flags: &[String]) -> Option<&'a str> {
+    for (i, arg) in args.iter().enumerate() {
+        if !is_flag(arg) {
+            continue;
+        }
+        for flag in flags {
+            if flag_matches(arg, flag) {
+                return Some(arg);
+            }
+            if let Some((name, value)) = flag.split_once('=') {
+                if arg == name && args.get(i + 1).is_some_and(|next| next == value) {
+                    return Some(arg);
+                }
+            }
+        }
+    }
+    None
+}
+
+fn match_subcommand<'a>(
+    rule: &'a CommandRule,
+    positionals: &[&String],
+) -> Option<(String, &'a SubcommandRule)> {
+    let first = positionals.get(rule.subcommand_position)?;
+    let two_words = positionals
+        .get(rule.subcommand_position + 1)
+        .map(|second| format!("{} {}", first, second));
+
+    // Prefer the more specific two-word match (`system prune` over `system`)
+    if let Some(two_words) = two_words {
+        if let Some(sub) = rule
+            .subcommands
+            .iter()
+            .find(|s| s.names.contains(&two_words))
+        {
+            return Some((two_words, sub));
+        }
+    }
+    rule.subcommands
+        .iter()
+        .find(|s| s.names.iter().any(|n| n == *first))
+        .map(|sub| (first.to_string(), sub))
+}
+
+/// Paths an argument may refer to: the argument itself or the value of `--opt=/path`
+pub fn path_candidates(arg: &str) -> Vec<PathBuf> {
+    let mut candidates = vec![arg];
+    if let Some((_, value)) = arg.split_once('=') {
+        candidates.push(value);
+    }
+    candidates
+        .into_iter()
+        .filter(|c| looks_like_path(c))
+        .map(normalize_path)
+        .collect()
+}
+
+/// `path_candidates`, plus relative arguments joined to `cwd`, or to the app's own working
+/// directory that commands without one inherit
+pub fn path_candidates_in(arg: &str, cwd: Option<&Path>) -> Vec<PathBuf> {
+    let mut candidates = path_candidates(arg);
+    let value = match arg.split_once('=') {
+        Some((name, value)) if is_flag(name) => value,
+        _ if is_flag(arg) => return candidates,
+        _ => arg,
+    };
+    if !value.is_empty() && !looks_like_path(value) {
+        // When even that is unknown, the root is the guess that reaches the most system paths
+        let base = cwd
+            .map(Path::to_path_buf)
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("/"));
+        candidates.push(normalize_path(&base.join(value).to_string_lossy()));
+    }
+    candidates
+}
+
+fn looks_like_path(value: &str) -> bool {
+    let bytes = value.as_bytes();
+    value.starts_with('/')
+        || value.starts_with('~')
+        || (bytes.len() >= 3
+            && bytes[0].is_ascii_alphabetic()
+            && bytes[1] == b':'
+            && (bytes[2] == b'\\' || bytes[2] == b'/'))
+}
+
+/// Expand `~` and resolve `.`/`..` lexically so `/tmp/../etc/shadow` is caught
+pub fn normalize_path(value: &str) -> PathBuf {
+    let expanded = match value.strip_prefix('~') {
+        Some(rest) => {
+            let home = std::env::var("HOME")
+                .or_else(|_| std::env::var("USERPROFILE"))
+                .unwrap_or_default();
+            format!("{}{}", home, rest)
+        }
+        None => value.to_string(),
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in Path::new(&expanded).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Whether `path` is `base` or inside it (case-insensitive for Windows drive paths)
+pub fn is_within(path: &Path, base: &str) -> bool {
+    let base = normalize_path(base);
+    if base.as_os_str().is_empty() {
+        return false;
+    }
+    if path.starts_with(&base) {
+        return true;
+    }
+    let path = path.to_string_lossy().replace('\\', "/").to_lowercase();
+    let base = base.to_string_lossy().replace('\\', "/").to_lowercase();
+    path == base || path.starts_with(&format!("{}/", base.trim_end_matches('/')))
+}
+
+#[tauri::command]
+pub fn evaluate_command(
+    command: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+) -> Result<PolicyVerdict, String> {
+    Ok(CommandPolicy::load()?.evaluate_in(&command, &args, cwd.as_deref().map(Path::new)))
+}
+
+#[tauri::command]
+pub fn get_command_policy() -> Result<CommandPolicy, String> {
+    CommandPolicy::load()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn evaluate(command: &str, list: &[&str]) -> PolicyVerdict {
+        CommandPolicy::default().evaluate(command, &args(list))
+    }
+
+    #[test]
+    fn subcommand_rules_set_the_risk() {
+        let verdict = evaluate("apt", &["list", "--installed"]);
+        assert!(verdict.allowed);
+        assert_eq!(verdict.risk, RiskLevel::Low);
+        assert_eq!(verdict.subcommand.as_deref(), Some("list"));
+
+        let verdict = evaluate("docker", &["system", "prune"]);
+        assert!(verdict.allowed);
+        assert_eq!(verdict.risk, RiskLevel::High);
+        assert_eq!(verdict.subcommand.as_deref(), Some("system prune"));
+    }
+
+    #[test]
+    fn unknown_programs_are_refused() {
+        let verdict = evaluate("rm", &["-rf", "/tmp/x"]);
+        assert!(!verdict.allowed);
+        assert!(verdict.rule.is_none());
+    }
+
+    #[test]
+    fn metacharacters_in_the_program_are_refused() {
+        assert!(!evaluate("ls;id", &[]).allowed);
+        assert!(!evaluate("ls -la", &[]).allowed);
+        assert!(!evaluate("", &[]).allowed);
+    }
+
+    #[test]
+    fn risky_flags_raise_the_risk() {
+        assert_eq!(evaluate("dpkg", &["-l"]).risk, RiskLevel::Low);
+        assert_eq!(evaluate("dpkg", &["-i", "pkg.deb"]).risk, RiskLevel::Medium);
+        assert_eq!(
+            evaluate("dpkg", &["--purge", "nginx"]).risk,
+            RiskLevel::High
+        );
+        assert_eq!(
+            evaluate("docker", &["run", "--privileged", "alpine"]).risk,
+            RiskLevel::High
+        );
+    }
+
+    #[test]
+    fn protected_paths_are_refused() {
+        assert!(!evaluate("cat", &["/etc/shadow"]).allowed);
+        assert!(!evaluate("ls", &["/etc/sudoers.d/"]).allowed);
+        assert!(!evaluate("grep", &["root", "/etc/../etc/shadow"]).allowed);
+        assert!(evaluate("cat", &["/etc/hostname"]).allowed);
+    }
+
+    #[test]
+    fn relative_paths_without_a_cwd_resolve_against_the_app_directory() {
+        let here = std::env::current_dir().unwrap();
+        let mut policy = CommandPolicy::default();
+        policy
+            .protected_paths
+            .push(here.join("segredo").to_string_lossy().to_string());
+
+        assert!(!policy.evaluate("cat", &args(&["segredo/chave"])).allowed);
+        assert!(!policy.evaluate("cat", &args(&["./segredo"])).allowed);
+        assert!(policy.evaluate("cat", &args(&["publico/chave"])).allowed);
+    }
+
+    #[test]
+    fn parent_components_are_resolved_before_the_check() {
+        let depth = std::env::current_dir().unwrap().components().count();
+        let shadow = format!("{}etc/shadow", "../".repeat(depth));
+        assert!(!evaluate("cat", &[&shadow]).allowed);
+
+        let verdict = CommandPolicy::default().evaluate_in(
+            "cat",
+            &args(&["../shadow"]),
+            Some(Path::new("/etc/ssl")),
+        );
+        assert!(!verdict.allowed);
+    }
+
+    #[test]
+    fn protected_working_directory_is_refused() {
+        let verdict =
+            CommandPolicy::default().evaluate_in("ls", &[], Some(Path::new("/root/.ssh")));
+        assert!(!verdict.allowed);
+    }
+
+    #[test]
+    fn read_only_programs_are_tagged() {
+        assert!(evaluate("cat", &["/etc/hostname"]).read_only);
+        assert!(evaluate("ps", &["aux"]).read_only);
+        assert!(!evaluate("systemctl", &["status", "nginx"]).read_only);
+    }
+
+    #[test]
+    fn programs_outside_system_directories_are_refused() {
+        assert!(evaluate("/usr/bin/apt", &["list"]).allowed);
+        assert!(!evaluate("./apt", &["list"]).allowed);
+        assert!(!evaluate("/tmp/evil/apt", &["list"]).allowed);
+        assert!(!evaluate("bin/apt", &["list"]).allowed);
+    }
+
+    #[test]
+    fn exe_suffix_is_stripped_case_insensitively() {
+        assert_eq!(program_name("ipconfig.exe"), "ipconfig");
+        assert_eq!(program_name("IPCONFIG.EXE"), "IPCONFIG");
+        assert_eq!(program_name("/usr/bin/apt"), "apt");
+    }
+}
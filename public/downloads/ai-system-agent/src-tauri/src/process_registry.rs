@@ -0,0 +1,431 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::State;
+
+use crate::executor::LimitKind;
+
+/// How long a stopped process gets to exit after SIGTERM before it is killed
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RunningCommand {
+    pub id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub pid: u32,
+    pub started_at: String,
+}
+
+/// Delivers signals to a process the app cannot signal itself, such as one the
+/// privileged helper runs as root
+pub trait Signaller: Send + Sync {
+    /// SIGTERM the process group, or SIGKILL it when `force` is set
+    fn signal(&self, force: bool) -> Result<(), String>;
+}
+
+impl fmt::Debug for dyn Signaller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Signaller")
+    }
+}
+
+/// Flags shared between the executor waiting on a process and whoever cancels it
+#[derive(Debug, Default)]
+pub struct ProcessState {
+    pub cancelled: AtomicBool,
+    pub finished: AtomicBool,
+    pub limit_hit: Mutex<Option<LimitKind>>,
+    /// Set when signals cannot be sent to the process directly
+    pub signaller: Option<Box<dyn Signaller>>,
+}
+
+impl ProcessState {
+    /// Record the first limit that caused the process to be stopped
+    pub fn record_limit(&self, kind: LimitKind) {
+        let mut limit_hit = self.limit_hit.lock().unwrap();
+        if limit_hit.is_none() {
+            *limit_hit = Some(kind);
+        }
+    }
+
+    /// Undo `record_limit` when the process could not be stopped, so the result does
+    /// not claim a limit that was never enforced
+    fn forget_limit(&self, kind: LimitKind) {
+        let mut limit_hit = self.limit_hit.lock().unwrap();
+        if *limit_hit == Some(kind) {
+            *limit_hit = None;
+        }
+    }
+}
+
+struct Entry {
+    info: RunningCommand,
+    state: Arc<ProcessState>,
+}
+
+/// Live processes spawned by the executor, keyed by their `CommandResult` id
+#[derive(Default)]
+pub struct ProcessRegistry {
+    running: Mutex<HashMap<String, Entry>>,
+}
+
+impl ProcessRegistry {
+    pub fn register(&self, info: RunningCommand) -> Arc<ProcessState> {
+        self.register_with(info, ProcessState::default())
+    }
+
+    pub fn register_with(&self, info: RunningCommand, state: ProcessState) -> Arc<ProcessState> {
+        let state = Arc::new(state);
+        let mut running = self.running.lock().unwrap();
+        running.insert(
+            info.id.clone(),
+            Entry {
+                info,
+                state: state.clone(),
+            },
+        );
+        state
+    }
+
+    pub fn unregister(&self, id: &str) {
+        let mut running = self.running.lock().unwrap();
+        if let Some(entry) = running.remove(id) {
+            entry.state.finished.store(true, Ordering::SeqCst);
+        }
+    }
+
+    pub fn list(&self) -> Vec<RunningCommand> {
+        let running = self.running.lock().unwrap();
+        let mut list: Vec<RunningCommand> = running.values().map(|e| e.info.clone()).collect();
+        list.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        list
+    }
+
+    /// Ask a process to terminate, killing it if it is still alive after the grace period
+    pub fn cancel(&self, id: &str) -> Result<(), String> {
+        let (pid, state) = {
+            let running = self.running.lock().unwrap();
+            let entry = running
+                .get(id)
+                .ok_or_else(|| format!("Nenhum comando em execução com id '{}'", id))?;
+            (entry.info.pid, entry.state.clone())
+        };
+
+        state.cancelled.store(true, Ordering::SeqCst);
+        if let Err(e) = stop(pid, state.clone()) {
+            // The command keeps running, so its result must not read as cancelled
+            state.cancelled.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+        Ok(())
+    }
+}
+
+/// Send SIGTERM to a process and escalate to SIGKILL if it outlives the grace period
+pub fn stop(pid: u32, state: Arc<ProcessState>) -> Result<(), String> {
+    stop_after(pid, state, CANCEL_GRACE_PERIOD)
+}
+
+/// Stop a process that exceeded `kind`, recording the limit only if it could be signalled
+pub fn enforce_limit(pid: u32, state: Arc<ProcessState>, kind: LimitKind) {
+    state.record_limit(kind);
+    if let Err(e) = stop(pid, state.clone()) {
+        state.forget_limit(kind);
+        eprintln!("[LIMITE] {}", e);
+    }
+}
+
+fn stop_after(pid: u32, state: Arc<ProcessState>, grace: Duration) -> Result<(), String> {
+    signal(pid, &state, false)?;
+
+    thread::spawn(move || {
+        thread::sleep(grace);
+        if !state.finished.load(Ordering::SeqCst) {
+            let _ = signal(pid, &state, true);
+        }
+    });
+
+    Ok(())
+}
+
+fn signal(pid: u32, state: &ProcessState, force: bool) -> Result<(), String> {
+    match &state.signaller {
+        Some(signaller) => signaller.signal(force),
+        None if force => kill(pid),
+        None => terminate(pid),
+    }
+}
+
+/// Keeps a process registered while the executor waits on it
+pub struct Registration<'a> {
+    registry: &'a ProcessRegistry,
+    id: String,
+    pub state: Arc<ProcessState>,
+}
+
+impl<'a> Registration<'a> {
+    pub fn new(registry: &'a ProcessRegistry, info: RunningCommand) -> Self {
+        Self::with_signaller(registry, info, None)
+    }
+
+    pub fn with_signaller(
+        registry: &'a ProcessRegistry,
+        info: RunningCommand,
+        signaller: Option<Box<dyn Signaller>>,
+    ) -> Self {
+        let id = info.id.clone();
+        let state = registry.register_with(
+            info,
+            ProcessState {
+                signaller,
+                ..ProcessState::default()
+            },
+        );
+        Self {
+            registry,
+            id,
+            state,
+        }
+    }
+
+    pub fn was_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    pub fn limit_hit(&self) -> Option<LimitKind> {
+        *self.state.limit_hit.lock().unwrap()
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.registry.unregister(&self.id);
+    }
+}
+
+// Processes are spawned as their own process group leader, so signalling the
+// negative pid also reaches anything they forked.
+#[cfg(unix)]
+fn signal_group(pid: u32, signal: libc::c_int) -> Result<(), String> {
+    // -0 is our own group and -1 every process we may signal
+    if pid <= 1 {
+        return Err(format!("PID inválido para sinalizar: {}", pid));
+    }
+    let rc = unsafe { libc::kill(-(pid as libc::pid_t), signal) };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(signal_error(pid, std::io::Error::last_os_error()))
+    }
+}
+
+/// Backends like pkexec and doas leave nothing in the group running as the user, so
+/// the app may not signal what they started
+#[cfg(unix)]
+fn signal_error(pid: u32, error: std::io::Error) -> String {
+    if error.raw_os_error() == Some(libc::EPERM) {
+        format!(
+            "Não é possível cancelar o comando elevado {}: ele roda como root e o backend de \
+             elevação não repassa sinais. Inicie o processo auxiliar privilegiado para poder cancelá-lo",
+            pid
+        )
+    } else {
+        format!("Erro ao sinalizar processo {}: {}", pid, error)
+    }
+}
+
+#[cfg(unix)]
+pub fn terminate(pid: u32) -> Result<(), String> {
+    signal_group(pid, libc::SIGTERM)
+}
+
+#[cfg(unix)]
+pub fn kill(pid: u32) -> Result<(), String> {
+    signal_group(pid, libc::SIGKILL)
+}
+
+#[cfg(windows)]
+fn taskkill(pid: u32, force: bool) -> Result<(), String> {
+    let mut cmd = std::process::Command::new("taskkill");
+    cmd.args(["/PID", &pid.to_string(), "/T"]);
+    if force {
+        cmd.arg("/F");
+    }
+    let status = cmd
+        .status()
+        .map_err(|e| format!("Erro ao executar taskkill: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("taskkill falhou para o processo {}", pid))
+    }
+}
+
+#[cfg(windows)]
+pub fn terminate(pid: u32) -> Result<(), String> {
+    taskkill(pid, false)
+}
+
+#[cfg(windows)]
+pub fn kill(pid: u32) -> Result<(), String> {
+    taskkill(pid, true)
+}
+
+#[tauri::command]
+pub fn list_running_commands(registry: State<'_, ProcessRegistry>) -> Vec<RunningCommand> {
+    registry.list()
+}
+
+#[tauri::command]
+pub fn cancel_command(id: String, registry: State<'_, ProcessRegistry>) -> Result<String, String> {
+    registry.cancel(&id)?;
+    Ok(format!("Cancelamento solicitado para o comando {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the signals it is asked to send, failing them when `fails` is set
+    struct FakeSignaller {
+        sent: Arc<Mutex<Vec<bool>>>,
+        fails: bool,
+    }
+
+    impl Signaller for FakeSignaller {
+        fn signal(&self, force: bool) -> Result<(), String> {
+            self.sent.lock().unwrap().push(force);
+            if self.fails {
+                Err("sem permissão".to_string())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn info(id: &str, started_at: &str) -> RunningCommand {
+        RunningCommand {
+            id: id.to_string(),
+            command: "sleep".to_string(),
+            args: vec!["30".to_string()],
+            pid: 4242,
+            started_at: started_at.to_string(),
+        }
+    }
+
+    fn faked(fails: bool) -> (ProcessState, Arc<Mutex<Vec<bool>>>) {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let state = ProcessState {
+            signaller: Some(Box::new(FakeSignaller {
+                sent: sent.clone(),
+                fails,
+            })),
+            ..ProcessState::default()
+        };
+        (state, sent)
+    }
+
+    #[test]
+    fn registrations_are_listed_until_dropped() {
+        let registry = ProcessRegistry::default();
+        let later = Registration::new(&registry, info("b", "2026-01-01T00:00:02Z"));
+        let state = {
+            let earlier = Registration::new(&registry, info("a", "2026-01-01T00:00:01Z"));
+            let ids: Vec<String> = registry.list().into_iter().map(|c| c.id).collect();
+            assert_eq!(ids, ["a", "b"]);
+            earlier.state.clone()
+        };
+
+        assert!(state.finished.load(Ordering::SeqCst));
+        assert_eq!(registry.list().len(), 1);
+        assert!(!later.was_cancelled());
+        assert!(registry.cancel("a").is_err());
+    }
+
+    #[test]
+    fn stop_escalates_to_kill_after_the_grace_period() {
+        let (state, sent) = faked(false);
+        stop_after(4242, Arc::new(state), Duration::from_millis(10)).unwrap();
+        assert_eq!(*sent.lock().unwrap(), [false]);
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*sent.lock().unwrap(), [false, true]);
+    }
+
+    #[test]
+    fn stop_does_not_kill_a_process_that_exited() {
+        let (state, sent) = faked(false);
+        let state = Arc::new(state);
+        stop_after(4242, state.clone(), Duration::from_millis(10)).unwrap();
+        state.finished.store(true, Ordering::SeqCst);
+
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(*sent.lock().unwrap(), [false]);
+    }
+
+    #[test]
+    fn cancel_that_cannot_signal_is_not_reported_as_cancelled() {
+        let registry = ProcessRegistry::default();
+        let (state, _) = faked(true);
+        let state = registry.register_with(info("a", ""), state);
+
+        assert!(registry.cancel("a").is_err());
+        assert!(!state.cancelled.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn limit_is_only_recorded_when_the_process_could_be_stopped() {
+        let (state, _) = faked(true);
+        let state = Arc::new(state);
+        enforce_limit(4242, state.clone(), LimitKind::Timeout);
+        assert_eq!(*state.limit_hit.lock().unwrap(), None);
+
+        let (state, _) = faked(false);
+        let state = Arc::new(state);
+        enforce_limit(4242, state.clone(), LimitKind::Timeout);
+        assert_eq!(*state.limit_hit.lock().unwrap(), Some(LimitKind::Timeout));
+        state.finished.store(true, Ordering::SeqCst);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_terminates_the_process_group() {
+        use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let registry = ProcessRegistry::default();
+        let mut running = info("a", "");
+        running.pid = child.id();
+        let registration = Registration::new(&registry, running);
+
+        registry.cancel("a").unwrap();
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+        assert!(registration.was_cancelled());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn own_group_and_every_process_are_never_signalled() {
+        assert!(terminate(0).is_err());
+        assert!(kill(1).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn permission_errors_explain_the_elevated_command_cannot_be_cancelled() {
+        let denied = signal_error(4242, std::io::Error::from_raw_os_error(libc::EPERM));
+        assert!(denied.contains("elevado"));
+        let missing = signal_error(4242, std::io::Error::from_raw_os_error(libc::ESRCH));
+        assert!(missing.starts_with("Erro ao sinalizar"));
+    }
+}
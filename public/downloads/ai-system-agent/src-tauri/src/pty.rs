@@ -0,0 +1,541 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use portable_pty::{native_pty_system, Child, ChildKiller, CommandBuilder, MasterPty, PtySize};
+use tauri::{Manager, State, Window};
+use uuid::Uuid;
+
+use crate::approval::ApprovalStore;
+use crate::audit::{AuditEntry, AuditLog};
+use crate::commands::{self, CommandResult};
+use crate::config;
+use crate::executor::{self, ExecutionContext};
+use crate::history::HistoryStore;
+use crate::process_registry::{ProcessRegistry, RunningCommand};
+use crate::queue::CommandQueue;
+use crate::sandbox;
+use crate::session::SessionStore;
+use crate::snapshot;
+
+/// Event carrying terminal output from a session
+pub const PTY_OUTPUT_EVENT: &str = "pty-output";
+/// Event emitted once the program of a session has exited
+pub const PTY_EXIT_EVENT: &str = "pty-exit";
+
+const READ_BUFFER_SIZE: usize = 8192;
+/// How long to wait for the last output after the program exits; background
+/// processes may keep the terminal open indefinitely
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+/// `TERM` for programs that do not get one from the environment
+const DEFAULT_TERM: &str = "xterm-256color";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PtySessionInfo {
+    pub id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub pid: Option<u32>,
+    pub rows: u16,
+    pub cols: u16,
+    pub started_at: String,
+    /// Snapshot of the files the program could change, taken before it started
+    pub snapshot_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PtyOutput {
+    pub id: String,
+    pub data: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PtyExit {
+    pub id: String,
+    pub success: bool,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+    pub closed: bool,
+}
+
+struct PtySession {
+    info: Mutex<PtySessionInfo>,
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    /// Input for the session's writer thread
+    input: mpsc::Sender<Vec<u8>>,
+    killer: Mutex<Box<dyn ChildKiller + Send + Sync>>,
+}
+
+/// Open pseudo-terminal sessions, keyed by session id
+#[derive(Default)]
+pub struct PtyStore {
+    sessions: Mutex<HashMap<String, Arc<PtySession>>>,
+}
+
+impl PtyStore {
+    fn get(&self, id: &str) -> Result<Arc<PtySession>, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Nenhuma sessão de terminal com id '{}'", id))
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+
+    pub fn list(&self) -> Vec<PtySessionInfo> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut list: Vec<PtySessionInfo> = sessions
+            .values()
+            .map(|s| s.info.lock().unwrap().clone())
+            .collect();
+        list.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+        list
+    }
+}
+
+fn pty_size(rows: u16, cols: u16) -> Result<PtySize, String> {
+    if rows == 0 || cols == 0 {
+        return Err("Tamanho de terminal inválido".to_string());
+    }
+    Ok(PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    })
+}
+
+/// Look the program up in the `PATH` it will run with.
+///
+/// portable-pty tries the working directory before `PATH`, which would let a file
+/// in that directory stand in for the program the policy approved.
+#[cfg(unix)]
+fn resolve_program(
+    program: &std::ffi::OsStr,
+    path: Option<&std::ffi::OsStr>,
+) -> Result<std::ffi::OsString, String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let program_path = std::path::Path::new(program);
+    if program_path.components().count() > 1 {
+        // Absolute paths come from the policy's trusted directories or the elevation backend
+        if program_path.is_absolute() {
+            return Ok(program.to_owned());
+        }
+        return Err(format!(
+            "⚠️ SEGURANÇA: Caminho relativo de programa não permitido: '{}'",
+            program.to_string_lossy()
+        ));
+    }
+    path.into_iter()
+        .flat_map(std::env::split_paths)
+        .filter(|dir| dir.is_absolute())
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            candidate
+                .metadata()
+                .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        })
+        .map(|candidate| candidate.into_os_string())
+        .ok_or_else(|| {
+            format!(
+                "Programa não encontrado no PATH: '{}'",
+                program.to_string_lossy()
+            )
+        })
+}
+
+/// Same wrapping as one-shot executions, so elevation goes through the configured backend / UAC as well.
+/// A `sandboxed` program is started through `sandbox::EXEC_ARG`, as the PTY has no pre-exec hook.
+fn build_pty_command(
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    sandboxed: bool,
+    context: &ExecutionContext,
+) -> Result<CommandBuilder, String> {
+    let process = executor::build_command(command, args, requires_sudo)?;
+    let environment = context.environment(&config::get_config().unwrap_or_default().env_allowlist);
+
+    #[cfg(unix)]
+    let program = resolve_program(
+        process.get_program(),
+        environment
+            .iter()
+            .rev()
+            .find(|(name, _)| name == "PATH")
+            .map(|(_, value)| value.as_os_str()),
+    )?;
+    #[cfg(not(unix))]
+    let program = process.get_program().to_owned();
+
+    let mut builder = if sandboxed {
+        let exe = std::env::current_exe()
+            .map_err(|e| format!("Erro ao localizar o executável: {}", e))?;
+        let mut builder = CommandBuilder::new(exe);
+        builder.arg(sandbox::EXEC_ARG);
+        builder.arg(program);
+        builder
+    } else {
+        CommandBuilder::new(program)
+    };
+    builder.args(process.get_args());
+    builder.env_clear();
+    builder.env("TERM", DEFAULT_TERM);
+    for (name, value) in environment {
+        builder.env(name, value);
+    }
+    for (name, value) in process.get_envs() {
+        if let Some(value) = value {
+            builder.env(name, value);
+        }
+    }
+    if let Some(cwd) = &context.cwd {
+        builder.cwd(cwd);
+    }
+    Ok(builder)
+}
+
+struct Spawned {
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    reader: Box<dyn Read + Send>,
+    writer: Box<dyn Write + Send>,
+}
+
+fn spawn(builder: CommandBuilder, size: PtySize) -> Result<Spawned, String> {
+    let pair = native_pty_system()
+        .openpty(size)
+        .map_err(|e| format!("Erro ao abrir pseudo-terminal: {}", e))?;
+    let child = pair
+        .slave
+        .spawn_command(builder)
+        .map_err(|e| format!("Erro ao executar comando: {}", e))?;
+    // Only the child keeps the slave side open, so reads end when it is gone
+    drop(pair.slave);
+
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("Erro ao abrir pseudo-terminal: {}", e))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("Erro ao abrir pseudo-terminal: {}", e))?;
+    Ok(Spawned {
+        master: pair.master,
+        child,
+        reader,
+        writer,
+    })
+}
+
+/// Write input to the terminal in the order it was queued, until the session is dropped
+/// or the terminal stops accepting it
+fn spawn_writer(mut writer: Box<dyn Write + Send>) -> mpsc::Sender<Vec<u8>> {
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    thread::spawn(move || {
+        for data in rx {
+            if let Err(e) = writer.write_all(&data).and_then(|_| writer.flush()) {
+                eprintln!("[PTY] Erro ao escrever no terminal: {}", e);
+                break;
+            }
+        }
+    });
+    tx
+}
+
+/// Forward terminal output to the window, then send the SHA-256 of everything read on `done`
+fn spawn_reader(
+    mut reader: Box<dyn Read + Send>,
+    id: String,
+    window: Window,
+    done: mpsc::Sender<String>,
+) {
+    thread::spawn(move || {
+        let mut hasher = Sha256::new();
+        let mut pending = Vec::new();
+        let mut buf = [0u8; READ_BUFFER_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                // Linux reports EIO once the last process holding the terminal is gone
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    hasher.update(&buf[..n]);
+                    pending.extend_from_slice(&buf[..n]);
+                    let data = executor::take_utf8_prefix(&mut pending);
+                    if !data.is_empty() {
+                        let _ = window.emit(
+                            PTY_OUTPUT_EVENT,
+                            PtyOutput {
+                                id: id.clone(),
+                                data,
+                            },
+                        );
+                    }
+                }
+            }
+        }
+        let _ = done.send(format!("{:x}", hasher.finalize()));
+    });
+}
+
+/// Open a pseudo-terminal running `command`, e.g. `top`, `vim` or an interactive `mysql`.
+///
+/// The program goes through the same approval, policy and native confirmation as
+/// `execute_command`; what is typed into the session afterwards is not checked.
+/// Output arrives as `pty-output` events and `pty-exit` is emitted when it exits.
+///
+/// The session holds a slot of the execution pool until it exits. `ExecutionLimits` do
+/// not apply: a timeout or output cap would end an editor or shell the user is still in.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn open_pty_session(
+    window: Window,
+    command: String,
+    args: Vec<String>,
+    requires_sudo: bool,
+    approval_token: String,
+    cwd: Option<String>,
+    env: Option<BTreeMap<String, String>>,
+    session_id: Option<String>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+    snapshot_paths: Option<Vec<String>>,
+    approvals: State<'_, ApprovalStore>,
+    registry: State<'_, ProcessRegistry>,
+    sessions: State<'_, PtyStore>,
+    audit: State<'_, AuditLog>,
+    shell_sessions: State<'_, SessionStore>,
+    queue: State<'_, CommandQueue>,
+) -> Result<PtySessionInfo, String> {
+    let context = shell_sessions.context(session_id.as_deref(), cwd, env, requires_sudo)?;
+    let approval = commands::validate_execution(
+        &window,
+        &command,
+        &args,
+        requires_sudo,
+        &context,
+        &approval_token,
+        &approvals,
+    )
+    .await?;
+
+    let size = pty_size(rows.unwrap_or(24), cols.unwrap_or(80))?;
+    let slot = queue.owned_slot().await?;
+    let id = Uuid::new_v4().to_string();
+    let start = Instant::now();
+    // Editors are the usual way configuration gets changed by hand
+    let snapshot_id = snapshot::before_execution(
+        &window,
+        &id,
+        &command,
+        &args,
+        requires_sudo,
+        approval.risk,
+        &context,
+        &snapshot_paths.unwrap_or_default(),
+    )
+    .await;
+
+    let spawned =
+        sandbox::should_confine(&command, &args, requires_sudo, &context).and_then(|sandboxed| {
+            let builder = build_pty_command(&command, &args, requires_sudo, sandboxed, &context)?;
+            spawn(builder, size).map(|spawned| (spawned, sandboxed))
+        });
+    let (
+        Spawned {
+            master,
+            mut child,
+            reader,
+            writer,
+        },
+        sandboxed,
+    ) = match spawned {
+        Ok(spawned) => spawned,
+        Err(e) => {
+            commands::audit_execution(
+                &window,
+                approval,
+                &command,
+                &args,
+                requires_sudo,
+                &Err(e.clone()),
+                &audit,
+            );
+            return Err(e);
+        }
+    };
+
+    let info = PtySessionInfo {
+        id: id.clone(),
+        command: command.clone(),
+        args: args.clone(),
+        pid: child.process_id(),
+        rows: size.rows,
+        cols: size.cols,
+        started_at: Utc::now().to_rfc3339(),
+        snapshot_id: snapshot_id.clone(),
+    };
+    // Listed with the other running commands, so `cancel_command` reaches it too
+    let state = child.process_id().map(|pid| {
+        registry.register(RunningCommand {
+            id: id.clone(),
+            command: command.clone(),
+            args: args.clone(),
+            pid,
+            started_at: info.started_at.clone(),
+        })
+    });
+    sessions.sessions.lock().unwrap().insert(
+        id.clone(),
+        Arc::new(PtySession {
+            info: Mutex::new(info.clone()),
+            master: Mutex::new(master),
+            input: spawn_writer(writer),
+            killer: Mutex::new(child.clone_killer()),
+        }),
+    );
+
+    let (done_tx, done_rx) = mpsc::channel();
+    spawn_reader(reader, id.clone(), window.clone(), done_tx);
+
+    thread::spawn(move || {
+        let status = child.wait();
+        drop(slot);
+        let transcript_sha256 = done_rx.recv_timeout(DRAIN_TIMEOUT).ok();
+        let duration_ms = start.elapsed().as_millis() as u64;
+        let closed = state
+            .as_ref()
+            .is_some_and(|s| s.cancelled.load(std::sync::atomic::Ordering::SeqCst));
+
+        window.state::<ProcessRegistry>().unregister(&id);
+        window.state::<PtyStore>().remove(&id);
+
+        let outcome = status
+            .map(|status| CommandResult {
+                id: id.clone(),
+                command: command.clone(),
+                args: args.clone(),
+                success: status.success(),
+                stdout: String::new(),
+                stderr: String::new(),
+                exit_code: status.exit_code() as i32,
+                executed_at: Utc::now().to_rfc3339(),
+                duration_ms,
+                cancelled: closed,
+                limit_hit: None,
+                cwd: context.cwd.clone(),
+                env: context.env_names(),
+                snapshot_id: snapshot_id.clone(),
+                truncated: false,
+                output_handle: None,
+                encoding: None,
+                stdout_raw: None,
+                stderr_raw: None,
+                // A terminal has a single output stream; its digest is recorded as stdout
+                stdout_sha256: transcript_sha256,
+                stderr_sha256: None,
+                sandboxed,
+                usage: None,
+            })
+            .map_err(|e| format!("Erro ao aguardar comando: {}", e));
+
+        let _ = window.emit(
+            PTY_EXIT_EVENT,
+            PtyExit {
+                id: id.clone(),
+                success: outcome.as_ref().is_ok_and(|r| r.success),
+                exit_code: outcome.as_ref().map_or(-1, |r| r.exit_code),
+                duration_ms,
+                closed,
+            },
+        );
+
+        let entry = AuditEntry::execution(
+            window.label(),
+            approval,
+            &command,
+            &args,
+            requires_sudo,
+            &outcome,
+        );
+        if let Err(e) = window.state::<AuditLog>().append(entry) {
+            eprintln!("[AUDIT] {}", e);
+        }
+        if let Ok(result) = &outcome {
+            commands::log_execution(result, &window.state::<HistoryStore>());
+        }
+    });
+
+    Ok(info)
+}
+
+/// Send keystrokes or pasted text to the session.
+///
+/// Only queues the input: a program that stops reading fills the PTY buffer, and the
+/// write then blocks the session's writer thread instead of the caller.
+#[tauri::command]
+pub fn write_pty_session(
+    id: String,
+    data: String,
+    sessions: State<'_, PtyStore>,
+) -> Result<(), String> {
+    sessions
+        .get(&id)?
+        .input
+        .send(data.into_bytes())
+        .map_err(|_| "Erro ao escrever no terminal: a sessão não aceita mais entrada".to_string())
+}
+
+#[tauri::command]
+pub fn resize_pty_session(
+    id: String,
+    rows: u16,
+    cols: u16,
+    sessions: State<'_, PtyStore>,
+) -> Result<(), String> {
+    let session = sessions.get(&id)?;
+    session
+        .master
+        .lock()
+        .unwrap()
+        .resize(pty_size(rows, cols)?)
+        .map_err(|e| format!("Erro ao redimensionar terminal: {}", e))?;
+
+    let mut info = session.info.lock().unwrap();
+    info.rows = rows;
+    info.cols = cols;
+    Ok(())
+}
+
+/// Hang up the session, then stop whatever is still running in it
+#[tauri::command]
+pub fn close_pty_session(
+    id: String,
+    sessions: State<'_, PtyStore>,
+    registry: State<'_, ProcessRegistry>,
+) -> Result<(), String> {
+    let session = sessions.get(&id)?;
+    // SIGHUP is what closing a terminal sends; interactive shells ignore SIGTERM
+    let _ = session.killer.lock().unwrap().kill();
+    // Sessions are only registered when the platform reports a pid
+    if registry.list().iter().any(|c| c.id == id) {
+        registry.cancel(&id)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_pty_sessions(sessions: State<'_, PtyStore>) -> Vec<PtySessionInfo> {
+    sessions.list()
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use chrono::Utc;
+use tauri::{Manager, State, Window};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, SemaphorePermit};
+use uuid::Uuid;
+
+use crate::ai_client::SuggestedCommand;
+use crate::commands::CommandResult;
+use crate::config;
+use crate::policy::CommandPolicy;
+use crate::risk;
+use crate::simulate;
+
+/// Event carrying the whole queue after any change
+pub const QUEUE_EVENT: &str = "command-queue-changed";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueStatus {
+    /// Waiting for the user to approve or reject it
+    Pending,
+    /// Approved and waiting for a free execution slot
+    Approved,
+    Executing,
+    Completed,
+    Failed,
+}
+
+/// A suggested command and where it stands, as listed in the command queue
+#[derive(Debug, Serialize, Clone)]
+pub struct QueuedCommand {
+    pub id: String,
+    #[serde(flatten)]
+    pub suggestion: SuggestedCommand,
+    pub status: QueueStatus,
+    pub result: Option<CommandResult>,
+    pub error: Option<String>,
+    pub queued_at: String,
+    pub updated_at: String,
+}
+
+/// Commands suggested to the user, and the pool bounding how many commands run at once.
+///
+/// The queue lives in the backend so it survives webview reloads; every change is
+/// broadcast as `command-queue-changed`. Updates for a `None` id are ignored, so
+/// commands run outside the queue share the same code path.
+pub struct CommandQueue {
+    entries: Mutex<Vec<QueuedCommand>>,
+    /// Sized from `max_concurrent_commands` when the app starts
+    slots: Arc<Semaphore>,
+}
+
+impl Default for CommandQueue {
+    fn default() -> Self {
+        let slots = config::get_config()
+            .unwrap_or_default()
+            .max_concurrent_commands
+            .max(1);
+        Self {
+            entries: Mutex::default(),
+            slots: Arc::new(Semaphore::new(slots)),
+        }
+    }
+}
+
+impl CommandQueue {
+    /// Wait for a free execution slot, released when the permit is dropped
+    pub async fn slot(&self) -> Result<SemaphorePermit<'_>, String> {
+        self.slots
+            .acquire()
+            .await
+            .map_err(|_| "O pool de execução foi encerrado".to_string())
+    }
+
+    /// `slot`, for a permit that outlives the caller, such as a terminal session's
+    pub async fn owned_slot(&self) -> Result<OwnedSemaphorePermit, String> {
+        self.slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|_| "O pool de execução foi encerrado".to_string())
+    }
+
+    pub fn list(&self) -> Vec<QueuedCommand> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Queue `suggestions` as pending. The local assessment, disagreements and simulation
+    /// are computed again here, so the webview cannot pass off its own.
+    fn add(
+        &self,
+        window: &Window,
+        suggestions: Vec<SuggestedCommand>,
+    ) -> Result<Vec<QueuedCommand>, String> {
+        let policy = CommandPolicy::load()?;
+        let now = Utc::now().to_rfc3339();
+        let added: Vec<QueuedCommand> = suggestions
+            .into_iter()
+            .map(|suggestion| {
+                let local_assessment = risk::assess(&policy, &suggestion.command, &suggestion.args);
+                SuggestedCommand {
+                    disagreements: risk::disagreements(
+                        &suggestion.risk_level,
+                        suggestion.requires_sudo,
+                        &local_assessment,
+                    ),
+                    simulation: simulate::plan(&suggestion.command, &suggestion.args, &policy).ok(),
+                    local_assessment,
+                    ..suggestion
+                }
+            })
+            .map(|suggestion| QueuedCommand {
+                id: Uuid::new_v4().to_string(),
+                suggestion,
+                status: QueueStatus::Pending,
+                result: None,
+                error: None,
+                queued_at: now.clone(),
+                updated_at: now.clone(),
+            })
+            .collect();
+        self.entries.lock().unwrap().extend(added.iter().cloned());
+        self.broadcast(window);
+        Ok(added)
+    }
+
+    /// Check that `id` is still pending and holds exactly this command
+    pub fn check_pending(
+        &self,
+        id: Option<&str>,
+        command: &str,
+        args: &[String],
+    ) -> Result<(), String> {
+        let Some(id) = id else {
+            return Ok(());
+        };
+        let entries = self.entries.lock().unwrap();
+        let entry = entries
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or_else(|| format!("Comando '{}' não está na fila", id))?;
+        if entry.status != QueueStatus::Pending {
+            return Err(format!("O comando '{}' já foi aprovado", id));
+        }
+        if entry.suggestion.command != command || entry.suggestion.args != args {
+            return Err(format!(
+                "⚠️ SEGURANÇA: O comando não corresponde ao item '{}' da fila",
+                id
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn set_status(&self, window: &Window, id: Option<&str>, status: QueueStatus) {
+        self.update(window, id, |entry| entry.status = status);
+    }
+
+    /// Store the result or error of an entry and mark it with `status`
+    pub fn record(
+        &self,
+        window: &Window,
+        id: Option<&str>,
+        status: QueueStatus,
+        result: Option<CommandResult>,
+        error: Option<String>,
+    ) {
+        self.update(window, id, |entry| {
+            entry.status = status;
+            entry.result = result;
+            entry.error = error;
+        });
+    }
+
+    /// Completed or failed, depending on whether the command could be run
+    pub fn settle(
+        &self,
+        window: &Window,
+        id: Option<&str>,
+        outcome: &Result<CommandResult, String>,
+    ) {
+        match outcome {
+            Ok(result) => self.record(
+                window,
+                id,
+                QueueStatus::Completed,
+                Some(result.clone()),
+                None,
+            ),
+            Err(e) => self.record(window, id, QueueStatus::Failed, None, Some(e.clone())),
+        }
+    }
+
+    fn update(&self, window: &Window, id: Option<&str>, change: impl FnOnce(&mut QueuedCommand)) {
+        let Some(id) = id else {
+            return;
+        };
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let Some(entry) = entries.iter_mut().find(|e| e.id == id) else {
+                return;
+            };
+            change(entry);
+            entry.updated_at = Utc::now().to_rfc3339();
+        }
+        self.broadcast(window);
+    }
+
+    fn remove(&self, window: &Window, id: &str) -> Result<(), String> {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            let index = entries
+                .iter()
+                .position(|e| e.id == id)
+                .ok_or_else(|| format!("Comando '{}' não está na fila", id))?;
+            if matches!(
+                entries[index].status,
+                QueueStatus::Approved | QueueStatus::Executing
+            ) {
+                return Err("Um comando aprovado não pode ser removido da fila".to_string());
+            }
+            entries.remove(index);
+        }
+        self.broadcast(window);
+        Ok(())
+    }
+
+    /// Every window gets the queue, so a reloaded webview catches up with the next change
+    fn broadcast(&self, window: &Window) {
+        let _ = window.emit_all(QUEUE_EVENT, self.list());
+    }
+}
+
+/// Add commands suggested by the model to the queue as pending
+#[tauri::command]
+pub fn enqueue_commands(
+    window: Window,
+    commands: Vec<SuggestedCommand>,
+    queue: State<'_, CommandQueue>,
+) -> Result<Vec<QueuedCommand>, String> {
+    queue.add(&window, commands)
+}
+
+/// The whole queue, oldest entry first; a reloaded webview starts from this
+#[tauri::command]
+pub fn get_command_queue(queue: State<'_, CommandQueue>) -> Vec<QueuedCommand> {
+    queue.list()
+}
+
+/// Drop a pending or finished entry, e.g. when the user rejects it
+#[tauri::command]
+pub fn remove_queued_command(
+    window: Window,
+    id: String,
+    queue: State<'_, CommandQueue>,
+) -> Result<(), String> {
+    queue.remove(&window, &id)
+}
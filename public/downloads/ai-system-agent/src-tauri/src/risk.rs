@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+use crate::policy::{CommandPolicy, RiskLevel};
+
+/// Risk of a command as computed by the backend, independent of what the model claims
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RiskAssessment {
+    pub allowed: bool,
+    pub risk_level: RiskLevel,
+    pub requires_sudo: bool,
+    pub reason: String,
+}
+
+/// Classify `command args` from the policy rules, arguments and target paths
+pub fn assess(policy: &CommandPolicy, command: &str, args: &[String]) -> RiskAssessment {
+    let verdict = policy.evaluate(command, args);
+    RiskAssessment {
+        allowed: verdict.allowed,
+        risk_level: verdict.risk,
+        requires_sudo: verdict.requires_sudo,
+        reason: verdict.reason,
+    }
+}
+
+/// Where the model's claims differ from the local assessment, as messages for the user
+pub fn disagreements(model_risk: &str, model_sudo: bool, local: &RiskAssessment) -> Vec<String> {
+    let mut found = Vec::new();
+
+    if !local.allowed {
+        found.push(format!(
+            "O modelo sugeriu um comando que a política bloqueia: {}",
+            local.reason
+        ));
+        return found;
+    }
+
+    match parse_risk(model_risk) {
+        Some(risk) if risk == local.risk_level => {}
+        Some(risk) => found.push(format!(
+            "O modelo classificou o risco como {}, mas a análise local indica {}",
+            risk.as_str(),
+            local.risk_level.as_str()
+        )),
+        None => found.push(format!(
+            "O modelo informou um nível de risco inválido ('{}'); a análise local indica {}",
+            model_risk,
+            local.risk_level.as_str()
+        )),
+    }
+
+    if model_sudo != local.requires_sudo {
+        found.push(if local.requires_sudo {
+            "O modelo não indicou sudo, mas o comando exige privilégios elevados".to_string()
+        } else {
+            "O modelo pediu sudo, mas o comando não exige privilégios elevados".to_string()
+        });
+    }
+
+    found
+}
+
+fn parse_risk(value: &str) -> Option<RiskLevel> {
+    match value.trim().to_lowercase().as_str() {
+        "low" => Some(RiskLevel::Low),
+        "medium" => Some(RiskLevel::Medium),
+        "high" => Some(RiskLevel::High),
+        _ => None,
+    }
+}
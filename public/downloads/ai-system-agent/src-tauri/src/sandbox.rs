@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::config;
+use crate::executor::ExecutionContext;
+use crate::policy::CommandPolicy;
+
+/// When commands the policy tags `read_only` run in the sandbox
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxMode {
+    /// Sandboxed where the kernel allows it, unconfined elsewhere
+    #[default]
+    Auto,
+    /// Refuse read-only commands when the sandbox is unavailable
+    Required,
+    Off,
+}
+
+impl SandboxMode {
+    /// Whether switching from `self` to `mode` confines fewer commands
+    pub fn weakened_by(self, mode: SandboxMode) -> bool {
+        let rank = |mode| match mode {
+            SandboxMode::Off => 0,
+            SandboxMode::Auto => 1,
+            SandboxMode::Required => 2,
+        };
+        rank(mode) < rank(self)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SandboxMode::Auto => "auto",
+            SandboxMode::Required => "required",
+            SandboxMode::Off => "off",
+        }
+    }
+}
+
+/// First argument of the app binary when it is re-run to enter the sandbox and exec a
+/// program, for processes spawned where no pre-exec hook is available (pseudo-terminals)
+pub const EXEC_ARG: &str = "--sandboxed-exec";
+
+static AVAILABLE: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Whether the sandbox can be entered on this system, probed once
+pub fn available() -> Result<(), String> {
+    AVAILABLE.get_or_init(probe).clone()
+}
+
+/// Whether `command args` must run in the sandbox: the policy or the context marks it
+/// read-only, it is not elevated and the sandbox is enabled. Fails when it must be sandboxed but cannot be.
+pub fn should_confine(
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    context: &ExecutionContext,
+) -> Result<bool, String> {
+    let mode = config::get_config().unwrap_or_default().sandbox_mode;
+    if requires_sudo || mode == SandboxMode::Off {
+        return Ok(false);
+    }
+    let verdict =
+        CommandPolicy::load()?.evaluate_in(command, args, context.cwd.as_deref().map(Path::new));
+    if !verdict.read_only && !context.read_only {
+        return Ok(false);
+    }
+
+    match available() {
+        Ok(()) => Ok(true),
+        Err(reason) if mode == SandboxMode::Required => Err(format!(
+            "⚠️ SEGURANÇA: O sandbox de comandos somente leitura não está disponível: {}",
+            reason
+        )),
+        Err(_) => Ok(false),
+    }
+}
+
+/// Make `process` enter the sandbox between fork and exec
+#[cfg(target_os = "linux")]
+pub fn confine(process: &mut Command) -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let sandbox = linux::Sandbox::new()?;
+    // `enter` only makes async-signal-safe system calls
+    unsafe {
+        process.pre_exec(move || sandbox.enter());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn confine(_process: &mut Command) -> Result<(), String> {
+    Err(probe().unwrap_err())
+}
+
+/// Entry point of `EXEC_ARG <program> [args...]`: enter the sandbox, then replace this
+/// process with the program. Returns the exit code only when that fails.
+#[cfg(target_os = "linux")]
+pub fn exec(args: &[String]) -> i32 {
+    use std::os::unix::process::CommandExt;
+
+    let Some((program, args)) = args.split_first() else {
+        eprintln!("[SANDBOX] Uso: {} <programa> [argumentos...]", EXEC_ARG);
+        return 126;
+    };
+    let entered = linux::Sandbox::new().and_then(|sandbox| {
+        sandbox
+            .enter()
+            .map_err(|e| format!("Erro ao entrar no sandbox: {}", e))
+    });
+    if let Err(e) = entered {
+        eprintln!("[SANDBOX] {}", e);
+        return 126;
+    }
+    let error = Command::new(program).args(args).exec();
+    eprintln!("[SANDBOX] Erro ao executar '{}': {}", program, error);
+    127
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn exec(_args: &[String]) -> i32 {
+    eprintln!("[SANDBOX] {}", probe().unwrap_err());
+    126
+}
+
+#[cfg(target_os = "linux")]
+fn probe() -> Result<(), String> {
+    linux::probe()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn probe() -> Result<(), String> {
+    Err("disponível apenas no Linux".to_string())
+}
+
+/// Namespaces, a read-only view of the filesystem and a seccomp filter.
+///
+/// Only the user's own uid and gid are mapped in the user namespace, so files and
+/// processes of other users show up as owned by the overflow id (`nobody`).
+#[cfg(target_os = "linux")]
+mod linux {
+    use libc::{c_int, c_long, c_void, sock_filter};
+    use std::ffi::CStr;
+    use std::io;
+
+    #[cfg(target_arch = "x86_64")]
+    const AUDIT_ARCH: u32 = 0xC000_003E;
+    #[cfg(target_arch = "aarch64")]
+    const AUDIT_ARCH: u32 = 0xC000_00B7;
+    /// Syscalls of the x32 ABI, which the filter below does not list
+    #[cfg(target_arch = "x86_64")]
+    const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+    const MOUNT_ATTR_RDONLY: u64 = 0x1;
+    const MOUNT_ATTR_NOSUID: u64 = 0x2;
+    const MOUNT_ATTR_NODEV: u64 = 0x4;
+
+    /// `struct mount_attr` of `mount_setattr(2)`
+    #[repr(C)]
+    struct MountAttr {
+        attr_set: u64,
+        attr_clr: u64,
+        propagation: u64,
+        userns_fd: u64,
+    }
+
+    /// Refused with EPERM: anything that could undo the sandbox, reach outside it through
+    /// a socket or a handle, or change the kernel
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    const DENIED_SYSCALLS: &[c_long] = &[
+        libc::SYS_mount,
+        libc::SYS_umount2,
+        libc::SYS_mount_setattr,
+        libc::SYS_open_tree,
+        libc::SYS_move_mount,
+        libc::SYS_fsopen,
+        libc::SYS_fsconfig,
+        libc::SYS_fsmount,
+        libc::SYS_fspick,
+        libc::SYS_pivot_root,
+        libc::SYS_chroot,
+        libc::SYS_unshare,
+        libc::SYS_setns,
+        libc::SYS_socket,
+        libc::SYS_ptrace,
+        libc::SYS_process_vm_writev,
+        libc::SYS_open_by_handle_at,
+        libc::SYS_name_to_handle_at,
+        libc::SYS_io_uring_setup,
+        libc::SYS_userfaultfd,
+        libc::SYS_bpf,
+        libc::SYS_perf_event_open,
+        libc::SYS_keyctl,
+        libc::SYS_add_key,
+        libc::SYS_request_key,
+        libc::SYS_init_module,
+        libc::SYS_finit_module,
+        libc::SYS_delete_module,
+        libc::SYS_kexec_load,
+        libc::SYS_kexec_file_load,
+        libc::SYS_reboot,
+        libc::SYS_swapon,
+        libc::SYS_swapoff,
+        libc::SYS_acct,
+        libc::SYS_quotactl,
+        libc::SYS_sethostname,
+        libc::SYS_setdomainname,
+    ];
+
+    /// Everything `enter` needs, prepared before the fork so the child does not allocate
+    pub struct Sandbox {
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        filter: Vec<sock_filter>,
+    }
+
+    fn check(rc: c_long) -> io::Result<()> {
+        if rc == -1 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write `data` to `path` with raw syscalls
+    unsafe fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd as c_long)?;
+        let written = libc::write(fd, data.as_ptr().cast::<c_void>(), data.len());
+        let error = io::Error::last_os_error();
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn statement(code: u32, k: u32) -> sock_filter {
+        sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+        sock_filter {
+            code: code as u16,
+            jt,
+            jf,
+            k,
+        }
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    fn seccomp_filter() -> Result<Vec<sock_filter>, String> {
+        use libc::{BPF_ABS, BPF_JEQ, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+
+        let deny = statement(
+            BPF_RET | BPF_K,
+            libc::SECCOMP_RET_ERRNO | libc::EPERM as u32,
+        );
+        // Offsets of `nr` and `arch` in `struct seccomp_data`
+        let mut filter = vec![
+            statement(BPF_LD | BPF_W | BPF_ABS, 4),
+            jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+            statement(BPF_RET | BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+            statement(BPF_LD | BPF_W | BPF_ABS, 0),
+        ];
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([
+            jump(BPF_JMP | libc::BPF_JGE | BPF_K, X32_SYSCALL_BIT, 0, 1),
+            deny,
+        ]);
+        for &nr in DENIED_SYSCALLS {
+            filter.push(jump(BPF_JMP | BPF_JEQ | BPF_K, nr as u32, 0, 1));
+            filter.push(deny);
+        }
+        filter.push(statement(BPF_RET | BPF_K, libc::SECCOMP_RET_ALLOW));
+        Ok(filter)
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    fn seccomp_filter() -> Result<Vec<sock_filter>, String> {
+        Err("arquitetura sem filtro seccomp".to_string())
+    }
+
+    impl Sandbox {
+        pub fn new() -> Result<Self, String> {
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            Ok(Self {
+                uid_map: format!("{} {} 1\n", uid, uid).into_bytes(),
+                gid_map: format!("{} {} 1\n", gid, gid).into_bytes(),
+                filter: seccomp_filter()?,
+            })
+        }
+
+        /// Enter the sandbox. Runs in the child between fork and exec, so it only makes
+        /// system calls and touches memory prepared by `new`.
+        pub fn enter(&self) -> io::Result<()> {
+            unsafe {
+                // A user namespace lets an unprivileged process own the others
+                check(libc::unshare(
+                    libc::CLONE_NEWUSER
+                        | libc::CLONE_NEWNS
+                        | libc::CLONE_NEWNET
+                        | libc::CLONE_NEWIPC,
+                ) as c_long)?;
+                write_file(c"/proc/self/setgroups", b"deny")?;
+                write_file(c"/proc/self/uid_map", &self.uid_map)?;
+                write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+                // Keep the changes below out of the parent's mounts, then make every
+                // mount read-only. Sockets and devices are not covered by read-only
+                // mounts, hence `nodev` and the seccomp filter.
+                check(libc::mount(
+                    std::ptr::null(),
+                    c"/".as_ptr(),
+                    std::ptr::null(),
+                    libc::MS_REC | libc::MS_PRIVATE,
+                    std::ptr::null(),
+                ) as c_long)?;
+                let attr = MountAttr {
+                    attr_set: MOUNT_ATTR_RDONLY | MOUNT_ATTR_NOSUID | MOUNT_ATTR_NODEV,
+                    attr_clr: 0,
+                    propagation: 0,
+                    userns_fd: 0,
+                };
+                check(libc::syscall(
+                    libc::SYS_mount_setattr,
+                    libc::AT_FDCWD,
+                    c"/".as_ptr(),
+                    libc::AT_RECURSIVE,
+                    &attr as *const MountAttr,
+                    std::mem::size_of::<MountAttr>(),
+                ))?;
+
+                check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) as c_long)?;
+                let program = libc::sock_fprog {
+                    len: self.filter.len() as u16,
+                    filter: self.filter.as_ptr() as *mut sock_filter,
+                };
+                check(libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    libc::SECCOMP_MODE_FILTER,
+                    &program as *const libc::sock_fprog,
+                ) as c_long)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Enter the sandbox in a forked child, which reports the errno of the step that failed
+    pub fn probe() -> Result<(), String> {
+        let sandbox = Sandbox::new()?;
+        let pid = unsafe { libc::fork() };
+        if pid == -1 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        if pid == 0 {
+            let code = match sandbox.enter() {
+                Ok(()) => 0,
+                Err(e) => e.raw_os_error().unwrap_or(1).clamp(1, 125),
+            };
+            unsafe { libc::_exit(code) };
+        }
+
+        let mut status: c_int = 0;
+        if unsafe { libc::waitpid(pid, &mut status, 0) } == -1 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        match libc::WIFEXITED(status).then(|| libc::WEXITSTATUS(status)) {
+            Some(0) => Ok(()),
+            Some(errno) => Err(format!(
+                "namespaces ou seccomp indisponíveis ({})",
+                io::Error::from_raw_os_error(errno)
+            )),
+            None => Err("o teste do sandbox terminou de forma anormal".to_string()),
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+use uuid::Uuid;
+
+use crate::executor::ExecutionContext;
+use crate::policy::{normalize_path, CommandPolicy};
+
+/// Lines kept in a session's history
+const MAX_SESSION_HISTORY: usize = 1000;
+
+/// State of one terminal tab, applied to every command run in it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShellSession {
+    pub id: String,
+    pub cwd: String,
+    /// Variables set with `export`
+    pub env: BTreeMap<String, String>,
+    /// `pushd` stack, most recent last
+    pub dir_stack: Vec<String>,
+    /// Directory before the last `cd`, for `cd -`
+    pub previous_dir: Option<String>,
+    pub history: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShellInput {
+    /// Whether the input was a builtin handled by the session; otherwise it should be
+    /// executed with this session's id
+    pub builtin: bool,
+    pub output: String,
+    pub session: ShellSession,
+}
+
+/// Open terminal sessions, keyed by session id
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: Mutex<HashMap<String, ShellSession>>,
+}
+
+impl SessionStore {
+    pub fn create(&self, cwd: Option<String>) -> Result<ShellSession, String> {
+        let cwd = match cwd.filter(|c| !c.is_empty()) {
+            Some(cwd) => normalize_path(&cwd),
+            None => normalize_path("~"),
+        };
+        if !cwd.is_dir() {
+            return Err(format!(
+                "Diretório de trabalho inválido: '{}'",
+                cwd.display()
+            ));
+        }
+
+        let session = ShellSession {
+            id: Uuid::new_v4().to_string(),
+            cwd: cwd.to_string_lossy().to_string(),
+            env: BTreeMap::new(),
+            dir_stack: vec![],
+            previous_dir: None,
+            history: vec![],
+        };
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.id.clone(), session.clone());
+        Ok(session)
+    }
+
+    pub fn get(&self, id: &str) -> Result<ShellSession, String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(id)
+            .cloned()
+            .ok_or_else(|| format!("Nenhuma sessão com id '{}'", id))
+    }
+
+    pub fn close(&self, id: &str) -> bool {
+        self.sessions.lock().unwrap().remove(id).is_some()
+    }
+
+    /// Execution context for a command, taken from session `session_id` when given.
+    ///
+    /// An explicit `cwd` replaces the session's, and explicit `env` entries override
+    /// exported ones. Exported variables are left out of elevated commands, which
+    /// never receive an environment.
+    pub fn context(
+        &self,
+        session_id: Option<&str>,
+        cwd: Option<String>,
+        env: Option<BTreeMap<String, String>>,
+        requires_sudo: bool,
+    ) -> Result<ExecutionContext, String> {
+        let Some(session_id) = session_id else {
+            return Ok(ExecutionContext::new(cwd, env));
+        };
+        let session = self.get(session_id)?;
+
+        let mut merged = if requires_sudo {
+            BTreeMap::new()
+        } else {
+            session.env
+        };
+        merged.extend(env.unwrap_or_default());
+        Ok(ExecutionContext::new(
+            cwd.or(Some(session.cwd)),
+            Some(merged),
+        ))
+    }
+
+    /// Record `command args` in the session history and run it if it is a builtin
+    pub fn input(&self, id: &str, command: &str, args: &[String]) -> Result<ShellInput, String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(id)
+            .ok_or_else(|| format!("Nenhuma sessão com id '{}'", id))?;
+
+        let line = std::iter::once(command)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !line.trim().is_empty() {
+            session.history.push(line);
+            let excess = session.history.len().saturating_sub(MAX_SESSION_HISTORY);
+            session.history.drain(..excess);
+        }
+
+        let output = match command {
+            "cd" => builtin_cd(session, args)?,
+            "pushd" => builtin_pushd(session, args)?,
+            "popd" => builtin_popd(session)?,
+            "dirs" => dirs_line(session),
+            "pwd" => session.cwd.clone(),
+            "export" => builtin_export(session, args)?,
+            "unset" => {
+                for name in args {
+                    session.env.remove(name);
+                }
+                String::new()
+            }
+            "history" => builtin_history(session, args)?,
+            _ => {
+                return Ok(ShellInput {
+                    builtin: false,
+                    output: String::new(),
+                    session: session.clone(),
+                })
+            }
+        };
+
+        Ok(ShellInput {
+            builtin: true,
+            output,
+            session: session.clone(),
+        })
+    }
+}
+
+/// Resolve `target` against the session directory, refusing missing and protected directories
+fn resolve_dir(session: &ShellSession, target: &str) -> Result<String, String> {
+    let path = if target == "~" || target.starts_with("~/") || Path::new(target).is_absolute() {
+        normalize_path(target)
+    } else {
+        normalize_path(&PathBuf::from(&session.cwd).join(target).to_string_lossy())
+    };
+
+    if !path.is_dir() {
+        return Err(format!("Diretório não encontrado: '{}'", target));
+    }
+    if CommandPolicy::load()?.is_protected(&path) {
+        return Err(format!(
+            "⚠️ SEGURANÇA: O diretório de trabalho '{}' é protegido",
+            path.display()
+        ));
+    }
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn change_dir(session: &mut ShellSession, dir: String) {
+    let previous = std::mem::replace(&mut session.cwd, dir);
+    session.previous_dir = Some(previous);
+}
+
+fn builtin_cd(session: &mut ShellSession, args: &[String]) -> Result<String, String> {
+    match args {
+        [] => {
+            let dir = resolve_dir(session, "~")?;
+            change_dir(session, dir);
+            Ok(String::new())
+        }
+        [target] if target == "-" => {
+            let previous = session
+                .previous_dir
+                .clone()
+                .ok_or("cd: nenhum diretório anterior")?;
+            let dir = resolve_dir(session, &previous)?;
+            change_dir(session, dir);
+            // Like bash, `cd -` prints where it went
+            Ok(session.cwd.clone())
+        }
+        [target] => {
+            let dir = resolve_dir(session, target)?;
+            change_dir(session, dir);
+            Ok(String::new())
+        }
+        _ => Err("cd: argumentos demais".to_string()),
+    }
+}
+
+fn builtin_pushd(session: &mut ShellSession, args: &[String]) -> Result<String, String> {
+    let target = match args {
+        // Without arguments, swap the two top directories
+        [] => session
+            .dir_stack
+            .last()
+            .cloned()
+            .ok_or("pushd: nenhum outro diretório")?,
+        [target] => target.clone(),
+        _ => return Err("pushd: argumentos demais".to_string()),
+    };
+    // A stack entry may have become protected or been removed since it was pushed
+    let dir = resolve_dir(session, &target)?;
+    if args.is_empty() {
+        session.dir_stack.pop();
+    }
+
+    let current = session.cwd.clone();
+    session.dir_stack.push(current);
+    change_dir(session, dir);
+    Ok(dirs_line(session))
+}
+
+fn builtin_popd(session: &mut ShellSession) -> Result<String, String> {
+    let target = session
+        .dir_stack
+        .last()
+        .cloned()
+        .ok_or("popd: pilha de diretórios vazia")?;
+    let dir = resolve_dir(session, &target)?;
+    session.dir_stack.pop();
+    change_dir(session, dir);
+    Ok(dirs_line(session))
+}
+
+/// Current directory followed by the stack, most recent first, as bash prints it
+fn dirs_line(session: &ShellSession) -> String {
+    std::iter::once(&session.cwd)
+        .chain(session.dir_stack.iter().rev())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn builtin_export(session: &mut ShellSession, args: &[String]) -> Result<String, String> {
+    if args.is_empty() {
+        return Ok(session
+            .env
+            .iter()
+            .map(|(name, value)| format!("export {}={:?}", name, value))
+            .collect::<Vec<_>>()
+            .join("\n"));
+    }
+
+    let mut assignments = BTreeMap::new();
+    for arg in args {
+        let (name, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("export: use NOME=valor ('{}')", arg))?;
+        assignments.insert(name.to_string(), value.to_string());
+    }
+    // Same rules as variables passed to a single execution
+    ExecutionContext::new(None, Some(assignments.clone())).validate(false)?;
+
+    session.env.extend(assignments);
+    Ok(String::new())
+}
+
+fn builtin_history(session: &mut ShellSession, args: &[String]) -> Result<String, String> {
+    let count = match args {
+        [] => session.history.len(),
+        [flag] if flag == "-c" => {
+            session.history.clear();
+            return Ok(String::new());
+        }
+        [count] => count
+            .parse::<usize>()
+            .map_err(|_| format!("history: número inválido: '{}'", count))?,
+        _ => return Err("history: argumentos demais".to_string()),
+    };
+
+    let start = session.history.len().saturating_sub(count);
+    Ok(session.history[start..]
+        .iter()
+        .enumerate()
+        .map(|(i, line)| format!("{:5}  {}", start + i + 1, line))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[tauri::command]
+pub fn create_shell_session(
+    cwd: Option<String>,
+    sessions: State<'_, SessionStore>,
+) -> Result<ShellSession, String> {
+    sessions.create(cwd)
+}
+
+#[tauri::command]
+pub fn get_shell_session(
+    id: String,
+    sessions: State<'_, SessionStore>,
+) -> Result<ShellSession, String> {
+    sessions.get(&id)
+}
+
+#[tauri::command]
+pub fn close_shell_session(id: String, sessions: State<'_, SessionStore>) -> bool {
+    sessions.close(&id)
+}
+
+/// Handle one line typed in a terminal tab: builtins (`cd`, `pushd`, `popd`, `dirs`, `pwd`,
+/// `export`, `unset`, `history`) run here, anything else is only recorded in the history
+#[tauri::command]
+pub fn shell_session_input(
+    id: String,
+    command: String,
+    args: Vec<String>,
+    sessions: State<'_, SessionStore>,
+) -> Result<ShellInput, String> {
+    sessions.input(&id, &command, &args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn args_env(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// Directory tree of one test, removed when dropped
+    struct Tree(PathBuf);
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A session rooted in a fresh directory holding `a` and `b`
+    fn session_in_tree() -> (SessionStore, String, Tree) {
+        let root = std::env::temp_dir().join(format!("session-test-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("a")).unwrap();
+        std::fs::create_dir_all(root.join("b")).unwrap();
+        let root = normalize_path(&root.to_string_lossy());
+
+        let store = SessionStore::default();
+        let id = store
+            .create(Some(root.to_string_lossy().to_string()))
+            .unwrap()
+            .id;
+        (store, id, Tree(root))
+    }
+
+    fn dir(root: &Path, name: &str) -> String {
+        root.join(name).to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn cd_resolves_relative_paths_and_goes_back_with_dash() {
+        let (store, id, tree) = session_in_tree();
+        let root = &tree.0;
+
+        let input = store.input(&id, "cd", &args(&["a"])).unwrap();
+        assert!(input.builtin);
+        assert_eq!(input.session.cwd, dir(root, "a"));
+
+        store.input(&id, "cd", &args(&["../b"])).unwrap();
+        assert_eq!(store.get(&id).unwrap().cwd, dir(root, "b"));
+
+        let input = store.input(&id, "cd", &args(&["-"])).unwrap();
+        assert_eq!(input.output, dir(root, "a"));
+        assert_eq!(input.session.previous_dir, Some(dir(root, "b")));
+    }
+
+    #[test]
+    fn cd_refuses_missing_directories_and_keeps_the_cwd() {
+        let (store, id, tree) = session_in_tree();
+        let root = &tree.0;
+
+        assert!(store.input(&id, "cd", &args(&["missing"])).is_err());
+        assert!(store.input(&id, "cd", &args(&["a", "b"])).is_err());
+        assert!(store.input(&id, "cd", &args(&["-"])).is_err());
+        assert_eq!(store.get(&id).unwrap().cwd, root.to_string_lossy());
+    }
+
+    #[test]
+    fn pushd_and_popd_walk_the_directory_stack() {
+        let (store, id, tree) = session_in_tree();
+        let root = &tree.0;
+        let top = root.to_string_lossy().to_string();
+
+        let input = store.input(&id, "pushd", &args(&["a"])).unwrap();
+        assert_eq!(input.output, format!("{} {}", dir(root, "a"), top));
+
+        store.input(&id, "pushd", &args(&["../b"])).unwrap();
+        let input = store.input(&id, "dirs", &[]).unwrap();
+        assert_eq!(
+            input.output,
+            format!("{} {} {}", dir(root, "b"), dir(root, "a"), top)
+        );
+
+        // Without arguments the two top directories are swapped
+        let input = store.input(&id, "pushd", &[]).unwrap();
+        assert_eq!(
+            input.output,
+            format!("{} {} {}", dir(root, "a"), dir(root, "b"), top)
+        );
+
+        store.input(&id, "popd", &[]).unwrap();
+        let input = store.input(&id, "popd", &[]).unwrap();
+        assert_eq!(input.output, top);
+        assert!(store.input(&id, "popd", &[]).is_err());
+    }
+
+    #[test]
+    fn export_sets_variables_used_by_later_commands() {
+        let (store, id, tree) = session_in_tree();
+        let root = &tree.0;
+
+        store
+            .input(&id, "export", &args(&["GREETING=olá", "EMPTY="]))
+            .unwrap();
+        let input = store.input(&id, "export", &[]).unwrap();
+        assert_eq!(input.output, "export EMPTY=\"\"\nexport GREETING=\"olá\"");
+
+        let context = store
+            .context(Some(&id), None, Some(args_env(&[("EMPTY", "set")])), false)
+            .unwrap();
+        assert_eq!(context.env.get("GREETING").map(String::as_str), Some("olá"));
+        assert_eq!(context.env.get("EMPTY").map(String::as_str), Some("set"));
+        assert_eq!(context.cwd.as_deref(), Some(&*root.to_string_lossy()));
+
+        // Elevated commands never receive the session's variables
+        let context = store.context(Some(&id), None, None, true).unwrap();
+        assert!(context.env.is_empty());
+
+        store.input(&id, "unset", &args(&["GREETING"])).unwrap();
+        assert!(!store.get(&id).unwrap().env.contains_key("GREETING"));
+    }
+
+    #[test]
+    fn export_refuses_malformed_and_forbidden_variables() {
+        let (store, id, _tree) = session_in_tree();
+
+        assert!(store.input(&id, "export", &args(&["NOVALUE"])).is_err());
+        for forbidden in ["PATH=/tmp", "LD_PRELOAD=/tmp/x.so", "=x"] {
+            let err = store.input(&id, "export", &args(&["SAFE=1", forbidden]));
+            assert!(err.is_err(), "{} should be refused", forbidden);
+        }
+        // A refused assignment sets none of the others
+        assert!(store.get(&id).unwrap().env.is_empty());
+    }
+}
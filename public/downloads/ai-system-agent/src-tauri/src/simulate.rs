@@ -0,0 +1,496 @@
+use serde::{Deserialize, Serialize};
+use tauri::{State, Window};
+use uuid::Uuid;
+
+use crate::audit::{AuditApproval, AuditLog};
+use crate::commands;
+use crate::executor::{self, ExecutionContext};
+use crate::policy::{flag_matches, is_flag, is_within, normalize_path, program_name, CommandPolicy};
+use crate::process_registry::ProcessRegistry;
+use crate::queue::CommandQueue;
+
+/// Package manager operations `-s` / `--assumeno` can dry-run
+const PACKAGE_OPERATIONS: &[&str] = &[
+    "install",
+    "reinstall",
+    "remove",
+    "purge",
+    "erase",
+    "autoremove",
+    "upgrade",
+    "update",
+    "full-upgrade",
+    "dist-upgrade",
+    "distro-sync",
+    "downgrade",
+    "build-dep",
+];
+
+/// Compose options that pick the project and must be kept for `config`
+const COMPOSE_OPTIONS: &[&str] = &[
+    "-f",
+    "--file",
+    "-p",
+    "--project-name",
+    "--project-directory",
+    "--env-file",
+    "--profile",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SimulationKind {
+    /// `apt-get -s` / `apt -s`: prints the `Inst`/`Remv` actions it would take
+    Apt,
+    /// `pacman -p`: prints the targets instead of acting on them
+    Pacman,
+    /// `apache2 -t` / `httpd -t`: validates the configuration
+    ConfigTest,
+    /// `docker compose config`: renders the project that `up` would start
+    Compose,
+}
+
+/// The no-op form of a state-changing command
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Simulation {
+    pub kind: SimulationKind,
+    pub command: String,
+    pub args: Vec<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SimulationReport {
+    pub simulation: Simulation,
+    /// Short summary of what the real command would do
+    pub predicted_effect: String,
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+    pub duration_ms: u64,
+}
+
+/// Map a command to its native dry-run form, or explain why none is safe
+pub fn plan(command: &str, args: &[String], policy: &CommandPolicy) -> Result<Simulation, String> {
+    let program = program_name(command);
+    let refuse = |reason: &str| {
+        format!(
+            "Nenhuma simulação segura disponível para '{}': {}",
+            program, reason
+        )
+    };
+    let operation = args.iter().find(|a| !is_flag(a)).map(String::as_str);
+
+    match program {
+        "apt" | "apt-get" => {
+            if !operation.is_some_and(|op| PACKAGE_OPERATIONS.contains(&op) && op != "update") {
+                // `update` rewrites the package lists even with `-s`
+                return Err(refuse(
+                    "apenas instalação, remoção e atualização de pacotes são simuláveis",
+                ));
+            }
+            let mut sim_args = vec!["-s".to_string()];
+            sim_args.extend(args.iter().cloned());
+            Ok(Simulation {
+                kind: SimulationKind::Apt,
+                command: program.to_string(),
+                args: sim_args,
+                description: format!("{} -s lista as ações sem alterar o sistema", program),
+            })
+        }
+        // Simulations run unelevated, offline and on a read-only filesystem
+        "dnf" | "yum" => Err(refuse(
+            "mesmo com --assumeno ele só resolve a transação como root, com rede e \
+             gravando no próprio cache",
+        )),
+        "nginx" => Err(refuse(
+            "'nginx -t' abre os logs para escrita e lê chaves TLS que só o root acessa",
+        )),
+        "pacman" => plan_pacman(args).map_err(|reason| refuse(&reason)),
+        "apache2" | "httpd" => {
+            let sim_args = plan_config_test(args, policy).map_err(|r| refuse(&r))?;
+            Ok(Simulation {
+                kind: SimulationKind::ConfigTest,
+                command: program.to_string(),
+                args: sim_args,
+                description: format!("{} -t valida a configuração sem recarregá-la", program),
+            })
+        }
+        "docker" | "podman" => {
+            let compose_at = args
+                .iter()
+                .position(|a| !is_flag(a))
+                .filter(|&i| args[i] == "compose")
+                .ok_or_else(|| refuse("apenas projetos compose são simuláveis"))?;
+            let mut sim_args = args[..=compose_at].to_vec();
+            sim_args.extend(plan_compose(&args[compose_at + 1..]).map_err(|r| refuse(&r))?);
+            Ok(Simulation {
+                kind: SimulationKind::Compose,
+                command: program.to_string(),
+                args: sim_args,
+                description: "compose config mostra o projeto sem iniciar nada".to_string(),
+            })
+        }
+        "docker-compose" => Ok(Simulation {
+            kind: SimulationKind::Compose,
+            command: program.to_string(),
+            args: plan_compose(args).map_err(|r| refuse(&r))?,
+            description: "docker-compose config mostra o projeto sem iniciar nada".to_string(),
+        }),
+        _ => Err(refuse(
+            "a ferramenta não tem um modo de simulação conhecido",
+        )),
+    }
+}
+
+fn plan_pacman(args: &[String]) -> Result<Simulation, String> {
+    let operation = args
+        .iter()
+        .find(|a| is_flag(a))
+        .ok_or("nenhuma operação informada")?;
+    let changes_packages = ["-S", "--sync", "-U", "--upgrade", "-R", "--remove"]
+        .iter()
+        .any(|flag| flag_matches(operation, flag));
+    if !changes_packages {
+        return Err("apenas -S, -U e -R são simuláveis".to_string());
+    }
+
+    // `-y` refreshes the sync databases, which is a change of its own
+    let mut sim_args = Vec::new();
+    for arg in args {
+        if arg == "--refresh" {
+            continue;
+        }
+        if is_flag(arg) && !arg.starts_with("--") {
+            let cluster: String = arg[1..].chars().filter(|c| *c != 'y').collect();
+            if !cluster.is_empty() {
+                sim_args.push(format!("-{}", cluster));
+            }
+            continue;
+        }
+        sim_args.push(arg.clone());
+    }
+    sim_args.push("--print".to_string());
+
+    Ok(Simulation {
+        kind: SimulationKind::Pacman,
+        command: "pacman".to_string(),
+        args: sim_args,
+        description: "pacman --print lista os pacotes afetados, sem atualizar as bases (-y)"
+            .to_string(),
+    })
+}
+
+/// `-t`, keeping only the option naming the configuration file, and only for a file in
+/// one of the policy's `config_paths`.
+///
+/// Options that add directives or change where the configuration is read from
+/// (`-C`/`-c`/`-d`) are refused: the test would load any module they name.
+fn plan_config_test(args: &[String], policy: &CommandPolicy) -> Result<Vec<String>, String> {
+    let file_option = "-f";
+    let dropped_with_value = ["-k", "-e"];
+    let dropped_flags = ["-t", "-S", "-M", "-l", "-L", "-v", "-V", "-X"];
+
+    let mut sim_args = Vec::new();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if arg == file_option {
+            let file = rest
+                .next()
+                .ok_or_else(|| format!("'{}' sem arquivo de configuração", file_option))?;
+            let path = normalize_path(file);
+            if !path.is_absolute() || !policy.config_paths.iter().any(|p| is_within(&path, p)) {
+                return Err(format!(
+                    "'{}' não está em um diretório de configuração permitido",
+                    file
+                ));
+            }
+            sim_args.push(arg.clone());
+            sim_args.push(file.clone());
+        } else if dropped_with_value.contains(&arg.as_str()) {
+            // The signal or action the real command sends, e.g. `-s reload`
+            rest.next();
+        } else if !dropped_flags.contains(&arg.as_str()) {
+            return Err(format!("a opção '{}' não é aceita na simulação", arg));
+        }
+    }
+    sim_args.push("-t".to_string());
+    Ok(sim_args)
+}
+
+/// Arguments after `compose`: keep the project options and replace the subcommand by `config`
+fn plan_compose(args: &[String]) -> Result<Vec<String>, String> {
+    let subcommand = positional_after_options(args, COMPOSE_OPTIONS)
+        .ok_or("nenhum subcomando compose informado")?;
+    if !matches!(
+        subcommand,
+        "up" | "create" | "run" | "start" | "restart" | "pull"
+    ) {
+        return Err(format!("'compose {}' não tem simulação", subcommand));
+    }
+
+    let mut sim_args = option_pairs(args, COMPOSE_OPTIONS);
+    sim_args.push("config".to_string());
+    Ok(sim_args)
+}
+
+/// `name value` / `name=value` occurrences of the given options, in order
+fn option_pairs(args: &[String], names: &[&str]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if names.contains(&arg.as_str()) {
+            kept.push(arg.clone());
+            if let Some(value) = args.get(i + 1) {
+                kept.push(value.clone());
+            }
+            i += 2;
+            continue;
+        }
+        if names
+            .iter()
+            .any(|name| arg.strip_prefix(name).is_some_and(|r| r.starts_with('=')))
+        {
+            kept.push(arg.clone());
+        }
+        i += 1;
+    }
+    kept
+}
+
+/// First positional argument, skipping the values of options that take one
+fn positional_after_options<'a>(args: &'a [String], with_value: &[&str]) -> Option<&'a str> {
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if with_value.contains(&arg.as_str()) {
+            i += 2;
+            continue;
+        }
+        if !is_flag(arg) {
+            return Some(arg);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Condense the dry-run output into one line for the approval UI
+fn predict_effect(kind: SimulationKind, stdout: &str, stderr: &str, exit_code: i32) -> String {
+    match kind {
+        SimulationKind::Apt => {
+            let count = |prefix: &str| stdout.lines().filter(|l| l.starts_with(prefix)).count();
+            if exit_code != 0 {
+                return "A simulação falhou; o comando real provavelmente também falharia"
+                    .to_string();
+            }
+            format!(
+                "{} pacote(s) instalado(s)/atualizado(s), {} removido(s), {} configurado(s)",
+                count("Inst "),
+                count("Remv ") + count("Purg "),
+                count("Conf ")
+            )
+        }
+        SimulationKind::Pacman => {
+            if exit_code != 0 {
+                return "A simulação falhou; o comando real provavelmente também falharia"
+                    .to_string();
+            }
+            let targets = stdout.lines().filter(|l| !l.trim().is_empty()).count();
+            format!("{} pacote(s) afetado(s)", targets)
+        }
+        SimulationKind::ConfigTest => {
+            if exit_code == 0 {
+                "Configuração válida; o recarregamento deve funcionar".to_string()
+            } else {
+                format!(
+                    "Configuração inválida: {}",
+                    stderr.lines().last().unwrap_or("veja a saída")
+                )
+            }
+        }
+        SimulationKind::Compose => {
+            if exit_code == 0 {
+                "Projeto compose válido; veja a saída para os serviços".to_string()
+            } else {
+                format!(
+                    "Projeto compose inválido: {}",
+                    stderr.lines().last().unwrap_or("veja a saída")
+                )
+            }
+        }
+    }
+}
+
+/// Run the dry-run form of a command, never elevated and in the read-only sandbox.
+/// Refuses when no safe simulation exists.
+///
+/// Needs no approval, since nothing it runs can change the system; it is audited all the same.
+#[tauri::command]
+pub async fn simulate_command(
+    window: Window,
+    command: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+    registry: State<'_, ProcessRegistry>,
+    audit: State<'_, AuditLog>,
+    queue: State<'_, CommandQueue>,
+) -> Result<SimulationReport, String> {
+    // Only the working directory: compose and package files are looked up relative to it
+    let context = ExecutionContext {
+        read_only: true,
+        ..ExecutionContext::new(cwd, None)
+    };
+    context.validate(false)?;
+    commands::check_policy(&command, &args, context.cwd.as_deref())?;
+    let simulation = plan(&command, &args, &CommandPolicy::load()?)?;
+    let verdict = commands::check_policy(
+        &simulation.command,
+        &simulation.args,
+        context.cwd.as_deref(),
+    )?;
+    let approval = AuditApproval {
+        // Nothing was approved: simulations run without a token
+        token_digest: String::new(),
+        risk: verdict.risk,
+        reason: format!("Simulação ({})", simulation.description),
+        native_confirmation: false,
+    };
+
+    let limits = commands::resolve_limits(None);
+    let encoding = commands::resolve_encoding(None)?;
+    let _slot = queue.slot().await?;
+    let outcome = executor::run(
+        Uuid::new_v4().to_string(),
+        &simulation.command,
+        &simulation.args,
+        false,
+        &limits,
+        &context,
+        encoding,
+        None,
+        None,
+        &registry,
+    )
+    .await;
+    commands::audit_execution(
+        &window,
+        approval,
+        &simulation.command,
+        &simulation.args,
+        false,
+        &outcome,
+        &audit,
+    );
+    let result = outcome?;
+
+    Ok(SimulationReport {
+        predicted_effect: predict_effect(
+            simulation.kind,
+            &result.stdout,
+            &result.stderr,
+            result.exit_code,
+        ),
+        simulation,
+        stdout: result.stdout,
+        stderr: result.stderr,
+        exit_code: result.exit_code,
+        duration_ms: result.duration_ms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn simulate(command: &str, list: &[&str]) -> Result<Simulation, String> {
+        plan(command, &args(list), &CommandPolicy::default())
+    }
+
+    fn simulated_args(command: &str, list: &[&str]) -> Vec<String> {
+        simulate(command, list).unwrap().args
+    }
+
+    #[test]
+    fn apt_installs_are_simulated_with_dash_s() {
+        assert_eq!(
+            simulated_args("apt-get", &["install", "-y", "nginx"]),
+            args(&["-s", "install", "-y", "nginx"])
+        );
+        assert!(simulate("apt", &["update"]).is_err());
+        assert!(simulate("apt", &["list", "--installed"]).is_err());
+    }
+
+    #[test]
+    fn tools_that_cannot_run_unprivileged_are_refused() {
+        let dnf = simulate("dnf", &["-y", "install", "httpd"]).unwrap_err();
+        assert!(dnf.contains("root"));
+        assert!(simulate("yum", &["remove", "httpd"]).is_err());
+        let nginx = simulate("nginx", &["-s", "reload"]).unwrap_err();
+        assert!(nginx.contains("nginx -t"));
+    }
+
+    #[test]
+    fn pacman_refresh_is_dropped() {
+        assert_eq!(
+            simulated_args("pacman", &["-Syu", "vim"]),
+            args(&["-Su", "vim", "--print"])
+        );
+        assert!(simulate("pacman", &["-Q"]).is_err());
+    }
+
+    #[test]
+    fn config_tests_drop_the_action() {
+        assert_eq!(
+            simulated_args("apache2", &["-k", "graceful"]),
+            args(&["-t"])
+        );
+        assert_eq!(
+            simulated_args(
+                "httpd",
+                &["-f", "/etc/httpd/conf/httpd.conf", "-k", "restart"]
+            ),
+            args(&["-f", "/etc/httpd/conf/httpd.conf", "-t"])
+        );
+    }
+
+    #[test]
+    fn config_tests_refuse_files_outside_config_paths() {
+        assert!(simulate("apache2", &["-f", "/tmp/evil.conf"]).is_err());
+        assert!(simulate("apache2", &["-f", "/etc/apache2/../../tmp/evil.conf"]).is_err());
+        assert!(simulate("apache2", &["-f", "apache2.conf"]).is_err());
+        assert!(simulate("httpd", &["-f", "/home/user/httpd.conf"]).is_err());
+        assert!(simulate("apache2", &["-f", "/etc/apache2/apache2.conf"]).is_ok());
+    }
+
+    #[test]
+    fn config_tests_refuse_options_that_load_code() {
+        assert!(simulate("apache2", &["-C", "LoadModule x /tmp/x.so"]).is_err());
+        assert!(simulate("apache2", &["-c", "LoadModule x /tmp/x.so"]).is_err());
+        assert!(simulate("httpd", &["-d", "/tmp/evil"]).is_err());
+    }
+
+    #[test]
+    fn compose_up_renders_the_config() {
+        assert_eq!(
+            simulated_args("docker", &["compose", "-f", "prod.yml", "up", "-d"]),
+            args(&["compose", "-f", "prod.yml", "config"])
+        );
+        assert_eq!(
+            simulated_args("docker-compose", &["--project-name=web", "up"]),
+            args(&["--project-name=web", "config"])
+        );
+        assert!(simulate("docker", &["compose", "down"]).is_err());
+        assert!(simulate("docker", &["run", "alpine"]).is_err());
+    }
+
+    #[test]
+    fn tools_without_dry_run_are_refused() {
+        assert!(simulate("systemctl", &["restart", "nginx"]).is_err());
+    }
+}
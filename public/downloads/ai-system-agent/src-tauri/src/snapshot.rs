@@ -0,0 +1,928 @@
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use chrono::Utc;
+use tauri::{Manager, State, Window};
+use uuid::Uuid;
+
+use crate::approval::{self, ApprovalStore, ApprovalToken};
+use crate::audit::{self, AuditApproval, AuditEntry, AuditLog};
+use crate::config;
+use crate::decode::OutputEncoding;
+use crate::executor::{self, ExecutionContext, ExecutionLimits};
+use crate::policy::{normalize_path, CommandPolicy, RiskLevel};
+use crate::process_registry::ProcessRegistry;
+
+const SNAPSHOT_DIR: &str = "snapshots";
+const MANIFEST_FILE: &str = "manifest.json";
+/// Snapshots kept; the oldest are removed beyond this
+const MAX_SNAPSHOTS: usize = 100;
+/// Larger files are left out of a snapshot
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+/// Files taken from a single directory target
+const MAX_DIR_FILES: usize = 256;
+
+/// Configuration read by a service, snapshotted when the service is named in the argv
+const SERVICE_CONFIGS: &[(&str, &str)] = &[
+    ("nginx", "/etc/nginx"),
+    ("apache2", "/etc/apache2"),
+    ("httpd", "/etc/httpd/conf"),
+    ("sshd", "/etc/ssh/sshd_config"),
+    ("ssh", "/etc/ssh/sshd_config"),
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SnapshotFile {
+    pub path: String,
+    /// Whether the file existed; restoring a file that did not removes it
+    pub existed: bool,
+    pub sha256: Option<String>,
+    pub size: u64,
+    /// Unix permission bits
+    #[serde(default)]
+    pub mode: Option<u32>,
+    /// Unix user and group ids
+    #[serde(default)]
+    pub owner: Option<(u32, u32)>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub id: String,
+    /// `CommandResult.id` of the execution it was taken before
+    pub command_id: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub created_at: String,
+    pub files: Vec<SnapshotFile>,
+}
+
+/// How a file differs now from its snapshot
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChange {
+    Unchanged,
+    Modified,
+    /// Existed in the snapshot and is gone now
+    Removed,
+    /// Did not exist in the snapshot
+    Created,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileDiff {
+    pub path: String,
+    pub change: FileChange,
+    /// Unified diff from the current content to the snapshot, i.e. what a restore changes
+    pub diff: String,
+    /// Either side is not UTF-8, so no diff is shown
+    pub binary: bool,
+}
+
+/// Copies of files taken before risky executions, stored in the data directory
+#[derive(Default)]
+pub struct SnapshotStore {
+    lock: Mutex<()>,
+}
+
+impl SnapshotStore {
+    /// Copy `paths` (files, or directories taken recursively) into a new snapshot.
+    /// Returns `None` when none of them could be read.
+    pub fn take(
+        &self,
+        command_id: &str,
+        command: &str,
+        args: &[String],
+        paths: &[PathBuf],
+    ) -> Result<Option<Snapshot>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let policy = CommandPolicy::load()?;
+
+        let mut files = Vec::new();
+        for path in paths {
+            collect_files(path, &policy, &mut files);
+        }
+        files.sort();
+        files.dedup();
+        if files.is_empty() {
+            return Ok(None);
+        }
+
+        let id = Uuid::new_v4().to_string();
+        let dir = get_snapshots_dir()?.join(&id);
+        create_private_dir(&dir)?;
+
+        let mut entries = Vec::new();
+        for path in files {
+            match copy_file(&path, &dir) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => eprintln!("[SNAPSHOT] {}", e),
+            }
+        }
+        if entries.is_empty() {
+            let _ = fs::remove_dir_all(&dir);
+            return Ok(None);
+        }
+
+        let snapshot = Snapshot {
+            id,
+            command_id: command_id.to_string(),
+            command: command.to_string(),
+            args: args.to_vec(),
+            created_at: Utc::now().to_rfc3339(),
+            files: entries,
+        };
+        let manifest = serde_json::to_string_pretty(&snapshot)
+            .map_err(|e| format!("Erro ao serializar snapshot: {}", e))?;
+        fs::write(dir.join(MANIFEST_FILE), manifest)
+            .map_err(|e| format!("Erro ao salvar snapshot: {}", e))?;
+
+        prune()?;
+        Ok(Some(snapshot))
+    }
+
+    /// Snapshots, most recent first, optionally only those of one execution
+    pub fn list(&self, command_id: Option<&str>) -> Result<Vec<Snapshot>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut snapshots: Vec<Snapshot> = load_all()?
+            .into_iter()
+            .filter(|s| command_id.is_none_or(|id| s.command_id == id))
+            .collect();
+        snapshots.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(snapshots)
+    }
+
+    /// Compare each file of a snapshot with its current content
+    pub fn diff(&self, id: &str) -> Result<Vec<FileDiff>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let dir = snapshot_dir(id)?;
+        let snapshot = load(&dir)?;
+
+        snapshot
+            .files
+            .iter()
+            .map(|file| {
+                let saved = read_saved(&dir, file)?;
+                let current = fs::read(&file.path).ok();
+                Ok(file_diff(&file.path, saved.as_deref(), current.as_deref()))
+            })
+            .collect()
+    }
+
+    /// Check the files of a snapshot, or only `paths` among them, before restoring them
+    pub fn prepare_restore(
+        &self,
+        id: &str,
+        paths: Option<&[String]>,
+    ) -> Result<RestorePlan, String> {
+        let _guard = self.lock.lock().unwrap();
+        let dir = snapshot_dir(id)?;
+        let snapshot = load(&dir)?;
+        let policy = CommandPolicy::load()?;
+
+        let files: Vec<SnapshotFile> = snapshot
+            .files
+            .into_iter()
+            .filter(|f| paths.is_none_or(|p| p.contains(&f.path)))
+            .collect();
+        if files.is_empty() {
+            return Err("Nenhum arquivo do snapshot foi selecionado".to_string());
+        }
+
+        // Check every saved copy before touching anything
+        for file in &files {
+            if policy.is_protected(Path::new(&file.path)) {
+                return Err(format!(
+                    "⚠️ SEGURANÇA: O caminho '{}' é protegido",
+                    file.path
+                ));
+            }
+            read_saved(&dir, file)?;
+        }
+
+        let elevated = files
+            .iter()
+            .filter(|f| !writable(Path::new(&f.path)))
+            .map(|f| f.path.clone())
+            .collect();
+        Ok(RestorePlan {
+            dir,
+            files,
+            elevated,
+        })
+    }
+
+    /// Put back the files of `plan` the app user can write. Returns the paths restored.
+    pub fn restore_unprivileged(&self, plan: &RestorePlan) -> Result<Vec<String>, String> {
+        let _guard = self.lock.lock().unwrap();
+        let mut restored = Vec::new();
+        for file in plan
+            .files
+            .iter()
+            .filter(|f| !plan.elevated.contains(&f.path))
+        {
+            let result = match read_saved(&plan.dir, file)? {
+                Some(content) => write_file(file, &content),
+                None => match fs::remove_file(&file.path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                    _ => Ok(()),
+                },
+            };
+            result.map_err(|e| {
+                format!(
+                    "Erro ao restaurar '{}': {}. Restaurados: {}",
+                    file.path,
+                    e,
+                    restored.len()
+                )
+            })?;
+            restored.push(file.path.clone());
+        }
+        Ok(restored)
+    }
+}
+
+/// Files selected for a restore, with their saved copies checked
+pub struct RestorePlan {
+    dir: PathBuf,
+    pub files: Vec<SnapshotFile>,
+    /// Paths the app user cannot write, put back through elevation
+    pub elevated: Vec<String>,
+}
+
+/// Files an execution may change: `declared` paths, arguments naming files (or new files in
+/// existing directories), and the configuration of services named in the argv
+pub fn targets(
+    command: &str,
+    args: &[String],
+    context: &ExecutionContext,
+    declared: &[String],
+) -> Vec<PathBuf> {
+    let cwd = context
+        .cwd
+        .as_deref()
+        .map(PathBuf::from)
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+    let resolve = |value: &str| {
+        if value == "~" || value.starts_with("~/") || Path::new(value).is_absolute() {
+            normalize_path(value)
+        } else {
+            normalize_path(&cwd.join(value).to_string_lossy())
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = declared.iter().map(|p| resolve(p)).collect();
+    for arg in args {
+        // `--config=/etc/app.conf` names a file too
+        let value = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with('-') => value,
+            _ if arg.starts_with('-') => continue,
+            _ => arg.as_str(),
+        };
+        if value.is_empty() {
+            continue;
+        }
+        let path = resolve(value);
+        let new_file = value.contains('/') && path.parent().is_some_and(Path::is_dir);
+        if path.is_file() || (!path.exists() && new_file) {
+            paths.push(path);
+        }
+    }
+
+    for word in std::iter::once(command).chain(args.iter().map(String::as_str)) {
+        let name = word.strip_suffix(".service").unwrap_or(word);
+        let name = name.rsplit('/').next().unwrap_or(name);
+        for (service, config) in SERVICE_CONFIGS {
+            if name == *service && Path::new(config).exists() {
+                paths.push(PathBuf::from(config));
+            }
+        }
+    }
+
+    let mut unique = Vec::new();
+    for path in paths {
+        if !unique.contains(&path) {
+            unique.push(path);
+        }
+    }
+    unique
+}
+
+/// Snapshot the targets of an execution about to run, when it is elevated, at least
+/// medium risk, or declares paths. A failed snapshot is logged and does not stop the execution.
+///
+/// Files are found and copied off the async runtime.
+#[allow(clippy::too_many_arguments)]
+pub async fn before_execution(
+    window: &Window,
+    command_id: &str,
+    command: &str,
+    args: &[String],
+    requires_sudo: bool,
+    risk: RiskLevel,
+    context: &ExecutionContext,
+    declared: &[String],
+) -> Option<String> {
+    if declared.is_empty() && !requires_sudo && risk < RiskLevel::Medium {
+        return None;
+    }
+
+    let app = window.app_handle();
+    let command_id = command_id.to_string();
+    let command = command.to_string();
+    let args = args.to_vec();
+    let context = context.clone();
+    let declared = declared.to_vec();
+    let taken = tokio::task::spawn_blocking(move || {
+        let paths = targets(&command, &args, &context, &declared);
+        if paths.is_empty() {
+            return Ok(None);
+        }
+        app.state::<SnapshotStore>()
+            .take(&command_id, &command, &args, &paths)
+    })
+    .await
+    .map_err(|e| format!("Erro ao tirar snapshot: {}", e))
+    .and_then(|taken| taken);
+    match taken {
+        Ok(snapshot) => snapshot.map(|s| s.id),
+        Err(e) => {
+            eprintln!("[SNAPSHOT] {}", e);
+            None
+        }
+    }
+}
+
+/// Regular files under `path`, without following symlinks or entering protected paths
+fn collect_files(path: &Path, policy: &CommandPolicy, files: &mut Vec<PathBuf>) {
+    if policy.is_protected(path) {
+        return;
+    }
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        // Kept so that restoring removes the file the command creates
+        files.push(path.to_path_buf());
+        return;
+    };
+
+    if metadata.is_file() {
+        files.push(path.to_path_buf());
+    } else if metadata.is_dir() {
+        let mut pending = vec![path.to_path_buf()];
+        let mut found = 0;
+        while let Some(dir) = pending.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(kind) = entry.file_type() else {
+                    continue;
+                };
+                let entry_path = entry.path();
+                if policy.is_protected(&entry_path) {
+                    continue;
+                }
+                if kind.is_dir() {
+                    pending.push(entry_path);
+                } else if kind.is_file() {
+                    if found == MAX_DIR_FILES {
+                        eprintln!(
+                            "[SNAPSHOT] '{}' tem mais de {} arquivos; o restante foi ignorado",
+                            path.display(),
+                            MAX_DIR_FILES
+                        );
+                        return;
+                    }
+                    files.push(entry_path);
+                    found += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Save the current content of `path` into `dir`, named by its SHA-256
+fn copy_file(path: &Path, dir: &Path) -> Result<SnapshotFile, String> {
+    let display = path.to_string_lossy().to_string();
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(SnapshotFile {
+                path: display,
+                existed: false,
+                sha256: None,
+                size: 0,
+                mode: None,
+                owner: None,
+            })
+        }
+        Err(e) => return Err(format!("Erro ao ler '{}': {}", display, e)),
+    };
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(format!(
+            "'{}' tem mais de {} bytes e não foi copiado",
+            display, MAX_FILE_BYTES
+        ));
+    }
+
+    let content = fs::read(path).map_err(|e| format!("Erro ao ler '{}': {}", display, e))?;
+    let sha256 = audit::sha256_hex(&content);
+    fs::write(dir.join(&sha256), &content)
+        .map_err(|e| format!("Erro ao copiar '{}': {}", display, e))?;
+
+    Ok(SnapshotFile {
+        path: display,
+        existed: true,
+        sha256: Some(sha256),
+        size: content.len() as u64,
+        mode: file_mode(&metadata),
+        owner: file_owner(&metadata),
+    })
+}
+
+/// Saved content of `file`, `None` when it did not exist; fails if the copy was altered
+fn read_saved(dir: &Path, file: &SnapshotFile) -> Result<Option<Vec<u8>>, String> {
+    let Some(sha256) = &file.sha256 else {
+        return Ok(None);
+    };
+    let content = fs::read(dir.join(sha256))
+        .map_err(|e| format!("Cópia de '{}' ilegível: {}", file.path, e))?;
+    if audit::sha256_hex(&content) != *sha256 {
+        return Err(format!(
+            "⚠️ SEGURANÇA: A cópia de '{}' foi alterada após o snapshot",
+            file.path
+        ));
+    }
+    Ok(Some(content))
+}
+
+/// Write the saved content back without following a symlink placed at the path since the
+/// snapshot, which would redirect the write to any file the app user can change
+fn write_file(file: &SnapshotFile, content: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let path = Path::new(&file.path);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink()) {
+        return Err(std::io::Error::other(
+            "⚠️ SEGURANÇA: o caminho virou um link simbólico depois do snapshot",
+        ));
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        // Closes the gap between the check above and the open
+        options.custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut out = options.open(path)?;
+    out.write_all(content)?;
+    #[cfg(unix)]
+    if let Some(mode) = file.mode {
+        use std::os::unix::fs::PermissionsExt;
+        out.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Put `file` back with elevated privileges, from its saved copy in `dir`
+async fn restore_elevated(
+    dir: &Path,
+    file: &SnapshotFile,
+    registry: &ProcessRegistry,
+) -> Result<(), String> {
+    let args = match &file.sha256 {
+        Some(sha256) => {
+            let mut args = vec!["-m".to_string()];
+            args.push(format!("{:o}", file.mode.unwrap_or(0o644)));
+            if let Some((uid, gid)) = file.owner {
+                args.extend(["-o".to_string(), uid.to_string()]);
+                args.extend(["-g".to_string(), gid.to_string()]);
+            }
+            args.push("--".to_string());
+            args.push(dir.join(sha256).to_string_lossy().to_string());
+            args.push(file.path.clone());
+            args
+        }
+        None => vec!["-f".to_string(), "--".to_string(), file.path.clone()],
+    };
+    let command = if file.sha256.is_some() {
+        "install"
+    } else {
+        "rm"
+    };
+
+    let result = executor::run(
+        Uuid::new_v4().to_string(),
+        command,
+        &args,
+        true,
+        &ExecutionLimits::default(),
+        &ExecutionContext::default(),
+        OutputEncoding::default(),
+        None,
+        None,
+        registry,
+    )
+    .await?;
+    if result.exit_code != 0 {
+        return Err(format!(
+            "Erro ao restaurar '{}': {}",
+            file.path,
+            result.stderr.trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Whether the app user can write `path`, or create or remove it in its directory
+#[cfg(unix)]
+fn writable(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let target = if path.exists() {
+        path
+    } else {
+        match path.ancestors().skip(1).find(|p| p.exists()) {
+            Some(parent) => parent,
+            None => return false,
+        }
+    };
+    let Ok(target) = CString::new(target.as_os_str().as_bytes()) else {
+        return false;
+    };
+    unsafe { libc::access(target.as_ptr(), libc::W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn writable(_path: &Path) -> bool {
+    true
+}
+
+fn file_diff(path: &str, saved: Option<&[u8]>, current: Option<&[u8]>) -> FileDiff {
+    let change = match (saved, current) {
+        (Some(saved), Some(current)) if saved == current => FileChange::Unchanged,
+        (Some(_), Some(_)) => FileChange::Modified,
+        (Some(_), None) => FileChange::Removed,
+        (None, Some(_)) => FileChange::Created,
+        (None, None) => FileChange::Unchanged,
+    };
+
+    let (diff, binary) = match (as_text(current), as_text(saved)) {
+        _ if change == FileChange::Unchanged => (String::new(), false),
+        (Some(current), Some(saved)) => (
+            TextDiff::from_lines(current, saved)
+                .unified_diff()
+                .header(
+                    &format!("{} (atual)", path),
+                    &format!("{} (snapshot)", path),
+                )
+                .to_string(),
+            false,
+        ),
+        _ => (String::new(), true),
+    };
+
+    FileDiff {
+        path: path.to_string(),
+        change,
+        diff,
+        binary,
+    }
+}
+
+fn as_text(bytes: Option<&[u8]>) -> Option<&str> {
+    std::str::from_utf8(bytes.unwrap_or_default()).ok()
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn file_owner(metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn file_owner(_metadata: &fs::Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+fn create_private_dir(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Erro ao criar diretório de snapshot: {}", e))?;
+    // Copies of system files must not become readable by other users
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .map_err(|e| format!("Erro ao proteger diretório de snapshot: {}", e))?;
+    }
+    Ok(())
+}
+
+fn get_snapshots_dir() -> Result<PathBuf, String> {
+    let dir = config::get_data_dir()?.join(SNAPSHOT_DIR);
+    create_private_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Directory of snapshot `id`; the id must be a UUID so it cannot point elsewhere
+fn snapshot_dir(id: &str) -> Result<PathBuf, String> {
+    Uuid::parse_str(id).map_err(|_| format!("Id de snapshot inválido: '{}'", id))?;
+    let dir = get_snapshots_dir()?.join(id);
+    if !dir.is_dir() {
+        return Err(format!("Nenhum snapshot com id '{}'", id));
+    }
+    Ok(dir)
+}
+
+fn load(dir: &Path) -> Result<Snapshot, String> {
+    let content = fs::read_to_string(dir.join(MANIFEST_FILE))
+        .map_err(|e| format!("Erro ao ler snapshot: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Snapshot corrompido: {}", e))
+}
+
+fn load_all() -> Result<Vec<Snapshot>, String> {
+    let entries =
+        fs::read_dir(get_snapshots_dir()?).map_err(|e| format!("Erro ao ler snapshots: {}", e))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| load(&entry.path()).ok())
+        .collect())
+}
+
+fn prune() -> Result<(), String> {
+    let mut snapshots = load_all()?;
+    if snapshots.len() <= MAX_SNAPSHOTS {
+        return Ok(());
+    }
+    snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    let dir = get_snapshots_dir()?;
+    for snapshot in &snapshots[..snapshots.len() - MAX_SNAPSHOTS] {
+        let _ = fs::remove_dir_all(dir.join(&snapshot.id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub fn list_snapshots(
+    command_id: Option<String>,
+    snapshots: State<'_, SnapshotStore>,
+) -> Result<Vec<Snapshot>, String> {
+    snapshots.list(command_id.as_deref())
+}
+
+/// What restoring the snapshot would change, file by file
+#[tauri::command]
+pub fn get_snapshot_diff(
+    id: String,
+    snapshots: State<'_, SnapshotStore>,
+) -> Result<Vec<FileDiff>, String> {
+    snapshots.diff(&id)
+}
+
+/// Checks `paths` of snapshot `id` off the main thread
+async fn prepare_restore(
+    window: &Window,
+    id: &str,
+    paths: Option<&[String]>,
+) -> Result<RestorePlan, String> {
+    let app = window.app_handle();
+    let id = id.to_string();
+    let paths = paths.map(<[String]>::to_vec);
+    tokio::task::spawn_blocking(move || {
+        app.state::<SnapshotStore>()
+            .prepare_restore(&id, paths.as_deref())
+    })
+    .await
+    .map_err(|e| format!("Erro ao verificar snapshot: {}", e))?
+}
+
+/// Called by the UI once the user confirms a restore; the token is then passed to `restore_snapshot`
+#[tauri::command]
+pub async fn request_restore_approval(
+    window: Window,
+    id: String,
+    paths: Option<Vec<String>>,
+    approvals: State<'_, ApprovalStore>,
+) -> Result<ApprovalToken, String> {
+    prepare_restore(&window, &id, paths.as_deref()).await?;
+    Ok(approvals.issue_restore(&id, paths.as_deref()))
+}
+
+/// Put the files of a snapshot back, or only `paths` among them, after a native
+/// confirmation. Files the app user cannot write are restored with elevated privileges.
+/// Returns the paths restored.
+#[tauri::command]
+pub async fn restore_snapshot(
+    window: Window,
+    id: String,
+    paths: Option<Vec<String>>,
+    approval_token: String,
+    approvals: State<'_, ApprovalStore>,
+    audit: State<'_, AuditLog>,
+    registry: State<'_, ProcessRegistry>,
+) -> Result<Vec<String>, String> {
+    approvals.consume_restore(&approval_token, &id, paths.as_deref())?;
+    let plan = prepare_restore(&window, &id, paths.as_deref()).await?;
+    let selected: Vec<String> = plan.files.iter().map(|f| f.path.clone()).collect();
+    approval::confirm_restore_natively(&window, &id, &selected, &plan.elevated).await?;
+
+    let elevated = !plan.elevated.is_empty();
+    let approval = AuditApproval {
+        token_digest: audit::sha256_hex(approval_token.as_bytes()),
+        risk: if elevated {
+            RiskLevel::High
+        } else {
+            RiskLevel::Medium
+        },
+        reason: format!("Restauração do snapshot {}", id),
+        native_confirmation: true,
+    };
+    let outcome = restore(&window, plan, &registry).await;
+    let entry = AuditEntry::restore(window.label(), approval, &id, &selected, elevated, &outcome);
+    if let Err(e) = audit.append(entry) {
+        eprintln!("[AUDIT] {}", e);
+    }
+
+    if let Ok(restored) = &outcome {
+        println!("[SNAPSHOT] {} restaurado ({} arquivos)", id, restored.len());
+    }
+    outcome
+}
+
+async fn restore(
+    window: &Window,
+    plan: RestorePlan,
+    registry: &ProcessRegistry,
+) -> Result<Vec<String>, String> {
+    let app = window.app_handle();
+    let (plan, mut restored) = tokio::task::spawn_blocking(move || {
+        let restored = app.state::<SnapshotStore>().restore_unprivileged(&plan);
+        restored.map(|restored| (plan, restored))
+    })
+    .await
+    .map_err(|e| format!("Erro ao restaurar snapshot: {}", e))??;
+
+    for file in plan
+        .files
+        .iter()
+        .filter(|f| plan.elevated.contains(&f.path))
+    {
+        restore_elevated(&plan.dir, file, registry)
+            .await
+            .map_err(|e| format!("{}. Restaurados: {}", e, restored.len()))?;
+        restored.push(file.path.clone());
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Directory of one test, removed when dropped
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("snapshot-test-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn file(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    /// Save `paths` into a snapshot directory inside `tree`, as `take` does
+    fn restore_plan(tree: &Tree, paths: &[&Path]) -> RestorePlan {
+        let dir = tree.0.join("saved");
+        fs::create_dir_all(&dir).unwrap();
+        let files = paths.iter().map(|p| copy_file(p, &dir).unwrap()).collect();
+        RestorePlan {
+            dir,
+            files,
+            elevated: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn targets_resolve_arguments_against_the_cwd() {
+        let tree = Tree::new();
+        let app = tree.file("app.conf", "a");
+        let site = tree.file("sites/site.conf", "b");
+        let context = ExecutionContext::new(Some(tree.0.to_string_lossy().to_string()), None);
+
+        let found = targets(
+            "sed",
+            &args(&[
+                "-i",
+                "--config=sites/site.conf",
+                "app.conf",
+                "sites/new.conf",
+                "missing/new.conf",
+                "s/a/b/",
+                "restart",
+            ]),
+            &context,
+            &args(&["declared.conf"]),
+        );
+        assert_eq!(
+            found,
+            [
+                tree.0.join("declared.conf"),
+                site,
+                app,
+                tree.0.join("sites/new.conf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_diff_shows_what_a_restore_changes() {
+        let modified = file_diff("/etc/app.conf", Some(b"old\n"), Some(b"new\n"));
+        assert_eq!(modified.change, FileChange::Modified);
+        assert!(modified.diff.contains("-new") && modified.diff.contains("+old"));
+        assert!(!modified.binary);
+
+        assert_eq!(
+            file_diff("/x", Some(b"a"), Some(b"a")).change,
+            FileChange::Unchanged
+        );
+        assert_eq!(
+            file_diff("/x", Some(b"a"), None).change,
+            FileChange::Removed
+        );
+        assert_eq!(
+            file_diff("/x", None, Some(b"a")).change,
+            FileChange::Created
+        );
+        assert!(file_diff("/x", Some(&[0xff]), Some(b"a")).binary);
+    }
+
+    #[test]
+    fn restoring_removes_files_created_after_the_snapshot() {
+        let tree = Tree::new();
+        let created = tree.0.join("created.conf");
+        let edited = tree.file("edited.conf", "before");
+        let plan = restore_plan(&tree, &[&created, &edited]);
+        assert!(!plan.files[0].existed);
+
+        fs::write(&created, "new").unwrap();
+        fs::write(&edited, "after").unwrap();
+        let restored = SnapshotStore::default()
+            .restore_unprivileged(&plan)
+            .unwrap();
+        assert_eq!(restored.len(), 2);
+        assert!(!created.exists());
+        assert_eq!(fs::read_to_string(&edited).unwrap(), "before");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restoring_does_not_follow_a_symlink_put_at_the_path() {
+        let tree = Tree::new();
+        let edited = tree.file("edited.conf", "before");
+        let victim = tree.file("victim", "untouched");
+        let plan = restore_plan(&tree, &[&edited]);
+
+        fs::remove_file(&edited).unwrap();
+        std::os::unix::fs::symlink(&victim, &edited).unwrap();
+        let err = SnapshotStore::default()
+            .restore_unprivileged(&plan)
+            .unwrap_err();
+        assert!(err.contains("link simbólico"), "{}", err);
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "untouched");
+    }
+}
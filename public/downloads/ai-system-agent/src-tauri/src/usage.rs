@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+/// What a finished process consumed, as reported by `wait4`. Includes the
+/// descendants it waited for, but not those still running when it exited.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    pub user_cpu_ms: u64,
+    pub system_cpu_ms: u64,
+    /// Peak resident set size
+    pub max_rss_bytes: u64,
+    /// Filesystem blocks read and written; reads served from the page cache do not count
+    pub block_input: u64,
+    pub block_output: u64,
+    pub voluntary_context_switches: u64,
+    pub involuntary_context_switches: u64,
+}
+
+impl ResourceUsage {
+    /// Usage of processes that ran side by side, like the stages of a pipeline
+    pub fn combine(self, other: ResourceUsage) -> ResourceUsage {
+        ResourceUsage {
+            user_cpu_ms: self.user_cpu_ms + other.user_cpu_ms,
+            system_cpu_ms: self.system_cpu_ms + other.system_cpu_ms,
+            max_rss_bytes: self.max_rss_bytes.max(other.max_rss_bytes),
+            block_input: self.block_input + other.block_input,
+            block_output: self.block_output + other.block_output,
+            voluntary_context_switches: self.voluntary_context_switches
+                + other.voluntary_context_switches,
+            involuntary_context_switches: self.involuntary_context_switches
+                + other.involuntary_context_switches,
+        }
+    }
+
+    pub fn cpu_ms(&self) -> u64 {
+        self.user_cpu_ms + self.system_cpu_ms
+    }
+
+    pub fn block_io(&self) -> u64 {
+        self.block_input + self.block_output
+    }
+
+    pub fn context_switches(&self) -> u64 {
+        self.voluntary_context_switches + self.involuntary_context_switches
+    }
+
+    #[cfg(unix)]
+    fn from_rusage(usage: &libc::rusage) -> ResourceUsage {
+        let millis = |time: libc::timeval| time.tv_sec as u64 * 1000 + time.tv_usec as u64 / 1000;
+        // Linux reports the peak RSS in kilobytes, macOS in bytes
+        let rss_unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+        ResourceUsage {
+            user_cpu_ms: millis(usage.ru_utime),
+            system_cpu_ms: millis(usage.ru_stime),
+            max_rss_bytes: usage.ru_maxrss as u64 * rss_unit,
+            block_input: usage.ru_inblock as u64,
+            block_output: usage.ru_oublock as u64,
+            voluntary_context_switches: usage.ru_nvcsw as u64,
+            involuntary_context_switches: usage.ru_nivcsw as u64,
+        }
+    }
+}
+
+/// Block until `pid` exits, then reap it and return its status and resource usage.
+///
+/// Whoever spawned the process must keep its handle alive until this returns, so
+/// nothing else reaps it first.
+#[cfg(unix)]
+pub fn wait(pid: u32) -> std::io::Result<(std::process::ExitStatus, ResourceUsage)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let rc = unsafe { libc::wait4(pid as libc::pid_t, &mut status, 0, &mut usage) };
+        if rc != -1 {
+            break;
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    Ok((
+        std::process::ExitStatus::from_raw(status),
+        ResourceUsage::from_rusage(&usage),
+    ))
+}
+
+/// Resource usage of `pid` once it has exited, leaving it unreaped for its owner to wait
+/// on. Awaits a pidfd instead of blocking a thread; `None` on kernels without pidfds.
+///
+/// Whoever spawned the process must not reap it before this returns, so its pid cannot
+/// be reused in the meantime.
+#[cfg(target_os = "linux")]
+pub async fn exited_usage(pid: u32) -> Option<ResourceUsage> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use tokio::io::unix::AsyncFd;
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        return None;
+    }
+    let pidfd = AsyncFd::new(unsafe { OwnedFd::from_raw_fd(fd as libc::c_int) }).ok()?;
+    // A pidfd becomes readable when the process exits
+    let _ready = pidfd.readable().await.ok()?;
+
+    // The raw `waitid` also reports usage, and `WNOWAIT` keeps the zombie
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_waitid,
+                libc::P_PID,
+                pid as libc::id_t,
+                &mut info as *mut libc::siginfo_t,
+                libc::WEXITED | libc::WNOWAIT,
+                &mut usage as *mut libc::rusage,
+            )
+        };
+        if rc == 0 {
+            return Some(ResourceUsage::from_rusage(&usage));
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return None;
+        }
+    }
+}
@@ -1,5 +1,6 @@
 import { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import { useStore } from './store';
 import { Sidebar } from './components/Sidebar';
 import { Chat } from './components/Chat';
@@ -13,7 +14,7 @@ type ViewMode = 'chat' | 'editor';
 function App() {
   const [activeTab, setActiveTab] = useState<'chat' | 'settings'>('chat');
   const [viewMode, setViewMode] = useState<ViewMode>('chat');
-  const { systemInfo, setSystemInfo, pendingCommands, setConfig } = useStore();
+  const { systemInfo, setSystemInfo, pendingCommands, setPendingCommands, setConfig } = useStore();
 
   useEffect(() => {
     // Get system info on startup
@@ -25,6 +26,18 @@ function App() {
     invoke('get_config').then((config: any) => {
       setConfig(config);
     }).catch(console.error);
+
+    // The command queue lives in the backend; after a reload, pick up where it is
+    invoke('get_command_queue').then((queue: any) => {
+      setPendingCommands(queue);
+    }).catch(console.error);
+    const unlistenQueue = listen<any>('command-queue-changed', (event) => {
+      setPendingCommands(event.payload);
+    });
+
+    return () => {
+      unlistenQueue.then((unlisten) => unlisten());
+    };
   }, []);
 
   return (
@@ -10,7 +10,6 @@ export function Chat() {
   const {
     messages,
     addMessage,
-    addPendingCommands,
     systemInfo,
     config,
     isLoading,
@@ -79,7 +78,7 @@ export function Chat() {
 
         // Add suggested commands to queue
         if (response.suggested_commands?.length > 0) {
-          addPendingCommands(response.suggested_commands);
+          await invoke('enqueue_commands', { commands: response.suggested_commands });
         }
       }
     } catch (error: any) {
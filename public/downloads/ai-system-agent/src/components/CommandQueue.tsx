@@ -10,40 +10,156 @@ import {
   Shield,
   ChevronDown,
   ChevronUp,
+  FlaskConical,
+  ListOrdered,
+  History,
+  RotateCcw,
+  KeyRound,
 } from 'lucide-react';
 import { motion, AnimatePresence } from 'framer-motion';
 
 export function CommandQueue() {
-  const { pendingCommands, updateCommandStatus, removeCommand } = useStore();
+  const { pendingCommands } = useStore();
   const [expandedId, setExpandedId] = useState<string | null>(null);
   const [executing, setExecuting] = useState<string | null>(null);
+  const [runningPlan, setRunningPlan] = useState(false);
+  const [helperRunning, setHelperRunning] = useState(false);
+  const [helperError, setHelperError] = useState<string | null>(null);
+  const [queueError, setQueueError] = useState<string | null>(null);
+  const [snapshotDiffs, setSnapshotDiffs] = useState<
+    Record<string, { path: string; change: string; diff: string; binary: boolean }[] | { error: string }>
+  >({});
+  const [simulating, setSimulating] = useState<string | null>(null);
+  const [simulations, setSimulations] = useState<
+    Record<string, { predicted_effect: string; stdout: string; stderr: string } | { error: string }>
+  >({});
 
+  const handleSimulate = async (id: string) => {
+    const command = pendingCommands.find((c) => c.id === id);
+    if (!command) return;
+
+    setSimulating(id);
+    try {
+      const report = await invoke<{ predicted_effect: string; stdout: string; stderr: string }>(
+        'simulate_command',
+        { command: command.command, args: command.args }
+      );
+      setSimulations((prev) => ({ ...prev, [id]: report }));
+    } catch (error: any) {
+      setSimulations((prev) => ({ ...prev, [id]: { error: error.message || error } }));
+    } finally {
+      setSimulating(null);
+    }
+  };
+
+  // The backend moves the entry through approved, executing and completed/failed
   const handleApprove = async (id: string) => {
     const command = pendingCommands.find((c) => c.id === id);
     if (!command) return;
 
     setExecuting(id);
-    updateCommandStatus(id, 'executing');
+    setQueueError(null);
 
     try {
-      const result = await invoke('execute_command', {
+      const approval = await invoke<{ token: string }>('request_approval', {
         command: command.command,
         args: command.args,
-        requiresSudo: command.requires_sudo,
-        userConfirmed: true,
+        requiresSudo: command.local_assessment.requires_sudo,
       });
 
-      updateCommandStatus(id, 'completed', result);
+      await invoke('execute_command', {
+        command: command.command,
+        args: command.args,
+        requiresSudo: command.local_assessment.requires_sudo,
+        approvalToken: approval.token,
+        queueId: id,
+      });
     } catch (error: any) {
-      updateCommandStatus(id, 'failed', { error: error.message || error });
+      setQueueError(error.message || error);
     } finally {
       setExecuting(null);
     }
   };
 
-  const handleReject = (id: string) => {
-    updateCommandStatus(id, 'rejected');
-    removeCommand(id);
+  // Runs every pending command in order as one plan, stopping at the first failure
+  const handleRunPlan = async () => {
+    const commands = pendingCommands.filter((c) => c.status === 'pending');
+    if (commands.length === 0) return;
+
+    const planSteps = commands.map((c) => ({
+      command: c.command,
+      args: c.args,
+      requires_sudo: c.local_assessment.requires_sudo,
+      description: c.description,
+      verify: c.verify,
+    }));
+
+    setRunningPlan(true);
+    setQueueError(null);
+
+    try {
+      const approval = await invoke<{ token: string }>('request_plan_approval', {
+        steps: planSteps,
+      });
+      await invoke('execute_plan', {
+        steps: planSteps,
+        approvalToken: approval.token,
+        queueIds: commands.map((c) => c.id),
+      });
+    } catch (error: any) {
+      setQueueError(error.message || error);
+    } finally {
+      setRunningPlan(false);
+    }
+  };
+
+  // Authenticates once so elevated commands stop prompting until the helper goes idle
+  const handleToggleHelper = async () => {
+    setHelperError(null);
+    try {
+      const status = await invoke<{ running: boolean }>(
+        helperRunning ? 'stop_privileged_helper' : 'start_privileged_helper'
+      );
+      setHelperRunning(status.running);
+    } catch (error: any) {
+      setHelperError(error.message || error);
+    }
+  };
+
+  // Shows what restoring the files saved before the command would change
+  const handleSnapshotDiff = async (snapshotId: string) => {
+    try {
+      const diffs = await invoke<{ path: string; change: string; diff: string; binary: boolean }[]>(
+        'get_snapshot_diff',
+        { id: snapshotId }
+      );
+      setSnapshotDiffs((prev) => ({ ...prev, [snapshotId]: diffs }));
+    } catch (error: any) {
+      setSnapshotDiffs((prev) => ({ ...prev, [snapshotId]: { error: error.message || error } }));
+    }
+  };
+
+  const handleRestoreSnapshot = async (snapshotId: string) => {
+    try {
+      const approval = await invoke<{ token: string }>('request_restore_approval', {
+        id: snapshotId,
+      });
+      await invoke<string[]>('restore_snapshot', {
+        id: snapshotId,
+        approvalToken: approval.token,
+      });
+      await handleSnapshotDiff(snapshotId);
+    } catch (error: any) {
+      setSnapshotDiffs((prev) => ({ ...prev, [snapshotId]: { error: error.message || error } }));
+    }
+  };
+
+  const handleReject = async (id: string) => {
+    try {
+      await invoke('remove_queued_command', { id });
+    } catch (error: any) {
+      setQueueError(error.message || error);
+    }
   };
 
   const getRiskColor = (risk: string) => {
@@ -61,6 +177,8 @@ export function CommandQueue() {
 
   const getStatusIcon = (status: string) => {
     switch (status) {
+      case 'approved':
+        return <Loader2 className="w-4 h-4 text-zinc-400" />;
       case 'executing':
         return <Loader2 className="w-4 h-4 animate-spin text-violet-400" />;
       case 'completed':
@@ -73,6 +191,10 @@ export function CommandQueue() {
   };
 
   const pendingOnly = pendingCommands.filter((c) => c.status === 'pending');
+  // Approved entries wait for a free slot of the backend's execution pool
+  const inFlight = pendingCommands.filter(
+    (c) => c.status === 'approved' || c.status === 'executing'
+  );
   const completedOrFailed = pendingCommands.filter(
     (c) => c.status === 'completed' || c.status === 'failed'
   );
@@ -88,12 +210,37 @@ export function CommandQueue() {
         <p className="text-xs text-zinc-400">
           Revise cada comando antes de executar. Clique para ver detalhes.
         </p>
+        {pendingOnly.length > 1 && (
+          <button
+            onClick={handleRunPlan}
+            disabled={runningPlan || executing !== null}
+            className="mt-3 w-full flex items-center justify-center gap-2 px-3 py-2 text-sm rounded-lg bg-violet-600 hover:bg-violet-500 disabled:opacity-50 transition-colors"
+          >
+            {runningPlan ? (
+              <Loader2 className="w-4 h-4 animate-spin" />
+            ) : (
+              <ListOrdered className="w-4 h-4" />
+            )}
+            Executar em sequência
+          </button>
+        )}
+        {pendingOnly.some((c) => c.local_assessment.requires_sudo) && (
+          <button
+            onClick={handleToggleHelper}
+            className="mt-2 w-full flex items-center justify-center gap-2 px-3 py-2 text-xs rounded-lg bg-zinc-800 hover:bg-zinc-700 transition-colors"
+          >
+            <KeyRound className="w-4 h-4 text-yellow-400" />
+            {helperRunning ? 'Encerrar sessão privilegiada' : 'Autenticar uma vez para esta sessão'}
+          </button>
+        )}
+        {helperError && <p className="mt-2 text-xs text-red-400">{helperError}</p>}
+        {queueError && <p className="mt-2 text-xs text-red-400">{queueError}</p>}
       </div>
 
       {/* Commands List */}
       <div className="flex-1 overflow-y-auto p-4 space-y-3">
         <AnimatePresence>
-          {pendingOnly.map((cmd) => (
+          {[...inFlight, ...pendingOnly].map((cmd) => (
             <motion.div
               key={cmd.id}
               initial={{ opacity: 0, y: 10 }}
@@ -110,18 +257,27 @@ export function CommandQueue() {
               >
                 <div className="flex-1">
                   <div className="flex items-center gap-2 mb-1">
-                    {cmd.requires_sudo && (
+                    {cmd.local_assessment.requires_sudo && (
                       <span className="text-xs px-1.5 py-0.5 bg-orange-500/20 text-orange-400 rounded">
                         SUDO
                       </span>
                     )}
                     <span
                       className={`text-xs px-1.5 py-0.5 rounded border ${getRiskColor(
-                        cmd.risk_level
+                        cmd.local_assessment.risk_level
                       )}`}
                     >
-                      {cmd.risk_level.toUpperCase()}
+                      {cmd.local_assessment.risk_level.toUpperCase()}
                     </span>
+                    {cmd.disagreements.length > 0 && (
+                      <span
+                        title={cmd.disagreements.join('\n')}
+                        className="text-xs px-1.5 py-0.5 bg-red-500/20 text-red-400 rounded flex items-center gap-1"
+                      >
+                        <AlertTriangle className="w-3 h-3" />
+                        DIVERGÊNCIA
+                      </span>
+                    )}
                     {getStatusIcon(cmd.status)}
                   </div>
                   <code className="text-sm text-zinc-300">
@@ -152,7 +308,31 @@ export function CommandQueue() {
                         </div>
                       )}
 
-                      {cmd.risk_level === 'high' && (
+                      {cmd.disagreements.length > 0 && (
+                        <div className="flex items-start gap-2 p-2 bg-red-500/10 border border-red-500/30 rounded-lg">
+                          <AlertTriangle className="w-4 h-4 text-red-400 shrink-0 mt-0.5" />
+                          <div className="text-xs text-red-300 space-y-1">
+                            <p className="font-semibold">
+                              A avaliação do modelo diverge da análise local
+                              (modelo: {cmd.risk_level.toUpperCase()}
+                              {cmd.requires_sudo ? ', sudo' : ''})
+                            </p>
+                            {cmd.disagreements.map((d, i) => (
+                              <p key={i}>{d}</p>
+                            ))}
+                          </div>
+                        </div>
+                      )}
+
+                      {cmd.local_assessment.reason && (
+                        <div className="bg-zinc-900 rounded-lg p-3">
+                          <p className="text-xs text-zinc-400">
+                            Análise local: {cmd.local_assessment.reason}
+                          </p>
+                        </div>
+                      )}
+
+                      {cmd.local_assessment.risk_level === 'high' && (
                         <div className="flex items-start gap-2 p-2 bg-red-500/10 rounded-lg">
                           <AlertTriangle className="w-4 h-4 text-red-400 shrink-0 mt-0.5" />
                           <p className="text-xs text-red-300">
@@ -162,6 +342,43 @@ export function CommandQueue() {
                         </div>
                       )}
 
+                      {/* Simulation */}
+                      {cmd.simulation && cmd.status === 'pending' && (
+                        <button
+                          onClick={() => handleSimulate(cmd.id)}
+                          disabled={simulating === cmd.id}
+                          title={cmd.simulation.description}
+                          className="w-full flex items-center justify-center gap-2 py-2 bg-zinc-700 hover:bg-zinc-600 disabled:opacity-50 rounded-lg transition-colors text-sm"
+                        >
+                          {simulating === cmd.id ? (
+                            <Loader2 className="w-4 h-4 animate-spin" />
+                          ) : (
+                            <FlaskConical className="w-4 h-4" />
+                          )}
+                          Simular ({cmd.simulation.command} {cmd.simulation.args.join(' ')})
+                        </button>
+                      )}
+                      {simulations[cmd.id] && (
+                        <div className="bg-zinc-950 rounded-lg p-3 font-mono text-xs overflow-x-auto">
+                          {'error' in simulations[cmd.id] ? (
+                            <p className="text-red-400">
+                              {(simulations[cmd.id] as { error: string }).error}
+                            </p>
+                          ) : (
+                            <>
+                              <p className="text-violet-300 mb-2">
+                                Efeito previsto:{' '}
+                                {(simulations[cmd.id] as { predicted_effect: string }).predicted_effect}
+                              </p>
+                              <pre className="text-zinc-400 whitespace-pre-wrap max-h-40 overflow-y-auto">
+                                {(simulations[cmd.id] as { stdout: string }).stdout}
+                                {(simulations[cmd.id] as { stderr: string }).stderr}
+                              </pre>
+                            </>
+                          )}
+                        </div>
+                      )}
+
                       {/* Action Buttons */}
                       {cmd.status === 'pending' && (
                         <div className="flex gap-2">
@@ -191,8 +408,8 @@ export function CommandQueue() {
                       {/* Result */}
                       {cmd.result && (
                         <div className="bg-zinc-950 rounded-lg p-3 font-mono text-xs overflow-x-auto">
-                          {cmd.result.error ? (
-                            <p className="text-red-400">{cmd.result.error}</p>
+                          {cmd.error ? (
+                            <p className="text-red-400">{cmd.error}</p>
                           ) : (
                             <>
                               {cmd.result.stdout && (
@@ -208,7 +425,58 @@ export function CommandQueue() {
                               <p className="text-zinc-500 mt-2">
                                 Exit code: {cmd.result.exit_code} |{' '}
                                 {cmd.result.duration_ms}ms
+                                {cmd.result.usage && (
+                                  <>
+                                    {' '}| CPU:{' '}
+                                    {cmd.result.usage.user_cpu_ms + cmd.result.usage.system_cpu_ms}ms
+                                    {' '}| RSS:{' '}
+                                    {(cmd.result.usage.max_rss_bytes / 1048576).toFixed(1)}MB
+                                  </>
+                                )}
                               </p>
+                              {cmd.result.snapshot_id && (
+                                <div className="mt-2 pt-2 border-t border-zinc-800">
+                                  <div className="flex gap-2">
+                                    <button
+                                      onClick={() => handleSnapshotDiff(cmd.result.snapshot_id)}
+                                      className="flex items-center gap-1 text-zinc-400 hover:text-zinc-200"
+                                    >
+                                      <History className="w-3 h-3" />
+                                      Ver alterações
+                                    </button>
+                                    <button
+                                      onClick={() => handleRestoreSnapshot(cmd.result.snapshot_id)}
+                                      className="flex items-center gap-1 text-zinc-400 hover:text-zinc-200"
+                                    >
+                                      <RotateCcw className="w-3 h-3" />
+                                      Restaurar arquivos
+                                    </button>
+                                  </div>
+                                  {(() => {
+                                    const diffs = snapshotDiffs[cmd.result.snapshot_id];
+                                    if (!diffs) return null;
+                                    if ('error' in diffs) {
+                                      return <p className="text-red-400 mt-1">{diffs.error}</p>;
+                                    }
+                                    return diffs.map((file) => (
+                                      <div key={file.path} className="mt-1">
+                                        <p className="text-zinc-400">
+                                          {file.path} ({file.change})
+                                        </p>
+                                        {file.binary ? (
+                                          <p className="text-zinc-500">Arquivo binário</p>
+                                        ) : (
+                                          file.diff && (
+                                            <pre className="text-zinc-300 whitespace-pre-wrap">
+                                              {file.diff}
+                                            </pre>
+                                          )
+                                        )}
+                                      </div>
+                                    ));
+                                  })()}
+                                </div>
+                              )}
                             </>
                           )}
                         </div>
@@ -221,7 +489,7 @@ export function CommandQueue() {
           ))}
         </AnimatePresence>
 
-        {pendingOnly.length === 0 && completedOrFailed.length === 0 && (
+        {pendingOnly.length === 0 && inFlight.length === 0 && completedOrFailed.length === 0 && (
           <div className="text-center py-8 text-zinc-500">
             <Shield className="w-8 h-8 mx-auto mb-2 opacity-50" />
             <p className="text-sm">Nenhum comando pendente</p>
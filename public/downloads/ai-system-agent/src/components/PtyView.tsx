@@ -0,0 +1,122 @@
+import { useEffect, useRef } from 'react';
+import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import { Terminal } from '@xterm/xterm';
+import { FitAddon } from '@xterm/addon-fit';
+import '@xterm/xterm/css/xterm.css';
+
+interface PtyViewProps {
+  command: string;
+  args: string[];
+  requiresSudo: boolean;
+  approvalToken: string;
+  // Shell session whose directory and variables the program starts with
+  sessionId?: string;
+  onOpened?: (sessionId: string) => void;
+  onExit?: (exitCode: number) => void;
+}
+
+/**
+ * Interactive pseudo-terminal session rendered with xterm.js.
+ * The session is opened here, after the listeners are in place, so no early output is lost.
+ */
+export function PtyView({
+  command,
+  args,
+  requiresSudo,
+  approvalToken,
+  sessionId: shellSessionId,
+  onOpened,
+  onExit,
+}: PtyViewProps) {
+  const containerRef = useRef<HTMLDivElement>(null);
+
+  useEffect(() => {
+    if (!containerRef.current) return;
+
+    const term = new Terminal({
+      fontSize: 13,
+      cursorBlink: true,
+      theme: { background: '#09090b' },
+    });
+    const fit = new FitAddon();
+    term.loadAddon(fit);
+    term.open(containerRef.current);
+    fit.fit();
+
+    let sessionId: string | null = null;
+    let disposed = false;
+    // Output that arrives before open_pty_session returns the id
+    const early: { id: string; data: string }[] = [];
+    const unlisten: (() => void)[] = [];
+
+    const setup = async () => {
+      unlisten.push(
+        await listen<{ id: string; data: string }>('pty-output', (event) => {
+          if (sessionId === null) {
+            early.push(event.payload);
+          } else if (event.payload.id === sessionId) {
+            term.write(event.payload.data);
+          }
+        })
+      );
+      unlisten.push(
+        await listen<{ id: string; exit_code: number }>('pty-exit', (event) => {
+          if (event.payload.id !== sessionId) return;
+          term.write(`\r\n[processo finalizado com código ${event.payload.exit_code}]\r\n`);
+          onExit?.(event.payload.exit_code);
+        })
+      );
+
+      try {
+        const session = await invoke<{ id: string }>('open_pty_session', {
+          command,
+          args,
+          requiresSudo,
+          approvalToken,
+          sessionId: shellSessionId,
+          rows: term.rows,
+          cols: term.cols,
+        });
+        if (disposed) {
+          invoke('close_pty_session', { id: session.id }).catch(() => {});
+          return;
+        }
+        sessionId = session.id;
+        early.filter((chunk) => chunk.id === sessionId).forEach((chunk) => term.write(chunk.data));
+        onOpened?.(session.id);
+      } catch (error: any) {
+        term.write(`\x1b[31mErro: ${error.message || error}\x1b[0m\r\n`);
+      }
+    };
+
+    term.onData((data) => {
+      if (sessionId) {
+        invoke('write_pty_session', { id: sessionId, data }).catch(() => {});
+      }
+    });
+    term.onResize(({ rows, cols }) => {
+      if (sessionId) {
+        invoke('resize_pty_session', { id: sessionId, rows, cols }).catch(() => {});
+      }
+    });
+
+    const observer = new ResizeObserver(() => fit.fit());
+    observer.observe(containerRef.current);
+
+    setup();
+    term.focus();
+
+    return () => {
+      disposed = true;
+      observer.disconnect();
+      unlisten.forEach((fn) => fn());
+      if (sessionId) {
+        invoke('close_pty_session', { id: sessionId }).catch(() => {});
+      }
+      term.dispose();
+    };
+  }, []);
+
+  return <div ref={containerRef} className="flex-1 min-h-0 p-1" />;
+}
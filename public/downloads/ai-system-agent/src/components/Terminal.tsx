@@ -1,5 +1,8 @@
 import { useState, useRef, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
+import { confirm } from '@tauri-apps/api/dialog';
+import { PtyView } from './PtyView';
 import { 
   Terminal as TerminalIcon, 
   X, 
@@ -22,8 +25,24 @@ interface TerminalTab {
   name: string;
   lines: TerminalLine[];
   currentDir: string;
+  // Backend shell session holding the directory, exported variables and history
+  sessionId?: string;
+  // Set for tabs running an interactive program in a pseudo-terminal
+  pty?: {
+    command: string;
+    args: string[];
+    requiresSudo: boolean;
+    approvalToken: string;
+    sessionId?: string;
+  };
 }
 
+// Programs that need a TTY; they open in their own tab instead of running one-shot
+const INTERACTIVE_PROGRAMS = [
+  'top', 'htop', 'btop', 'vim', 'vi', 'nvim', 'nano', 'less', 'more', 'man',
+  'mysql', 'psql', 'sqlite3', 'redis-cli', 'mongosh', 'ssh', 'python3', 'node',
+];
+
 export function IntegratedTerminal() {
   const [tabs, setTabs] = useState<TerminalTab[]>([
     { id: '1', name: 'Terminal 1', lines: [], currentDir: '~' }
@@ -38,6 +57,24 @@ export function IntegratedTerminal() {
   const outputRef = useRef<HTMLDivElement>(null);
 
   const currentTab = tabs.find(t => t.id === activeTab) || tabs[0];
+  const creatingSessions = useRef(new Set<string>());
+
+  useEffect(() => {
+    // Every plain tab gets its own shell session
+    tabs
+      .filter(tab => !tab.pty && !tab.sessionId && !creatingSessions.current.has(tab.id))
+      .forEach(tab => {
+        creatingSessions.current.add(tab.id);
+        invoke<{ id: string; cwd: string }>('create_shell_session', {})
+          .then(session => {
+            setTabs(prev => prev.map(t =>
+              t.id === tab.id ? { ...t, sessionId: session.id, currentDir: session.cwd } : t
+            ));
+          })
+          .catch(error => addLine('error', `Erro ao criar sessão: ${error.message || error}`))
+          .finally(() => creatingSessions.current.delete(tab.id));
+      });
+  }, [tabs]);
 
   useEffect(() => {
     outputRef.current?.scrollTo(0, outputRef.current.scrollHeight);
@@ -66,6 +103,99 @@ export function IntegratedTerminal() {
     }));
   };
 
+  const runPipeline = async (command: string, sessionId?: string) => {
+    const stages = command.split('|').map(part => {
+      const words = part.trim().split(' ').filter(Boolean);
+      return { command: words[0] || '', args: words.slice(1) };
+    });
+    if (stages.some(stage => !stage.command)) {
+      addLine('error', 'Erro: etapa vazia no pipeline');
+      return;
+    }
+    const cwd = sessionId ? currentTab.currentDir : undefined;
+
+    // Same pre-check as single commands, stage by stage
+    let risk: 'low' | 'medium' | 'high' = 'low';
+    const reasons: string[] = [];
+    for (const stage of stages) {
+      const verdict = await invoke<{
+        allowed: boolean;
+        risk: 'low' | 'medium' | 'high';
+        reason: string;
+      }>('evaluate_command', { command: stage.command, args: stage.args, cwd });
+      if (!verdict.allowed) {
+        addLine('error', `⚠️ SEGURANÇA: ${stage.command}: ${verdict.reason}`);
+        return;
+      }
+      if (verdict.risk === 'high' || (verdict.risk === 'medium' && risk === 'low')) {
+        risk = verdict.risk;
+      }
+      reasons.push(`${stage.command}: ${verdict.reason}`);
+    }
+
+    if (risk !== 'low') {
+      const confirmed = await confirm(
+        `${command}\n\nRisco: ${risk.toUpperCase()}\n${reasons.join('\n')}`,
+        { title: 'Confirmar pipeline', type: 'warning' }
+      );
+      if (!confirmed) {
+        addLine('system', 'Comando cancelado.');
+        return;
+      }
+    }
+
+    const approval = await invoke<{ token: string }>('request_pipeline_approval', {
+      stages,
+      sessionId,
+    });
+
+    let commandId: string | null = null;
+    const unlistenStarted = await listen<{ id: string; command: string }>('command-started', (event) => {
+      if (commandId === null && event.payload.command === stages[0].command) {
+        commandId = event.payload.id;
+      }
+    });
+    const unlistenOutput = await listen<{ id: string; stream: string; data: string }>('command-output', (event) => {
+      if (event.payload.id !== commandId) return;
+      addLine(event.payload.stream === 'stderr' ? 'error' : 'output', event.payload.data);
+    });
+
+    let result;
+    try {
+      result = await invoke<{
+        success: boolean;
+        truncated: boolean;
+        output_handle: string | null;
+        stages: { command: string; success: boolean; exit_code: number }[];
+      }>('execute_pipeline', {
+        stages,
+        approvalToken: approval.token,
+        sessionId,
+      });
+    } finally {
+      unlistenStarted();
+      unlistenOutput();
+    }
+
+    result.stages.forEach((stage, i) => {
+      if (!stage.success) {
+        addLine('error', `Etapa ${i + 1} (${stage.command}) terminou com código: ${stage.exit_code}`);
+      }
+    });
+    reportTruncated(result);
+  };
+
+  // Output past the inline cap is not streamed; it stays in a file readable by page
+  const reportTruncated = (result: { truncated: boolean; output_handle: string | null }) => {
+    if (!result.truncated) return;
+    addLine(
+      'system',
+      result.output_handle
+        ? `Saída truncada. A saída completa foi salva (read_command_output: ${result.output_handle}).`
+        : 'Saída truncada.'
+    );
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     if (!input.trim() || isExecuting) return;
@@ -92,6 +222,13 @@ Comandos disponíveis:
   clear     - Limpa o terminal
   help      - Mostra esta ajuda
   exit      - Fecha esta aba do terminal
+  cd, pushd, popd, dirs, pwd
+            - Navegam entre diretórios desta aba
+  export, unset
+            - Definem variáveis para os próximos comandos desta aba
+  history   - Mostra os comandos desta aba
+
+Programas interativos (top, vim, mysql, ...) abrem em uma nova aba.
   
 Comandos do sistema (requerem aprovação):
   apt, docker, systemctl, nginx, etc.
@@ -113,32 +250,116 @@ Comandos do sistema (requerem aprovação):
     setIsExecuting(true);
 
     try {
+      const sessionId = currentTab.sessionId;
+
+      // `a | b` runs as a pipeline: every stage is checked and no shell is involved
+      if (command.includes('|')) {
+        await runPipeline(command, sessionId);
+        return;
+      }
+
       // Parse command and args
-      const parts = command.split(' ');
+      const parts = command.split(' ').filter(Boolean);
       const cmd = parts[0];
       const args = parts.slice(1);
 
-      // Check if needs sudo
-      const needsSudo = ['apt', 'apt-get', 'systemctl', 'service'].includes(cmd);
+      // Builtins like cd and export only change the session
+      let cwd: string | undefined;
+      if (sessionId) {
+        const input = await invoke<{
+          builtin: boolean;
+          output: string;
+          session: { cwd: string };
+        }>('shell_session_input', { id: sessionId, command: cmd, args });
+        cwd = input.session.cwd;
+        setTabs(prev => prev.map(tab =>
+          tab.id === activeTab ? { ...tab, currentDir: input.session.cwd } : tab
+        ));
+        if (input.builtin) {
+          if (input.output) addLine('output', input.output);
+          return;
+        }
+      }
 
-      // Execute command
-      const result = await invoke<{
-        success: boolean;
-        stdout: string;
-        stderr: string;
-        exit_code: number;
-      }>('execute_command', {
+      // Ask the backend whether the command needs sudo and how risky it is
+      const verdict = await invoke<{
+        allowed: boolean;
+        risk: 'low' | 'medium' | 'high';
+        requires_sudo: boolean;
+        reason: string;
+      }>('evaluate_command', { command: cmd, args, cwd });
+      if (!verdict.allowed) {
+        addLine('error', `⚠️ SEGURANÇA: ${verdict.reason}`);
+        return;
+      }
+      const needsSudo = verdict.requires_sudo;
+
+      if (verdict.risk !== 'low' || needsSudo) {
+        const confirmed = await confirm(
+          `${command}\n\nRisco: ${verdict.risk.toUpperCase()}${needsSudo ? ' (sudo)' : ''}\n${verdict.reason}`,
+          { title: 'Confirmar comando', type: 'warning' }
+        );
+        if (!confirmed) {
+          addLine('system', 'Comando cancelado.');
+          return;
+        }
+      }
+
+      const approval = await invoke<{ token: string }>('request_approval', {
         command: cmd,
         args,
-        requires_sudo: needsSudo,
-        user_confirmed: true // In real app, would show confirmation dialog
+        requiresSudo: needsSudo,
+        sessionId,
       });
 
-      if (result.stdout) {
-        addLine('output', result.stdout);
+      if (INTERACTIVE_PROGRAMS.includes(cmd.split('/').pop() || cmd)) {
+        const newId = Date.now().toString();
+        setTabs(prev => [...prev, {
+          id: newId,
+          name: cmd,
+          lines: [],
+          currentDir: currentTab.currentDir,
+          pty: { command: cmd, args, requiresSudo: needsSudo, approvalToken: approval.token, sessionId },
+        }]);
+        setActiveTab(newId);
+        return;
       }
-      if (result.stderr) {
-        addLine('error', result.stderr);
+
+      // Stream output as it arrives; chunks are tagged with the CommandResult id
+      let commandId: string | null = null;
+      const unlistenStarted = await listen<{ id: string; command: string }>('command-started', (event) => {
+        if (commandId === null && event.payload.command === cmd) {
+          commandId = event.payload.id;
+        }
+      });
+      const unlistenOutput = await listen<{ id: string; stream: string; data: string }>('command-output', (event) => {
+        if (event.payload.id !== commandId) return;
+        addLine(event.payload.stream === 'stderr' ? 'error' : 'output', event.payload.data);
+      });
+
+      let result;
+      try {
+        result = await invoke<{
+          success: boolean;
+          exit_code: number;
+          truncated: boolean;
+          output_handle: string | null;
+          sandboxed: boolean;
+        }>('execute_command_stream', {
+          command: cmd,
+          args,
+          requiresSudo: needsSudo,
+          approvalToken: approval.token,
+          sessionId,
+        });
+      } finally {
+        unlistenStarted();
+        unlistenOutput();
+      }
+
+      reportTruncated(result);
+      if (result.sandboxed) {
+        addLine('system', 'Executado no sandbox somente leitura (sem rede e sem escrita).');
       }
       if (!result.success) {
         addLine('error', `Processo terminou com código: ${result.exit_code}`);
@@ -184,7 +405,12 @@ Comandos do sistema (requerem aprovação):
 
   const closeTab = (tabId: string) => {
     if (tabs.length <= 1) return;
-    
+
+    const closing = tabs.find(t => t.id === tabId);
+    if (closing?.sessionId && !closing.pty) {
+      invoke('close_shell_session', { id: closing.sessionId }).catch(() => {});
+    }
+
     setTabs(prev => prev.filter(t => t.id !== tabId));
     if (activeTab === tabId) {
       const remaining = tabs.filter(t => t.id !== tabId);
@@ -246,11 +472,27 @@ Comandos do sistema (requerem aprovação):
         </div>
       </div>
 
+      {/* Interactive sessions stay mounted while hidden, so switching tabs keeps them alive */}
+      {tabs.filter(tab => tab.pty).map(tab => (
+        <div
+          key={tab.id}
+          className={`flex-1 min-h-0 flex-col ${activeTab === tab.id ? 'flex' : 'hidden'}`}
+        >
+          <PtyView
+            command={tab.pty!.command}
+            args={tab.pty!.args}
+            requiresSudo={tab.pty!.requiresSudo}
+            approvalToken={tab.pty!.approvalToken}
+            sessionId={tab.pty!.sessionId}
+          />
+        </div>
+      ))}
+
       {/* Terminal Output */}
       <div
         ref={outputRef}
         onClick={() => inputRef.current?.focus()}
-        className="flex-1 overflow-y-auto p-3 font-mono text-sm cursor-text"
+        className={`flex-1 overflow-y-auto p-3 font-mono text-sm cursor-text ${currentTab?.pty ? 'hidden' : ''}`}
       >
         {currentTab?.lines.map(line => (
           <div key={line.id} className={`${getLineColor(line.type)} whitespace-pre-wrap break-all`}>
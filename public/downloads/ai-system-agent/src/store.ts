@@ -15,6 +15,20 @@ interface Message {
   timestamp: Date;
 }
 
+interface RiskAssessment {
+  allowed: boolean;
+  risk_level: 'low' | 'medium' | 'high';
+  requires_sudo: boolean;
+  reason: string;
+}
+
+interface Simulation {
+  kind: 'apt' | 'pacman' | 'configtest' | 'compose';
+  command: string;
+  args: string[];
+  description: string;
+}
+
 interface SuggestedCommand {
   command: string;
   args: string[];
@@ -22,12 +36,20 @@ interface SuggestedCommand {
   requires_sudo: boolean;
   risk_level: 'low' | 'medium' | 'high';
   explanation: string;
+  verify: boolean;
+  local_assessment: RiskAssessment;
+  disagreements: string[];
+  simulation: Simulation | null;
 }
 
+// An entry of the backend-owned command queue
 interface PendingCommand extends SuggestedCommand {
   id: string;
-  status: 'pending' | 'approved' | 'rejected' | 'executing' | 'completed' | 'failed';
-  result?: any;
+  status: 'pending' | 'approved' | 'executing' | 'completed' | 'failed';
+  result: any | null;
+  error: string | null;
+  queued_at: string;
+  updated_at: string;
 }
 
 interface Config {
@@ -50,12 +72,9 @@ interface Store {
   addMessage: (message: Omit<Message, 'id' | 'timestamp'>) => void;
   clearMessages: () => void;
 
-  // Commands
+  // Commands, mirrored from the backend queue
   pendingCommands: PendingCommand[];
-  addPendingCommands: (commands: SuggestedCommand[]) => void;
-  updateCommandStatus: (id: string, status: PendingCommand['status'], result?: any) => void;
-  removeCommand: (id: string) => void;
-  clearCommands: () => void;
+  setPendingCommands: (commands: PendingCommand[]) => void;
 
   // Config
   config: Config;
@@ -88,28 +107,7 @@ export const useStore = create<Store>((set) => ({
 
   // Commands
   pendingCommands: [],
-  addPendingCommands: (commands) =>
-    set((state) => ({
-      pendingCommands: [
-        ...state.pendingCommands,
-        ...commands.map((cmd) => ({
-          ...cmd,
-          id: crypto.randomUUID(),
-          status: 'pending' as const,
-        })),
-      ],
-    })),
-  updateCommandStatus: (id, status, result) =>
-    set((state) => ({
-      pendingCommands: state.pendingCommands.map((cmd) =>
-        cmd.id === id ? { ...cmd, status, result } : cmd
-      ),
-    })),
-  removeCommand: (id) =>
-    set((state) => ({
-      pendingCommands: state.pendingCommands.filter((cmd) => cmd.id !== id),
-    })),
-  clearCommands: () => set({ pendingCommands: [] }),
+  setPendingCommands: (commands) => set({ pendingCommands: commands }),
 
   // Config
   config: {